@@ -0,0 +1,21 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use ndarray::Array2;
+use sitk_registration_sys::Transform;
+use std::hint::black_box;
+
+fn transform_coordinates(c: &mut Criterion) {
+    let t = Transform::new(
+        [1.1, 0.2, -0.3, 0.9, 3.5, -2.25],
+        [400.0, 300.0],
+        [600, 800],
+    );
+    let coordinates = Array2::from_shape_fn((1_000_000, 2), |(i, j)| {
+        ((i * (j + 3)) % 1013) as f64 * 0.37
+    });
+    c.bench_function("transform_coordinates 1e6 points", |b| {
+        b.iter(|| t.transform_coordinates(black_box(&coordinates)).unwrap())
+    });
+}
+
+criterion_group!(benches, transform_coordinates);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use ndarray::Array2;
+use sitk_registration_sys::Transform;
+use std::hint::black_box;
+
+fn transform_image_identity(c: &mut Criterion) {
+    let image = Array2::from_shape_fn((1024, 1024), |(i, j)| ((i * 7 + j * 13) % 256) as u8);
+    let identity = Transform::new([1.0, 0.0, 0.0, 1.0, 0.0, 0.0], [511.5, 511.5], [1024, 1024]);
+    // almost the identity, but not quite, so the adapter is called, the difference between the
+    // two is the speedup of skipping the adapter for the identity
+    let almost = Transform::new(
+        [1.0, 0.0, 0.0, 1.0, 1e-9, 0.0],
+        [511.5, 511.5],
+        [1024, 1024],
+    );
+    c.bench_function("transform_image_bspline identity 1024x1024", |b| {
+        b.iter(|| identity.transform_image_bspline(black_box(&image)).unwrap())
+    });
+    c.bench_function("transform_image_bspline almost identity 1024x1024", |b| {
+        b.iter(|| almost.transform_image_bspline(black_box(&image)).unwrap())
+    });
+}
+
+criterion_group!(benches, transform_image_identity);
+criterion_main!(benches);
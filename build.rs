@@ -1,46 +1,84 @@
 use cmake::Config;
 use git2::Repository;
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// true if build_dir contains a completed SimpleITK SuperBuild, including elastix if required
+fn is_built(build_dir: &Path, elastix: bool) -> bool {
+    let libraries = std::fs::read_dir(build_dir.join("SimpleITK-build").join("lib"))
+        .map(|entries| {
+            entries.flatten().any(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("libSimpleITK")
+            })
+        })
+        .unwrap_or(false);
+    libraries && (!elastix || build_dir.join("Elastix-build").exists())
+}
+
+/// clone and build SimpleITK next to the target directory, unless that was done before,
+/// returns the build directory
+fn build_sitk(out_dir: &Path, elastix: bool) -> PathBuf {
+    let mut target_dir = out_dir.to_path_buf();
+    while target_dir.file_name() != Some(OsStr::new("target")) {
+        if !target_dir.pop() {
+            panic!("Could not find target directory");
+        }
+    }
+
+    let sitk_dir = if let Some(d) = target_dir.parent() {
+        d.join("sitk").to_path_buf()
+    } else {
+        target_dir.join("sitk")
+    };
+    if !sitk_dir.exists() {
+        Repository::clone("https://github.com/SimpleITK/SimpleITK.git", &sitk_dir)
+            .expect("unable to clone sitk");
+    }
+
+    let sitk_build_dir = sitk_dir.join("build");
+    // an existing build without elastix is reconfigured when elastix is requested
+    if !is_built(&sitk_build_dir, elastix) {
+        println!("cargo::warning=Simple ITK; this will take a long time...");
+        Config::new(sitk_dir.join("SuperBuild"))
+            .out_dir(&sitk_dir)
+            .no_build_target(true)
+            .define("BUILD_TESTING", "OFF")
+            .define("WRAP_CSHARP", "OFF")
+            .define("WRAP_JAVA", "OFF")
+            .define("WRAP_LUA", "OFF")
+            .define("WRAP_R", "OFF")
+            .define("WRAP_RUBY", "OFF")
+            .define("WRAP_TCL", "OFF")
+            .define("WRAP_PYTHON", "OFF")
+            .define("WRAP_DEFAULT", "OFF")
+            .define("SimpleITK_USE_ELASTIX", if elastix { "ON" } else { "OFF" })
+            .build();
+    }
+    sitk_build_dir
+}
 
 fn main() {
     if std::env::var("DOCS_RS").is_err() {
         let out_dir = PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR is undefined"));
-        let mut target_dir = out_dir.clone();
-        while target_dir.file_name() != Some(OsStr::new("target")) {
-            if !target_dir.pop() {
-                panic!("Could not find target directory");
-            }
-        }
+        let elastix = std::env::var("CARGO_FEATURE_ELASTIX").is_ok();
 
-        let sitk_dir = if let Some(d) = target_dir.parent() {
-            d.join("sitk").to_path_buf()
-        } else {
-            target_dir.join("sitk")
+        // a SuperBuild build directory, containing SimpleITK-build, ITK-build and Elastix-build
+        let sitk_build_dir = match std::env::var("SITK_PREBUILT_DIR") {
+            Ok(dir) if is_built(Path::new(&dir), elastix) => PathBuf::from(dir),
+            Ok(dir) => {
+                println!(
+                    "cargo::warning=SITK_PREBUILT_DIR={} does not contain a SimpleITK build{}, building from source",
+                    dir,
+                    if elastix { " with elastix" } else { "" }
+                );
+                build_sitk(&out_dir, elastix)
+            }
+            Err(_) => build_sitk(&out_dir, elastix),
         };
-        if !sitk_dir.exists() {
-            Repository::clone("https://github.com/SimpleITK/SimpleITK.git", &sitk_dir)
-                .expect("unable to clone sitk");
-        }
 
-        let sitk_build_dir = sitk_dir.join("build");
-        if !sitk_build_dir.exists() {
-            println!("cargo::warning=Simple ITK; this will take a long time...");
-            Config::new(sitk_dir.join("SuperBuild"))
-                .out_dir(&sitk_dir)
-                .no_build_target(true)
-                .define("BUILD_TESTING", "OFF")
-                .define("WRAP_CSHARP", "OFF")
-                .define("WRAP_JAVA", "OFF")
-                .define("WRAP_LUA", "OFF")
-                .define("WRAP_R", "OFF")
-                .define("WRAP_RUBY", "OFF")
-                .define("WRAP_TCL", "OFF")
-                .define("WRAP_PYTHON", "OFF")
-                .define("WRAP_DEFAULT", "OFF")
-                .define("SimpleITK_USE_ELASTIX", "ON")
-                .build();
-        }
         println!(
             "cargo::rustc-env=CMAKE_INSTALL_PREFIX={}",
             out_dir.display()
@@ -50,6 +88,7 @@ fn main() {
             .define("Elastix_DIR", sitk_build_dir.join("Elastix-build"))
             .define("ITK_DIR", sitk_build_dir.join("ITK-build"))
             .define("SimpleITK_DIR", sitk_build_dir.join("SimpleITK-build"))
+            .define("SITK_ADAPTER_ELASTIX", if elastix { "ON" } else { "OFF" })
             .define("CMAKE_INSTALL_PREFIX", out_dir)
             .build();
         println!("cargo::rustc-link-arg=-Wl,-rpath,{}", path.display());
@@ -57,5 +96,6 @@ fn main() {
         println!("cargo::rustc-link-lib=dylib=sitk_adapter");
         println!("cargo::rerun-if-changed=build.rs");
         println!("cargo::rerun-if-changed=cpp");
+        println!("cargo::rerun-if-env-changed=SITK_PREBUILT_DIR");
     }
 }
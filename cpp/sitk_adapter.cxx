@@ -1,5 +1,14 @@
 #include <SimpleITK.h>
 #include <sitkImageOperators.h>
+#include <itkAffineTransform.h>
+#include <itkImageRegistrationMethodv4.h>
+#include <itkMattesMutualInformationImageToImageMetricv4.h>
+#include <itkMeanSquaresImageToImageMetricv4.h>
+#include <itkObjectToObjectMultiMetricv4.h>
+#include <itkRegistrationParameterScalesFromPhysicalShift.h>
+#include <itkRegularStepGradientDescentOptimizerv4.h>
+#include <chrono>
+#include <cmath>
 #include <cstring>
 #include <filesystem>
 
@@ -24,6 +33,36 @@ std::string gen_random(const int len) {
 }
 
 
+thread_local string last_error;
+
+
+extern "C" const char*
+sitk_adapter_last_error() {
+  return last_error.c_str();
+}
+
+
+void
+set_error(const std::exception &exc, int* status) {
+  last_error = exc.what();
+  *status = 1;
+}
+
+
+extern "C" const char*
+sitk_adapter_itk_version() {
+  static const string version = sitk::Version::ITKVersionString();
+  return version.c_str();
+}
+
+
+extern "C" const char*
+sitk_adapter_simpleitk_version() {
+  static const string version = sitk::Version::VersionString();
+  return version.c_str();
+}
+
+
 template <typename T> 
 sitk::Image make_image(
   unsigned int width,
@@ -67,25 +106,138 @@ sitk::Image make_image(
 }
 
 
+
+template <typename T>
+sitk::Image make_vector_image(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  T* image,
+  sitk::PixelIDValueEnum id
+) {
+  // the channels of each pixel are adjacent in memory, as in a VectorImage buffer
+  sitk::Image im(width, height, id, channels);
+  memcpy(im.GetBufferAsVoid(), image, width * height * channels * sizeof(T));
+  return im;
+}
+
+
+template <typename T>
+sitk::Image make_image_3d(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  T* image,
+  sitk::PixelIDValueEnum id
+) {
+  sitk::Image im(width, height, depth, id);
+  memcpy(im.GetBufferAsVoid(), image, width * height * depth * sizeof(T));
+  return im;
+}
+
+// sitk has no half precision pixel type, f16 images are converted to and from float
+float half_to_float(uint16_t h) {
+  uint32_t sign = ((uint32_t) (h & 0x8000)) << 16;
+  uint32_t exponent = (h >> 10) & 0x1f;
+  uint32_t mantissa = h & 0x3ff;
+  uint32_t bits;
+  if (exponent == 0) {
+    float f = ldexp((float) mantissa, -24);
+    return (sign == 0) ? f : -f;
+  } else if (exponent == 31) {
+    bits = sign | 0x7f800000 | (mantissa << 13);
+  } else {
+    bits = sign | ((exponent + 112) << 23) | (mantissa << 13);
+  }
+  float f;
+  memcpy(&f, &bits, 4);
+  return f;
+}
+
+
+uint16_t float_to_half(float f) {
+  uint32_t bits;
+  memcpy(&bits, &f, 4);
+  uint16_t sign = (bits >> 16) & 0x8000;
+  if (isnan(f)) {
+    return sign | 0x7e00;
+  }
+  float a = fabs(f);
+  if (a >= 65520.0f) {
+    return sign | 0x7c00;
+  }
+  if (a < 6.103515625e-05f) {
+    // subnormal, rounds to nearest even
+    return sign | (uint16_t) nearbyint(a * 16777216.0f);
+  }
+  uint32_t exponent = ((bits >> 23) & 0xff) - 112;
+  uint32_t mantissa = bits & 0x7fffff;
+  uint32_t h = (exponent << 10) | (mantissa >> 13);
+  uint32_t remainder = mantissa & 0x1fff;
+  if ((remainder > 0x1000) || ((remainder == 0x1000) && ((h & 1) == 1))) {
+    h++;
+  }
+  return sign | (uint16_t) h;
+}
+
+
+vector<float> half_to_float(uint16_t* image, unsigned int n) {
+  vector<float> f(n);
+  for (unsigned int i = 0; i < n; i++) {
+    f[i] = half_to_float(image[i]);
+  }
+  return f;
+}
+
+
+// interpolator: 0: nearest neighbor, n + 1: bspline of order n, from 0 to 5, a bspline of order
+// 0 is nearest neighbor interpolation
+sitk::InterpolatorEnum
+interpolator_enum(unsigned int interpolator) {
+  switch (interpolator) {
+    case 0:
+    case 1:
+      return sitk::sitkNearestNeighbor;
+    case 2:
+      return sitk::sitkBSpline1;
+    case 3:
+      return sitk::sitkBSpline2;
+    case 4:
+      return sitk::sitkBSpline3;
+    case 5:
+      return sitk::sitkBSpline4;
+    case 6:
+      return sitk::sitkBSpline5;
+    default:
+      throw std::invalid_argument("the order of the bspline must be 0 to 5");
+  }
+}
+
+
 sitk::Image
 interp(
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   sitk::Image image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi
 ) {
-  try {
-    vector<double> matrix = {transform[0], transform[1], transform[2], transform[3]};
-    vector<double> translation = {transform[4], transform[5]};
-    vector<double> ori = {origin[0], origin[1]};
-    sitk::AffineTransform t(matrix, translation, ori);
-    sitk::InterpolatorEnum interpolator = (bspline_or_nn == false) ? sitk::sitkBSpline : sitk::sitkNearestNeighbor;
-    image = sitk::Resample(image, t, interpolator);
-    return image;
-  } catch (const std::exception &exc) {
-    cerr << exc.what();
-    return image;
-  }
+  // roi: x, y, width, height of the output region
+  image.SetSpacing({spacing[0], spacing[1]});
+  image.SetDirection({direction[0], direction[1], direction[2], direction[3]});
+  vector<double> matrix = {transform[0], transform[1], transform[2], transform[3]};
+  vector<double> translation = {transform[4], transform[5]};
+  vector<double> ori = {origin[0], origin[1]};
+  sitk::AffineTransform t(matrix, translation, ori);
+  sitk::InterpolatorEnum interp_enum = interpolator_enum(interpolator);
+  vector<uint32_t> size = {roi[2], roi[3]};
+  vector<double> output_origin = image.TransformIndexToPhysicalPoint({(int64_t) roi[0], (int64_t) roi[1]});
+  image = sitk::Resample(
+    image, size, t, interp_enum, output_origin, image.GetSpacing(), image.GetDirection()
+  );
+  return image;
 }
 
 
@@ -95,13 +247,22 @@ interp_u8(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   uint8_t** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  uint8_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt8);
-  im = interp(transform, origin, im, bspline_or_nn);
-  uint8_t* c = im.GetBufferAsUInt8();
-  memcpy(*image, c, width * height);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt8);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    uint8_t* c = im.GetBufferAsUInt8();
+    memcpy(*output, c, roi[2] * roi[3]);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -110,13 +271,22 @@ interp_i8(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   int8_t** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  int8_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt8);
-  im = interp(transform, origin, im, bspline_or_nn);
-  int8_t* c = im.GetBufferAsInt8();
-  memcpy(*image, c, width * height);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt8);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    int8_t* c = im.GetBufferAsInt8();
+    memcpy(*output, c, roi[2] * roi[3]);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -125,13 +295,22 @@ interp_u16(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   uint16_t** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  uint16_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt16);
-  im = interp(transform, origin, im, bspline_or_nn);
-  uint16_t* c = im.GetBufferAsUInt16();
-  memcpy(*image, c, width * height * 2);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt16);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    uint16_t* c = im.GetBufferAsUInt16();
+    memcpy(*output, c, roi[2] * roi[3] * 2);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -140,13 +319,22 @@ interp_i16(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   int16_t** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  int16_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt16);
-  im = interp(transform, origin, im, bspline_or_nn);
-  int16_t* c = im.GetBufferAsInt16();
-  memcpy(*image, c, width * height * 2);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt16);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    int16_t* c = im.GetBufferAsInt16();
+    memcpy(*output, c, roi[2] * roi[3] * 2);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -155,13 +343,22 @@ interp_u32(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   uint32_t** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  uint32_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt32);
-  im = interp(transform, origin, im, bspline_or_nn);
-  uint32_t* c = im.GetBufferAsUInt32();
-  memcpy(*image, c, width * height * 4);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt32);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    uint32_t* c = im.GetBufferAsUInt32();
+    memcpy(*output, c, roi[2] * roi[3] * 4);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -170,13 +367,22 @@ interp_i32(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   int32_t** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  int32_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt32);
-  im = interp(transform, origin, im, bspline_or_nn);
-  int32_t* c = im.GetBufferAsInt32();
-  memcpy(*image, c, width * height * 4);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt32);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    int32_t* c = im.GetBufferAsInt32();
+    memcpy(*output, c, roi[2] * roi[3] * 4);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -185,13 +391,22 @@ interp_u64(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   uint64_t** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  uint64_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt64);
-  im = interp(transform, origin, im, bspline_or_nn);
-  uint64_t* c = im.GetBufferAsUInt64();
-  memcpy(*image, c, width * height * 8);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt64);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    uint64_t* c = im.GetBufferAsUInt64();
+    memcpy(*output, c, roi[2] * roi[3] * 8);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -200,13 +415,22 @@ interp_i64(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   int64_t** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  int64_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt64);
-  im = interp(transform, origin, im, bspline_or_nn);
-  int64_t* c = im.GetBufferAsInt64();
-  memcpy(*image, c, width * height * 8);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt64);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    int64_t* c = im.GetBufferAsInt64();
+    memcpy(*output, c, roi[2] * roi[3] * 8);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -215,13 +439,22 @@ interp_f32(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   float** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  float** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkFloat32);
-  im = interp(transform, origin, im, bspline_or_nn);
-  float* c = im.GetBufferAsFloat();
-  memcpy(*image, c, width * height * 4);
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkFloat32);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    float* c = im.GetBufferAsFloat();
+    memcpy(*output, c, roi[2] * roi[3] * 4);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -230,13 +463,120 @@ interp_f64(
   unsigned int height,
   double* transform,
   double* origin,
+  double* spacing,
+  double* direction,
   double** image,
-  bool bspline_or_nn
+  unsigned int interpolator,
+  unsigned int* roi,
+  double** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkFloat64);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    double* c = im.GetBufferAsDouble();
+    memcpy(*output, c, roi[2] * roi[3] * 8);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_f16(
+  unsigned int width,
+  unsigned int height,
+  double* transform,
+  double* origin,
+  double* spacing,
+  double* direction,
+  uint16_t** image,
+  unsigned int interpolator,
+  unsigned int* roi,
+  uint16_t** output,
+  int* status
 ) {
-  sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkFloat64);
-  im = interp(transform, origin, im, bspline_or_nn);
-  double* c = im.GetBufferAsDouble();
-  memcpy(*image, c, width * height * 8);
+  try {
+    vector<float> f = half_to_float(*image, width * height);
+    sitk::Image im = make_image(width, height, f.data(), sitk::PixelIDValueEnum::sitkFloat32);
+    im = interp(transform, origin, spacing, direction, im, interpolator, roi);
+    float* c = im.GetBufferAsFloat();
+    for (unsigned int i = 0; i < roi[2] * roi[3]; i++) {
+      (*output)[i] = float_to_half(c[i]);
+    }
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+
+// called with the value of the metric after each iteration of the optimizer
+typedef void (*metric_callback)(void* data, double metric);
+
+
+// called after each iteration of the optimizer, returns true to stop the optimizer
+typedef bool (*stop_callback)(void* data);
+
+
+// how the optimizer of reg2 ended, iterations: the total over all resolutions, timed_out: whether
+// the time limit stopped the optimizer
+struct registration_info {
+    double final_metric;
+    unsigned int iterations;
+    char stop_condition[256];
+    bool timed_out;
+};
+
+
+// options for registration, zero means the default
+// metric: 1: mean squares, 2: normalized correlation, 3: mutual information
+// optimizer: 1: regular step gradient descent, 2: gradient descent line search,
+// 3: conjugate gradient line search, any of these uses reg2 instead of elastix
+// seed: seed of the random sampler, only used if fixed_seed is true
+// convergence_tolerance, min_step_length: only used by reg2
+// smoothing_sigma: blur both images with a gaussian before registration
+// histogram_match: map the intensities of moving onto the histogram of fixed before registration
+// histogram_levels: number of histogram bins used to match, 256 if 0
+// normalization: 1: subtract the mean and divide by the standard deviation, 2: rescale to [0, 1],
+// both images are normalized independently, in float
+// estimate_scales: how reg2 estimates the optimizer scales, 1: jacobian, 2: index shift,
+// 3: physical shift (the default), 4: no estimation, all scales are 1, any of these uses reg2
+// instead of elastix
+// time_limit: seconds after which reg2 stops the optimizer, any of these uses reg2 instead of
+// elastix
+struct registration_options {
+    unsigned int max_iterations;
+    unsigned int pyramid_levels;
+    double sampling_fraction;
+    unsigned int mi_bins;
+    unsigned int metric;
+    unsigned int optimizer;
+    bool fixed_seed;
+    uint64_t seed;
+    double convergence_tolerance;
+    double min_step_length;
+    double max_step_length;
+    double smoothing_sigma;
+    bool histogram_match;
+    unsigned int histogram_levels;
+    unsigned int estimate_scales;
+    unsigned int normalization;
+    double time_limit;
+};
+
+
+void
+set_metric(sitk::ImageRegistrationMethod &R, registration_options* options) {
+    // the metric of reg2, mattes mutual information unless given otherwise
+    unsigned int metric = (options == nullptr) ? 0 : options->metric;
+    if (metric == 1) {
+        R.SetMetricAsMeanSquares();
+    } else if (metric == 2) {
+        R.SetMetricAsCorrelation();
+    } else if ((options != nullptr) && (options->mi_bins > 0)) {
+        R.SetMetricAsMattesMutualInformation(options->mi_bins);
+    } else {
+        R.SetMetricAsMattesMutualInformation();
+    }
 }
 
 
@@ -244,85 +584,511 @@ void
 reg2(
     sitk::Image fixed,
     sitk::Image moving,
-    bool t_or_a,
-    double** transform
+    unsigned int mode,
+    double* center,
+    double** transform,
+    registration_options* options,
+    double* initial = nullptr,
+    metric_callback callback = nullptr,
+    void* data = nullptr,
+    sitk::Image* fixed_mask = nullptr,
+    sitk::Image* moving_mask = nullptr,
+    registration_info* info = nullptr,
+    stop_callback stop = nullptr
 ) {
-    try {
-        string kind = (t_or_a == false) ? "translation" : "affine";
+    // registration with sitk instead of elastix, for transforms with fixed parameters,
+    // or when the metric needs to be observed or the optimizer needs a starting point
+    // initial: parameters of the affine transform to start from, only used in mode 1
+    // 0: translation, 1: affine, 2: rigid, 3: similarity, 4: rotation, 5: isotropic scale,
+    // 6: anisotropic scale
+    vector<double> c;
+    if (center == nullptr) {
+        c = fixed.TransformContinuousIndexToPhysicalPoint(
+            {(fixed.GetWidth() - 1) / 2.0, (fixed.GetHeight() - 1) / 2.0}
+        );
+    } else {
+        c = {center[0], center[1]};
+    }
 
-        sitk::ImageRegistrationMethod R;
-        R.SetMetricAsMattesMutualInformation();
-        const double       maxStep = 4.0;
-        const double       minStep = 0.01;
-        const unsigned int numberOfIterations = 200;
-        const double       relaxationFactor = 0.5;
-    //     R.SetOptimizerAsLBFGSB(maxStep, minStep, numberOfIterations, relaxationFactor);
-        R.SetOptimizerAsRegularStepGradientDescent(maxStep, minStep, numberOfIterations, relaxationFactor);
-    //     R.SetOptimizerAsLBFGS2();
-        vector<double> matrix = {1.0, 0.0, 0.0, 1.0};
-        vector<double> translation = {0., 0.};
-        vector<double> origin = {399.5, 299.5};
-        R.SetInitialTransform(sitk::AffineTransform(matrix, translation, origin));
-        R.SetInterpolator(sitk::sitkBSpline);
-        sitk::Transform outTx = R.Execute(fixed, moving);
-        vector<double> t = outTx.GetParameters();
-        for (int i = 0; i < t.size(); i++) {
-            cout << t[i] << " ";
-            (*transform)[i] = t[i];
+    sitk::ImageRegistrationMethod R;
+    set_metric(R, options);
+    double             maxStep = 4.0;
+    double             minStep = 0.01;
+    unsigned int       numberOfIterations = 200;
+    const double       relaxationFactor = 0.5;
+    unsigned int       levels = 3;
+    double             gradientTolerance = 1e-4;
+    double             convergenceTolerance = 1e-6;
+    if (options != nullptr) {
+        if (options->max_step_length > 0) {
+            maxStep = options->max_step_length;
+        }
+        if (options->min_step_length > 0) {
+            minStep = options->min_step_length;
+        }
+        if (options->convergence_tolerance > 0) {
+            gradientTolerance = options->convergence_tolerance;
+            convergenceTolerance = options->convergence_tolerance;
+        }
+        if (options->max_iterations > 0) {
+            numberOfIterations = options->max_iterations;
+        }
+        if (options->pyramid_levels > 0) {
+            levels = options->pyramid_levels;
+        }
+        if (options->sampling_fraction > 0) {
+            // a fixed seed keeps the result reproducible
+            unsigned int seed = options->fixed_seed ? (unsigned int) options->seed : 42;
+            R.SetMetricSamplingStrategy(R.RANDOM);
+            R.SetMetricSamplingPercentage(options->sampling_fraction, seed);
+        }
+    }
+//     R.SetOptimizerAsLBFGSB(maxStep, minStep, numberOfIterations, relaxationFactor);
+    unsigned int optimizer = (options == nullptr) ? 0 : options->optimizer;
+    if (optimizer == 2) {
+        R.SetOptimizerAsGradientDescentLineSearch(1.0, numberOfIterations, convergenceTolerance, 10);
+    } else if (optimizer == 3) {
+        R.SetOptimizerAsConjugateGradientLineSearch(1.0, numberOfIterations, convergenceTolerance, 10);
+    } else {
+        R.SetOptimizerAsRegularStepGradientDescent(
+            maxStep, minStep, numberOfIterations, relaxationFactor, gradientTolerance
+        );
+    }
+//     R.SetOptimizerAsLBFGS2();
+    unsigned int scales = (options == nullptr) ? 0 : options->estimate_scales;
+    if (scales == 1) {
+        R.SetOptimizerScalesFromJacobian();
+    } else if (scales == 2) {
+        R.SetOptimizerScalesFromIndexShift();
+    } else if (scales != 4) {
+        R.SetOptimizerScalesFromPhysicalShift();
+    }
+    // halve the resolution for each level: {4, 2, 1} and {2, 1, 0} for 3 levels
+    vector<unsigned int> shrink;
+    vector<double> smoothing;
+    for (unsigned int level = levels; level > 0; level--) {
+        shrink.push_back(1 << (level - 1));
+        smoothing.push_back(level - 1);
+    }
+    R.SetShrinkFactorsPerLevel(shrink);
+    R.SetSmoothingSigmasPerLevel(smoothing);
+    R.SetInterpolator(sitk::sitkBSpline);
+    if (mode == 0) {
+        R.SetInitialTransform(sitk::TranslationTransform(2), false);
+    } else if (mode == 1) {
+        // affine transform: matrix, translation
+        sitk::AffineTransform affine(2);
+        affine.SetCenter(c);
+        if (initial != nullptr) {
+            affine.SetMatrix({initial[0], initial[1], initial[2], initial[3]});
+            affine.SetTranslation({initial[4], initial[5]});
+        }
+        R.SetInitialTransform(affine, false);
+    } else if (mode == 2) {
+        // euler transform: angle, translation
+        R.SetInitialTransform(sitk::Euler2DTransform(c, 0.0, {0.0, 0.0}), false);
+    } else if (mode == 3) {
+        // similarity transform: scale, angle, translation
+        R.SetInitialTransform(sitk::Similarity2DTransform(1.0, 0.0, {0.0, 0.0}, c), false);
+    } else if (mode == 4) {
+        // euler transform: angle, translation; only the angle is optimized
+        R.SetInitialTransform(sitk::Euler2DTransform(c, 0.0, {0.0, 0.0}), false);
+        R.SetOptimizerWeights({1.0, 0.0, 0.0});
+    } else if (mode == 5) {
+        // similarity transform: scale, angle, translation; only the scale is optimized
+        R.SetInitialTransform(sitk::Similarity2DTransform(1.0, 0.0, {0.0, 0.0}, c), false);
+        R.SetOptimizerWeights({1.0, 0.0, 0.0, 0.0});
+    } else {
+        // scale transform: scale x, scale y
+        sitk::ScaleTransform scale(2, {1.0, 1.0});
+        scale.SetCenter(c);
+        R.SetInitialTransform(scale, false);
+    }
+    if (callback != nullptr) {
+        R.AddCommand(sitk::sitkIterationEvent, [&R, callback, data]() {
+            callback(data, R.GetMetricValue());
+        });
+    }
+    unsigned int iterations = 0;
+    if (info != nullptr) {
+        R.AddCommand(sitk::sitkIterationEvent, [&iterations]() {
+            iterations++;
+        });
+    }
+    if (fixed_mask != nullptr) {
+        R.SetMetricFixedMask(*fixed_mask);
+    }
+    if (moving_mask != nullptr) {
+        R.SetMetricMovingMask(*moving_mask);
+    }
+    // stopping the optimizer keeps the transform found so far, with more than one resolution
+    // each remaining resolution stops after its first iteration
+    bool timed_out = false;
+    double time_limit = (options == nullptr) ? 0 : options->time_limit;
+    std::chrono::steady_clock::time_point start = std::chrono::steady_clock::now();
+    if (time_limit > 0) {
+        R.AddCommand(sitk::sitkIterationEvent, [&R, &timed_out, start, time_limit]() {
+            std::chrono::duration<double> elapsed = std::chrono::steady_clock::now() - start;
+            if (elapsed.count() > time_limit) {
+                timed_out = true;
+                R.StopRegistration();
+            }
+        });
+    }
+    // stop uses the same data as callback
+    bool stopped = false;
+    if (stop != nullptr) {
+        R.AddCommand(sitk::sitkIterationEvent, [&R, &stopped, stop, data]() {
+            if (stop(data)) {
+                stopped = true;
+                R.StopRegistration();
+            }
+        });
+    }
+    sitk::Transform outTx = R.Execute(fixed, moving);
+    if (info != nullptr) {
+        info->final_metric = R.GetMetricValue();
+        info->iterations = iterations;
+        info->timed_out = timed_out;
+        string stop_condition = R.GetOptimizerStopConditionDescription();
+        if (timed_out) {
+            stop_condition = "time limit of " + to_string(time_limit) + " s exceeded: " + stop_condition;
+        } else if (stopped) {
+            stop_condition = "stopped by the caller: " + stop_condition;
+        }
+        strncpy(info->stop_condition, stop_condition.c_str(), 255);
+        info->stop_condition[255] = '\0';
+    }
+    vector<double> t = outTx.GetParameters();
+    if (mode == 0) {
+        (*transform)[0] = 1.0;
+        (*transform)[1] = 0.0;
+        (*transform)[2] = 0.0;
+        (*transform)[3] = 1.0;
+        (*transform)[4] = t[0];
+        (*transform)[5] = t[1];
+        return;
+    } else if (mode == 1) {
+        for (int j = 0; j < 6; j++) {
+            (*transform)[j] = t[j];
         }
-    } catch (const std::exception &exc) {
-        cerr << exc.what();
+        return;
+    } else if (mode == 2) {
+        double angle = t[0];
+        (*transform)[0] = cos(angle);
+        (*transform)[1] = -sin(angle);
+        (*transform)[2] = sin(angle);
+        (*transform)[3] = cos(angle);
+        (*transform)[4] = t[1];
+        (*transform)[5] = t[2];
+        return;
+    } else if (mode == 3) {
+        double scale = t[0];
+        double angle = t[1];
+        (*transform)[0] = scale * cos(angle);
+        (*transform)[1] = -scale * sin(angle);
+        (*transform)[2] = scale * sin(angle);
+        (*transform)[3] = scale * cos(angle);
+        (*transform)[4] = t[2];
+        (*transform)[5] = t[3];
+        return;
+    } else if (mode == 4) {
+        double angle = t[0];
+        (*transform)[0] = cos(angle);
+        (*transform)[1] = -sin(angle);
+        (*transform)[2] = sin(angle);
+        (*transform)[3] = cos(angle);
+    } else if (mode == 5) {
+        (*transform)[0] = t[0];
+        (*transform)[1] = 0.0;
+        (*transform)[2] = 0.0;
+        (*transform)[3] = t[0];
+    } else {
+        (*transform)[0] = t[0];
+        (*transform)[1] = 0.0;
+        (*transform)[2] = 0.0;
+        (*transform)[3] = t[1];
     }
+    (*transform)[4] = 0.0;
+    (*transform)[5] = 0.0;
 }
 
 
+#ifdef SITK_ADAPTER_ELASTIX
 void
 reg(
-    sitk::Image fixed,
-    sitk::Image moving,
-    bool t_or_a,
-    double** transform
+    vector<sitk::Image> fixed,
+    vector<sitk::Image> moving,
+    unsigned int mode,
+    double* center,
+    double** transform,
+    registration_options* options = nullptr,
+    sitk::Image* fixed_mask = nullptr,
+    sitk::Image* moving_mask = nullptr
 ) {
-    try {
-        string kind = (t_or_a == false) ? "translation" : "affine";
-//         std::filesystem::path output_path = std::filesystem::temp_directory_path() / gen_random(12);
-//         std::filesystem::create_directory(output_path);
-        std::filesystem::path output_path = std::filesystem::temp_directory_path();
-
-        sitk::ElastixImageFilter tfilter = sitk::ElastixImageFilter();
-        tfilter.LogToConsoleOff();
-        tfilter.LogToFileOff();
-        tfilter.SetLogToFile(false);
-        tfilter.SetFixedImage(fixed);
-        tfilter.SetMovingImage(moving);
-        tfilter.SetParameterMap(sitk::GetDefaultParameterMap(kind));
-        tfilter.SetParameter("WriteResultImage", "false");
-        tfilter.SetOutputDirectory(output_path);
-        tfilter.Execute();
-        sitk::ElastixImageFilter::ParameterMapType parameter_map = tfilter.GetTransformParameterMap(0);
-        for (sitk::ElastixImageFilter::ParameterMapType::iterator parameter = parameter_map.begin(); parameter != parameter_map.end(); ++parameter) {
-            if (parameter->first == "TransformParameters") {
-                vector<string> tp = parameter->second;
-                if (t_or_a == true) {
-                    for (int j = 0; j < tp.size(); j++) {
-                        (*transform)[j] = stod(tp[j]);
-                    }
-                } else {
-                    (*transform)[0] = 1.0;
-                    (*transform)[1] = 0.0;
-                    (*transform)[2] = 0.0;
-                    (*transform)[3] = 1.0;
+    // more than one fixed and moving image: one metric for each pair
+    // masks: only pixels where the mask is nonzero are used
+    // 0: translation, 1: affine, 2: rigid, 3: similarity, 4-6: reg2
+    string kind;
+    if (mode == 0) {
+        kind = "translation";
+    } else if ((mode == 2) || (mode == 3)) {
+        kind = "rigid";
+    } else {
+        kind = "affine";
+    }
+//     std::filesystem::path output_path = std::filesystem::temp_directory_path() / gen_random(12);
+//     std::filesystem::create_directory(output_path);
+    std::filesystem::path output_path = std::filesystem::temp_directory_path();
+
+    sitk::ElastixImageFilter tfilter = sitk::ElastixImageFilter();
+    tfilter.LogToConsoleOff();
+    tfilter.LogToFileOff();
+    tfilter.SetLogToFile(false);
+    tfilter.SetFixedImage(fixed[0]);
+    tfilter.SetMovingImage(moving[0]);
+    for (int i = 1; i < fixed.size(); i++) {
+        tfilter.AddFixedImage(fixed[i]);
+        tfilter.AddMovingImage(moving[i]);
+    }
+    if (fixed_mask != nullptr) {
+        tfilter.SetFixedMask(*fixed_mask);
+    }
+    if (moving_mask != nullptr) {
+        tfilter.SetMovingMask(*moving_mask);
+    }
+    sitk::ElastixImageFilter::ParameterMapType parameters = sitk::GetDefaultParameterMap(kind);
+    if (mode == 3) {
+        parameters["Transform"] = {"SimilarityTransform"};
+    }
+    if ((center != nullptr) && (mode != 0)) {
+        // elastix uses the geometric center of the fixed image if not given
+        parameters["CenterOfRotationPoint"] = {to_string(center[0]), to_string(center[1])};
+    }
+    if (options != nullptr) {
+        if (options->max_iterations > 0) {
+            parameters["MaximumNumberOfIterations"] = {to_string(options->max_iterations)};
+        }
+        if (options->pyramid_levels > 0) {
+            parameters["NumberOfResolutions"] = {to_string(options->pyramid_levels)};
+        }
+        if (options->sampling_fraction > 0) {
+            unsigned int n = fixed[0].GetNumberOfPixels() * options->sampling_fraction;
+            parameters["NumberOfSpatialSamples"] = {to_string(max(n, 1u))};
+        }
+        if (options->metric == 1) {
+            parameters["Metric"] = {"AdvancedMeanSquares"};
+        } else if (options->metric == 2) {
+            parameters["Metric"] = {"AdvancedNormalizedCorrelation"};
+        } else if (options->metric == 3) {
+            parameters["Metric"] = {"AdvancedMattesMutualInformation"};
+        }
+        if ((options->mi_bins > 0) && (parameters["Metric"][0].find("MutualInformation") != string::npos)) {
+            parameters["NumberOfHistogramBins"] = {to_string(options->mi_bins)};
+        }
+        if (options->fixed_seed) {
+            parameters["RandomSeed"] = {to_string((unsigned int) options->seed)};
+        }
+        if (options->max_step_length > 0) {
+            parameters["MaximumStepLength"] = {to_string(options->max_step_length)};
+        }
+    }
+    if (fixed.size() > 1) {
+        parameters["Registration"] = {"MultiMetricMultiResolutionRegistration"};
+        for (string key : {"Metric", "FixedImagePyramid", "MovingImagePyramid", "Interpolator", "ImageSampler"}) {
+            parameters[key] = vector<string>(fixed.size(), parameters[key][0]);
+        }
+    }
+    tfilter.SetParameterMap(parameters);
+    tfilter.SetParameter("WriteResultImage", "false");
+    tfilter.SetOutputDirectory(output_path);
+    tfilter.Execute();
+    sitk::ElastixImageFilter::ParameterMapType parameter_map = tfilter.GetTransformParameterMap(0);
+    for (sitk::ElastixImageFilter::ParameterMapType::iterator parameter = parameter_map.begin(); parameter != parameter_map.end(); ++parameter) {
+        if (parameter->first == "TransformParameters") {
+            vector<string> tp = parameter->second;
+            if (mode == 0) {
+                (*transform)[0] = 1.0;
+                (*transform)[1] = 0.0;
+                (*transform)[2] = 0.0;
+                (*transform)[3] = 1.0;
                 for (int j = 0; j < tp.size(); j++) {
                     (*transform)[j + 4] = stod(tp[j]);
                 }
+            } else if (mode == 2) {
+                // euler transform: angle, translation
+                double angle = stod(tp[0]);
+                (*transform)[0] = cos(angle);
+                (*transform)[1] = -sin(angle);
+                (*transform)[2] = sin(angle);
+                (*transform)[3] = cos(angle);
+                (*transform)[4] = stod(tp[1]);
+                (*transform)[5] = stod(tp[2]);
+            } else if (mode == 3) {
+                // similarity transform: scale, angle, translation
+                double scale = stod(tp[0]);
+                double angle = stod(tp[1]);
+                (*transform)[0] = scale * cos(angle);
+                (*transform)[1] = -scale * sin(angle);
+                (*transform)[2] = scale * sin(angle);
+                (*transform)[3] = scale * cos(angle);
+                (*transform)[4] = stod(tp[2]);
+                (*transform)[5] = stod(tp[3]);
+            } else {
+                for (int j = 0; j < tp.size(); j++) {
+                    (*transform)[j] = stod(tp[j]);
+                }
+            }
+            break;
+        }
+    }
+}
+#endif
+
+
+#ifdef SITK_ADAPTER_ELASTIX
+void
+elastix_info(
+    sitk::Image fixed,
+    sitk::Image moving,
+    double* center,
+    double** transform,
+    registration_options* options,
+    registration_info* info
+) {
+    // elastix does not report how it ended, so the final metric is that of reg2 evaluated for
+    // the transform found, the transform is about center, or the geometric center of fixed,
+    // there are no iterations to count
+    vector<double> c;
+    if (center == nullptr) {
+        c = fixed.TransformContinuousIndexToPhysicalPoint(
+            {(fixed.GetWidth() - 1) / 2.0, (fixed.GetHeight() - 1) / 2.0}
+        );
+    } else {
+        c = {center[0], center[1]};
+    }
+    sitk::AffineTransform affine(2);
+    affine.SetCenter(c);
+    affine.SetMatrix({(*transform)[0], (*transform)[1], (*transform)[2], (*transform)[3]});
+    affine.SetTranslation({(*transform)[4], (*transform)[5]});
+    sitk::ImageRegistrationMethod R;
+    set_metric(R, options);
+    R.SetInterpolator(sitk::sitkBSpline);
+    R.SetInitialTransform(affine, false);
+    info->final_metric = R.MetricEvaluate(fixed, moving);
+    info->iterations = 0;
+    info->timed_out = false;
+    strncpy(info->stop_condition, "registered with elastix, which does not report how it stopped", 255);
+    info->stop_condition[255] = '\0';
+}
+#endif
+
+
+void
+registration(
+    sitk::Image fixed,
+    sitk::Image moving,
+    unsigned int mode,
+    bool edges,
+    double* center,
+    double* spacing,
+    double* direction,
+    double** transform,
+    registration_options* options,
+    double* initial = nullptr,
+    metric_callback callback = nullptr,
+    void* data = nullptr,
+    sitk::Image* fixed_mask = nullptr,
+    sitk::Image* moving_mask = nullptr,
+    registration_info* info = nullptr,
+    stop_callback stop = nullptr
+) {
+    fixed.SetSpacing({spacing[0], spacing[1]});
+    fixed.SetDirection({direction[0], direction[1], direction[2], direction[3]});
+    moving.SetSpacing({spacing[0], spacing[1]});
+    moving.SetDirection({direction[0], direction[1], direction[2], direction[3]});
+    for (sitk::Image* mask : {fixed_mask, moving_mask}) {
+        if (mask != nullptr) {
+            mask->SetSpacing({spacing[0], spacing[1]});
+            mask->SetDirection({direction[0], direction[1], direction[2], direction[3]});
+        }
+    }
+    if ((options != nullptr) && (options->normalization > 0)) {
+        for (sitk::Image* image : {&fixed, &moving}) {
+            sitk::MinimumMaximumImageFilter minmax = sitk::MinimumMaximumImageFilter();
+            minmax.Execute(*image);
+            if (minmax.GetMinimum() == minmax.GetMaximum()) {
+                throw std::invalid_argument("cannot normalize an image with constant intensity");
+            }
+            if (options->normalization == 1) {
+                *image = sitk::Normalize(sitk::Cast(*image, sitk::sitkFloat32));
+            } else {
+                *image = sitk::RescaleIntensity(sitk::Cast(*image, sitk::sitkFloat32), 0.0, 1.0);
             }
-          break;
-          }
         }
-    } catch (const std::exception &exc) {
-        cerr << exc.what();
     }
-} 
+    if ((options != nullptr) && options->histogram_match) {
+        // in float, so that integer pixel types cannot overflow
+        fixed = sitk::Cast(fixed, sitk::sitkFloat32);
+        sitk::HistogramMatchingImageFilter matcher = sitk::HistogramMatchingImageFilter();
+        matcher.SetNumberOfHistogramLevels(options->histogram_levels > 0 ? options->histogram_levels : 256);
+        matcher.SetNumberOfMatchPoints(7);
+        matcher.ThresholdAtMeanIntensityOn();
+        moving = matcher.Execute(sitk::Cast(moving, sitk::sitkFloat32), fixed);
+    }
+    if (edges == true) {
+        fixed = sitk::GradientMagnitudeRecursiveGaussian(sitk::Cast(fixed, sitk::sitkFloat32));
+        moving = sitk::GradientMagnitudeRecursiveGaussian(sitk::Cast(moving, sitk::sitkFloat32));
+    }
+    if ((options != nullptr) && (options->smoothing_sigma > 0)) {
+        fixed = sitk::SmoothingRecursiveGaussian(sitk::Cast(fixed, sitk::sitkFloat32), options->smoothing_sigma);
+        moving = sitk::SmoothingRecursiveGaussian(sitk::Cast(moving, sitk::sitkFloat32), options->smoothing_sigma);
+    }
+    for (sitk::Image image : {fixed, moving}) {
+        sitk::MinimumMaximumImageFilter minmax = sitk::MinimumMaximumImageFilter();
+        minmax.Execute(image);
+        if (minmax.GetMinimum() == minmax.GetMaximum()) {
+            throw std::invalid_argument("cannot register an image with constant intensity");
+        }
+    }
+#ifdef SITK_ADAPTER_ELASTIX
+    bool optimizer = (options != nullptr) && (options->optimizer > 0);
+    bool scaled = (options != nullptr) && (options->estimate_scales > 0);
+    bool limited = (options != nullptr) && (options->time_limit > 0);
+    // with info, the callback only records the metric, which elastix cannot, so it does not need
+    // reg2, whereas stop does
+    bool observed = ((callback != nullptr) && (info == nullptr)) || (stop != nullptr);
+    if ((mode >= 4) || observed || (initial != nullptr) || optimizer || scaled || limited) {
+        reg2(fixed, moving, mode, center, transform, options, initial, callback, data, fixed_mask, moving_mask, info, stop);
+    } else {
+        reg({fixed}, {moving}, mode, center, transform, options, fixed_mask, moving_mask);
+        if (info != nullptr) {
+            elastix_info(fixed, moving, center, transform, options, info);
+        }
+    }
+#else
+    // without elastix everything is done with the sitk registration method
+    reg2(fixed, moving, mode, center, transform, options, initial, callback, data, fixed_mask, moving_mask, info, stop);
+#endif
+}
+
+
+#ifdef SITK_ADAPTER_ELASTIX
+void
+registration_vector(
+    sitk::Image fixed,
+    sitk::Image moving,
+    unsigned int mode,
+    double** transform
+) {
+    vector<sitk::Image> fixed_channels;
+    vector<sitk::Image> moving_channels;
+    for (unsigned int c = 0; c < fixed.GetNumberOfComponentsPerPixel(); c++) {
+        fixed_channels.push_back(sitk::VectorIndexSelectionCast(fixed, c));
+        moving_channels.push_back(sitk::VectorIndexSelectionCast(moving, c));
+    }
+    reg(fixed_channels, moving_channels, mode, nullptr, transform);
+}
 
 
 extern "C" void
@@ -331,13 +1097,23 @@ register_u8(
   unsigned int height,
   uint8_t** fixed_arr,
   uint8_t** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -346,13 +1122,23 @@ register_i8(
   unsigned int height,
   int8_t** fixed_arr,
   int8_t** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -361,13 +1147,23 @@ register_u16(
   unsigned int height,
   uint16_t** fixed_arr,
   uint16_t** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -376,13 +1172,23 @@ register_i16(
   unsigned int height,
   int16_t** fixed_arr,
   int16_t** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -391,13 +1197,23 @@ register_u32(
   unsigned int height,
   uint32_t** fixed_arr,
   uint32_t** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -406,13 +1222,23 @@ register_i32(
   unsigned int height,
   int32_t** fixed_arr,
   int32_t** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -421,13 +1247,23 @@ register_u64(
   unsigned int height,
   uint64_t** fixed_arr,
   uint64_t** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -436,13 +1272,23 @@ register_i64(
   unsigned int height,
   int64_t** fixed_arr,
   int64_t** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -451,13 +1297,23 @@ register_f32(
   unsigned int height,
   float** fixed_arr,
   float** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
 
 extern "C" void
@@ -466,11 +1322,3227 @@ register_f64(
   unsigned int height,
   double** fixed_arr,
   double** moving_arr,
-  bool t_or_a,
-  double** transform
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
 ) {
-  sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
-  sitk::Image fixed = make_image(width, height, *fixed_arr, id);
-  sitk::Image moving = make_image(width, height, *moving_arr, id);
-  reg(fixed, moving, t_or_a, transform);
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_f16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  unsigned int mode,
+  bool edges,
+  double* center,
+  double* spacing,
+  double* direction,
+  double** transform,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    vector<float> f = half_to_float(*fixed_arr, width * height);
+    vector<float> m = half_to_float(*moving_arr, width * height);
+    sitk::Image fixed = make_image(width, height, f.data(), id);
+    sitk::Image moving = make_image(width, height, m.data(), id);
+    registration(fixed, moving, mode, edges, center, spacing, direction, transform, options);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_u8(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorUInt8;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_i8(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorInt8;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_u16(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorUInt16;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_i16(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorInt16;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_u32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorUInt32;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_i32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorInt32;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_u64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorUInt64;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_i64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorInt64;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_f32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  float** fixed_arr,
+  float** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorFloat32;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_vector_f64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int channels,
+  double** fixed_arr,
+  double** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkVectorFloat64;
+    sitk::Image fixed = make_vector_image(width, height, channels, *fixed_arr, id);
+    sitk::Image moving = make_vector_image(width, height, channels, *moving_arr, id);
+    registration_vector(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+#endif
+
+extern "C" void
+register_recording_u8(
+  unsigned int width,
+  unsigned int height,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_i8(
+  unsigned int width,
+  unsigned int height,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_u16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_i16(
+  unsigned int width,
+  unsigned int height,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_u32(
+  unsigned int width,
+  unsigned int height,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_i32(
+  unsigned int width,
+  unsigned int height,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_u64(
+  unsigned int width,
+  unsigned int height,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_i64(
+  unsigned int width,
+  unsigned int height,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_f32(
+  unsigned int width,
+  unsigned int height,
+  float** fixed_arr,
+  float** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_recording_f64(
+  unsigned int width,
+  unsigned int height,
+  double** fixed_arr,
+  double** moving_arr,
+  unsigned int mode,
+  double* center,
+  double* initial,
+  double** transform,
+  metric_callback callback,
+  void* data,
+  registration_options* options,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, center, spacing, direction, transform, options, initial, callback, data);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+
+#ifdef SITK_ADAPTER_ELASTIX
+void
+reg_bspline(
+    sitk::Image fixed,
+    sitk::Image moving,
+    unsigned int* grid_spacing,
+    double** field
+) {
+    std::filesystem::path output_path = std::filesystem::temp_directory_path();
+
+    sitk::ElastixImageFilter tfilter = sitk::ElastixImageFilter();
+    tfilter.LogToConsoleOff();
+    tfilter.LogToFileOff();
+    tfilter.SetLogToFile(false);
+    tfilter.SetFixedImage(fixed);
+    tfilter.SetMovingImage(moving);
+    sitk::ElastixImageFilter::ParameterMapType parameters = sitk::GetDefaultParameterMap("bspline");
+    parameters["FinalGridSpacingInPhysicalUnits"] = {to_string(grid_spacing[0]), to_string(grid_spacing[1])};
+    tfilter.SetParameterMap(parameters);
+    tfilter.SetParameter("WriteResultImage", "false");
+    tfilter.SetOutputDirectory(output_path);
+    tfilter.Execute();
+
+    // sample the deformation on the pixel grid of the fixed image
+    sitk::TransformixImageFilter transformix = sitk::TransformixImageFilter();
+    transformix.LogToConsoleOff();
+    transformix.LogToFileOff();
+    transformix.SetMovingImage(moving);
+    transformix.SetTransformParameterMap(tfilter.GetTransformParameterMap());
+    transformix.ComputeDeformationFieldOn();
+    transformix.SetOutputDirectory(output_path);
+    transformix.Execute();
+    sitk::Image deformation = sitk::Cast(transformix.GetDeformationField(), sitk::sitkVectorFloat64);
+    double* d = deformation.GetBufferAsDouble();
+    memcpy(*field, d, fixed.GetWidth() * fixed.GetHeight() * 2 * 8);
+}
+#endif
+
+
+void
+reg_demons(
+    sitk::Image fixed,
+    sitk::Image moving,
+    unsigned int iterations,
+    double sigma,
+    double** field
+) {
+    // demons needs real valued images
+    fixed = sitk::Cast(fixed, sitk::sitkFloat32);
+    moving = sitk::Cast(moving, sitk::sitkFloat32);
+
+    sitk::FastSymmetricForcesDemonsRegistrationFilter filter = sitk::FastSymmetricForcesDemonsRegistrationFilter();
+    filter.SetNumberOfIterations(iterations);
+    filter.SetStandardDeviations(sigma);
+    sitk::Image deformation = sitk::Cast(filter.Execute(fixed, moving), sitk::sitkVectorFloat64);
+    double* d = deformation.GetBufferAsDouble();
+    memcpy(*field, d, fixed.GetWidth() * fixed.GetHeight() * 2 * 8);
+}
+
+
+sitk::Image
+warp(
+  sitk::Image image,
+  double* field,
+  unsigned int interpolator
+) {
+  sitk::Image displacement(image.GetWidth(), image.GetHeight(), sitk::sitkVectorFloat64, 2);
+  double* d = displacement.GetBufferAsDouble();
+  memcpy(d, field, image.GetWidth() * image.GetHeight() * 2 * 8);
+  sitk::DisplacementFieldTransform t(displacement);
+  sitk::InterpolatorEnum interp_enum = interpolator_enum(interpolator);
+  return sitk::Resample(image, t, interp_enum);
+}
+
+
+extern "C" void
+warp_u8(
+  unsigned int width,
+  unsigned int height,
+  uint8_t** image,
+  double* field,
+  unsigned int interpolator,
+  uint8_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt8);
+    im = warp(im, field, interpolator);
+    uint8_t* c = im.GetBufferAsUInt8();
+    memcpy(*output, c, width * height);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_i8(
+  unsigned int width,
+  unsigned int height,
+  int8_t** image,
+  double* field,
+  unsigned int interpolator,
+  int8_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt8);
+    im = warp(im, field, interpolator);
+    int8_t* c = im.GetBufferAsInt8();
+    memcpy(*output, c, width * height);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_u16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** image,
+  double* field,
+  unsigned int interpolator,
+  uint16_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt16);
+    im = warp(im, field, interpolator);
+    uint16_t* c = im.GetBufferAsUInt16();
+    memcpy(*output, c, width * height * 2);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_i16(
+  unsigned int width,
+  unsigned int height,
+  int16_t** image,
+  double* field,
+  unsigned int interpolator,
+  int16_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt16);
+    im = warp(im, field, interpolator);
+    int16_t* c = im.GetBufferAsInt16();
+    memcpy(*output, c, width * height * 2);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_u32(
+  unsigned int width,
+  unsigned int height,
+  uint32_t** image,
+  double* field,
+  unsigned int interpolator,
+  uint32_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt32);
+    im = warp(im, field, interpolator);
+    uint32_t* c = im.GetBufferAsUInt32();
+    memcpy(*output, c, width * height * 4);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_i32(
+  unsigned int width,
+  unsigned int height,
+  int32_t** image,
+  double* field,
+  unsigned int interpolator,
+  int32_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt32);
+    im = warp(im, field, interpolator);
+    int32_t* c = im.GetBufferAsInt32();
+    memcpy(*output, c, width * height * 4);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_u64(
+  unsigned int width,
+  unsigned int height,
+  uint64_t** image,
+  double* field,
+  unsigned int interpolator,
+  uint64_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkUInt64);
+    im = warp(im, field, interpolator);
+    uint64_t* c = im.GetBufferAsUInt64();
+    memcpy(*output, c, width * height * 8);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_i64(
+  unsigned int width,
+  unsigned int height,
+  int64_t** image,
+  double* field,
+  unsigned int interpolator,
+  int64_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkInt64);
+    im = warp(im, field, interpolator);
+    int64_t* c = im.GetBufferAsInt64();
+    memcpy(*output, c, width * height * 8);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_f32(
+  unsigned int width,
+  unsigned int height,
+  float** image,
+  double* field,
+  unsigned int interpolator,
+  float** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkFloat32);
+    im = warp(im, field, interpolator);
+    float* c = im.GetBufferAsFloat();
+    memcpy(*output, c, width * height * 4);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+warp_f64(
+  unsigned int width,
+  unsigned int height,
+  double** image,
+  double* field,
+  unsigned int interpolator,
+  double** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image(width, height, *image, sitk::PixelIDValueEnum::sitkFloat64);
+    im = warp(im, field, interpolator);
+    double* c = im.GetBufferAsDouble();
+    memcpy(*output, c, width * height * 8);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+#ifdef SITK_ADAPTER_ELASTIX
+extern "C" void
+register_bspline_u8(
+  unsigned int width,
+  unsigned int height,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_i8(
+  unsigned int width,
+  unsigned int height,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_u16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_i16(
+  unsigned int width,
+  unsigned int height,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_u32(
+  unsigned int width,
+  unsigned int height,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_i32(
+  unsigned int width,
+  unsigned int height,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_u64(
+  unsigned int width,
+  unsigned int height,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_i64(
+  unsigned int width,
+  unsigned int height,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_f32(
+  unsigned int width,
+  unsigned int height,
+  float** fixed_arr,
+  float** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_bspline_f64(
+  unsigned int width,
+  unsigned int height,
+  double** fixed_arr,
+  double** moving_arr,
+  unsigned int* grid_spacing,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_bspline(fixed, moving, grid_spacing, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+#endif
+
+extern "C" void
+register_demons_u8(
+  unsigned int width,
+  unsigned int height,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_i8(
+  unsigned int width,
+  unsigned int height,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_u16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_i16(
+  unsigned int width,
+  unsigned int height,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_u32(
+  unsigned int width,
+  unsigned int height,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_i32(
+  unsigned int width,
+  unsigned int height,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_u64(
+  unsigned int width,
+  unsigned int height,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_i64(
+  unsigned int width,
+  unsigned int height,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_f32(
+  unsigned int width,
+  unsigned int height,
+  float** fixed_arr,
+  float** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_demons_f64(
+  unsigned int width,
+  unsigned int height,
+  double** fixed_arr,
+  double** moving_arr,
+  unsigned int iterations,
+  double sigma,
+  double** field,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_demons(fixed, moving, iterations, sigma, field);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+
+void
+reg_3d(
+    sitk::Image fixed,
+    sitk::Image moving,
+    unsigned int mode,
+    double** transform
+) {
+    // 1: affine, 2: rigid (Euler3DTransform), both about the geometric center of the fixed image
+    // transform: the 3x3 matrix in row major order, followed by the translation
+    for (sitk::Image image : {fixed, moving}) {
+        sitk::MinimumMaximumImageFilter minmax = sitk::MinimumMaximumImageFilter();
+        minmax.Execute(image);
+        if (minmax.GetMinimum() == minmax.GetMaximum()) {
+            throw std::invalid_argument("cannot register an image with constant intensity");
+        }
+    }
+    vector<double> c = fixed.TransformContinuousIndexToPhysicalPoint({
+        (fixed.GetWidth() - 1) / 2.0, (fixed.GetHeight() - 1) / 2.0, (fixed.GetDepth() - 1) / 2.0
+    });
+
+    sitk::ImageRegistrationMethod R;
+    R.SetMetricAsMattesMutualInformation();
+    // a fixed seed keeps the result reproducible
+    R.SetMetricSamplingStrategy(R.RANDOM);
+    R.SetMetricSamplingPercentage(0.1, 42);
+    R.SetOptimizerAsRegularStepGradientDescent(4.0, 0.01, 200, 0.5);
+    R.SetOptimizerScalesFromPhysicalShift();
+    R.SetShrinkFactorsPerLevel({4, 2, 1});
+    R.SetSmoothingSigmasPerLevel({2, 1, 0});
+    R.SetInterpolator(sitk::sitkLinear);
+    vector<double> m;
+    vector<double> t;
+    if (mode == 1) {
+        sitk::AffineTransform affine(3);
+        affine.SetCenter(c);
+        R.SetInitialTransform(affine, false);
+        sitk::AffineTransform result(R.Execute(fixed, moving));
+        m = result.GetMatrix();
+        t = result.GetTranslation();
+    } else if (mode == 2) {
+        sitk::Euler3DTransform euler;
+        euler.SetCenter(c);
+        R.SetInitialTransform(euler, false);
+        sitk::Euler3DTransform result(R.Execute(fixed, moving));
+        m = result.GetMatrix();
+        t = result.GetTranslation();
+    } else {
+        throw std::invalid_argument("unsupported mode for 3D registration: " + to_string(mode));
+    }
+    for (int j = 0; j < 9; j++) {
+        (*transform)[j] = m[j];
+    }
+    for (int j = 0; j < 3; j++) {
+        (*transform)[j + 9] = t[j];
+    }
+}
+
+
+extern "C" void
+register_3d_u8(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_i8(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_u16(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_i16(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_u32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_i32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_u64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_i64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_f32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  float** fixed_arr,
+  float** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_f64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double** fixed_arr,
+  double** moving_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    reg_3d(fixed, moving, mode, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+
+sitk::Image
+interp_3d(
+  double* transform,
+  double* origin,
+  sitk::Image image,
+  unsigned int interpolator
+) {
+  // transform: the 3x3 matrix in row major order, followed by the translation
+  vector<double> matrix(transform, transform + 9);
+  vector<double> translation = {transform[9], transform[10], transform[11]};
+  vector<double> ori = {origin[0], origin[1], origin[2]};
+  sitk::AffineTransform t(matrix, translation, ori);
+  sitk::InterpolatorEnum interp_enum = interpolator_enum(interpolator);
+  return sitk::Resample(image, image, t, interp_enum);
+}
+
+
+extern "C" void
+interp_3d_u8(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  uint8_t** image,
+  unsigned int interpolator,
+  uint8_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkUInt8);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(uint8_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_i8(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  int8_t** image,
+  unsigned int interpolator,
+  int8_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkInt8);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(int8_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_u16(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  uint16_t** image,
+  unsigned int interpolator,
+  uint16_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkUInt16);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(uint16_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_i16(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  int16_t** image,
+  unsigned int interpolator,
+  int16_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkInt16);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(int16_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_u32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  uint32_t** image,
+  unsigned int interpolator,
+  uint32_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkUInt32);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(uint32_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_i32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  int32_t** image,
+  unsigned int interpolator,
+  int32_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkInt32);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(int32_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_u64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  uint64_t** image,
+  unsigned int interpolator,
+  uint64_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkUInt64);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(uint64_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_i64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  int64_t** image,
+  unsigned int interpolator,
+  int64_t** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkInt64);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(int64_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_f32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  float** image,
+  unsigned int interpolator,
+  float** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkFloat32);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(float));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+interp_3d_f64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double* transform,
+  double* origin,
+  double** image,
+  unsigned int interpolator,
+  double** output,
+  int* status
+) {
+  try {
+    sitk::Image im = make_image_3d(width, height, depth, *image, sitk::PixelIDValueEnum::sitkFloat64);
+    im = interp_3d(transform, origin, im, interpolator);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * depth * sizeof(double));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+
+extern "C" void
+register_masked_u8(
+  unsigned int width,
+  unsigned int height,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_i8(
+  unsigned int width,
+  unsigned int height,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_u16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_i16(
+  unsigned int width,
+  unsigned int height,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_u32(
+  unsigned int width,
+  unsigned int height,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_i32(
+  unsigned int width,
+  unsigned int height,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_u64(
+  unsigned int width,
+  unsigned int height,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_i64(
+  unsigned int width,
+  unsigned int height,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_f32(
+  unsigned int width,
+  unsigned int height,
+  float** fixed_arr,
+  float** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_masked_f64(
+  unsigned int width,
+  unsigned int height,
+  double** fixed_arr,
+  double** moving_arr,
+  uint8_t* fixed_mask_arr,
+  uint8_t* moving_mask_arr,
+  unsigned int mode,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image fixed_mask;
+    sitk::Image moving_mask;
+    if (fixed_mask_arr != nullptr) {
+      fixed_mask = make_image(width, height, fixed_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    if (moving_mask_arr != nullptr) {
+      moving_mask = make_image(width, height, moving_mask_arr, sitk::PixelIDValueEnum::sitkUInt8);
+    }
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr, nullptr, nullptr, nullptr,
+      (fixed_mask_arr == nullptr) ? nullptr : &fixed_mask, (moving_mask_arr == nullptr) ? nullptr : &moving_mask
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+
+sitk::Image
+registration_resample(
+    sitk::Image fixed,
+    sitk::Image moving,
+    unsigned int mode,
+    unsigned int interpolator,
+    double** transform
+) {
+    // register, and resample moving with the transform found onto the pixel grid of fixed,
+    // the transform is about the geometric center of fixed
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(fixed, moving, mode, false, nullptr, spacing, direction, transform, nullptr);
+    double origin[2] = {(fixed.GetWidth() - 1) / 2.0, (fixed.GetHeight() - 1) / 2.0};
+    unsigned int roi[4] = {0, 0, fixed.GetWidth(), fixed.GetHeight()};
+    return interp(*transform, origin, spacing, direction, moving, interpolator, roi);
+}
+
+
+extern "C" void
+register_resample_u8(
+  unsigned int width,
+  unsigned int height,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  uint8_t** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(uint8_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_i8(
+  unsigned int width,
+  unsigned int height,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  int8_t** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(int8_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_u16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  uint16_t** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(uint16_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_i16(
+  unsigned int width,
+  unsigned int height,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  int16_t** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(int16_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_u32(
+  unsigned int width,
+  unsigned int height,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  uint32_t** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(uint32_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_i32(
+  unsigned int width,
+  unsigned int height,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  int32_t** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(int32_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_u64(
+  unsigned int width,
+  unsigned int height,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  uint64_t** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(uint64_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_i64(
+  unsigned int width,
+  unsigned int height,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  int64_t** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(int64_t));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_f32(
+  unsigned int width,
+  unsigned int height,
+  float** fixed_arr,
+  float** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  float** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(float));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_resample_f64(
+  unsigned int width,
+  unsigned int height,
+  double** fixed_arr,
+  double** moving_arr,
+  unsigned int mode,
+  unsigned int interpolator,
+  double** transform,
+  double** output,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    sitk::Image im = registration_resample(fixed, moving, mode, interpolator, transform);
+    memcpy(*output, im.GetBufferAsVoid(), width * height * sizeof(double));
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+
+void
+reg_exhaustive(
+    sitk::Image fixed,
+    sitk::Image moving,
+    double* step,
+    double* range,
+    double** transform
+) {
+    // evaluate the correlation for all translations on a grid: -range..=range in steps of step,
+    // for x and y, and return the best one
+    sitk::ImageRegistrationMethod R;
+    R.SetMetricAsCorrelation();
+    vector<unsigned int> steps = {
+        (unsigned int) floor(range[0] / step[0]), (unsigned int) floor(range[1] / step[1])
+    };
+    // the grid spacing is the step length times the scale of each parameter
+    R.SetOptimizerAsExhaustive(steps, 1.0);
+    R.SetOptimizerScales({step[0], step[1]});
+    R.SetInterpolator(sitk::sitkLinear);
+    R.SetInitialTransform(sitk::TranslationTransform(2), false);
+    sitk::Transform outTx = R.Execute(fixed, moving);
+    vector<double> t = outTx.GetParameters();
+    (*transform)[0] = 1.0;
+    (*transform)[1] = 0.0;
+    (*transform)[2] = 0.0;
+    (*transform)[3] = 1.0;
+    (*transform)[4] = t[0];
+    (*transform)[5] = t[1];
+}
+
+
+extern "C" void
+register_exhaustive_u8(
+  unsigned int width,
+  unsigned int height,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_i8(
+  unsigned int width,
+  unsigned int height,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_u16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_i16(
+  unsigned int width,
+  unsigned int height,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_u32(
+  unsigned int width,
+  unsigned int height,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_i32(
+  unsigned int width,
+  unsigned int height,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_u64(
+  unsigned int width,
+  unsigned int height,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_i64(
+  unsigned int width,
+  unsigned int height,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_f32(
+  unsigned int width,
+  unsigned int height,
+  float** fixed_arr,
+  float** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_exhaustive_f64(
+  unsigned int width,
+  unsigned int height,
+  double** fixed_arr,
+  double** moving_arr,
+  double* step,
+  double* range,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    reg_exhaustive(fixed, moving, step, range, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+
+typedef itk::Image<float, 3> ImageType3D;
+
+
+ImageType3D::Pointer
+to_itk_3d(sitk::Image image) {
+    // the itk image stays alive after the sitk image goes out of scope because of the smart pointer
+    sitk::Image cast = sitk::Cast(image, sitk::sitkFloat32);
+    ImageType3D::Pointer itk_image = dynamic_cast<ImageType3D*>(cast.GetITKBase());
+    if (itk_image.IsNull()) {
+        throw std::invalid_argument("could not convert the image to an itk image");
+    }
+    return itk_image;
+}
+
+
+void
+reg_3d_labels(
+    sitk::Image fixed,
+    sitk::Image moving,
+    sitk::Image fixed_label,
+    sitk::Image moving_label,
+    double** transform
+) {
+    // affine registration about the geometric center of the fixed image, sitk's registration
+    // method takes only one metric, so this uses itk with two: mattes mutual information between
+    // the images, plus the mean squared difference between the labels made binary, which is the
+    // fraction of the voxels where the labels do not overlap
+    // transform: the 3x3 matrix in row major order, followed by the translation
+    for (sitk::Image image : {fixed, moving, fixed_label, moving_label}) {
+        sitk::MinimumMaximumImageFilter minmax = sitk::MinimumMaximumImageFilter();
+        minmax.Execute(image);
+        if (minmax.GetMinimum() == minmax.GetMaximum()) {
+            throw std::invalid_argument("cannot register an image or label with constant intensity");
+        }
+    }
+    vector<double> c = fixed.TransformContinuousIndexToPhysicalPoint({
+        (fixed.GetWidth() - 1) / 2.0, (fixed.GetHeight() - 1) / 2.0, (fixed.GetDepth() - 1) / 2.0
+    });
+
+    typedef itk::AffineTransform<double, 3> TransformType;
+    typedef itk::MattesMutualInformationImageToImageMetricv4<ImageType3D, ImageType3D> MIMetricType;
+    typedef itk::MeanSquaresImageToImageMetricv4<ImageType3D, ImageType3D> MSMetricType;
+    typedef itk::RegularStepGradientDescentOptimizerv4<double> OptimizerType;
+    typedef itk::ImageRegistrationMethodv4<ImageType3D, ImageType3D, TransformType> RegistrationType;
+    // an itk::ObjectToObjectMultiMetricv4 with the same virtual image as the registration method
+    typedef RegistrationType::MultiMetricType MultiMetricType;
+
+    MIMetricType::Pointer mi = MIMetricType::New();
+    mi->SetNumberOfHistogramBins(50);
+    MSMetricType::Pointer ms = MSMetricType::New();
+    MultiMetricType::Pointer metric = MultiMetricType::New();
+    metric->AddMetric(mi);
+    metric->AddMetric(ms);
+    MultiMetricType::WeightsArrayType weights(2);
+    weights[0] = 1.0;
+    weights[1] = 1.0;
+    metric->SetMetricWeights(weights);
+
+    OptimizerType::Pointer optimizer = OptimizerType::New();
+    optimizer->SetLearningRate(4.0);
+    optimizer->SetMinimumStepLength(0.01);
+    optimizer->SetNumberOfIterations(200);
+    optimizer->SetRelaxationFactor(0.5);
+    typedef itk::RegistrationParameterScalesFromPhysicalShift<MIMetricType> ScalesType;
+    ScalesType::Pointer scales = ScalesType::New();
+    scales->SetMetric(mi);
+    optimizer->SetScalesEstimator(scales);
+
+    TransformType::Pointer initial = TransformType::New();
+    TransformType::InputPointType center;
+    for (int j = 0; j < 3; j++) {
+        center[j] = c[j];
+    }
+    initial->SetCenter(center);
+
+    RegistrationType::Pointer R = RegistrationType::New();
+    R->SetFixedImage(0, to_itk_3d(fixed));
+    R->SetMovingImage(0, to_itk_3d(moving));
+    R->SetFixedImage(1, to_itk_3d(sitk::NotEqual(fixed_label, 0)));
+    R->SetMovingImage(1, to_itk_3d(sitk::NotEqual(moving_label, 0)));
+    R->SetMetric(metric);
+    R->SetOptimizer(optimizer);
+    R->SetInitialTransform(initial);
+    R->InPlaceOn();
+    // a fixed seed keeps the result reproducible
+    R->SetMetricSamplingStrategy(RegistrationType::MetricSamplingStrategyEnum::RANDOM);
+    R->SetMetricSamplingPercentage(0.1);
+    R->MetricSamplingReinitializeSeed(42);
+    RegistrationType::ShrinkFactorsArrayType shrink(3);
+    RegistrationType::SmoothingSigmasArrayType smoothing(3);
+    for (int level = 0; level < 3; level++) {
+        shrink[level] = 1 << (2 - level);
+        smoothing[level] = 2 - level;
+    }
+    R->SetNumberOfLevels(3);
+    R->SetShrinkFactorsPerLevel(shrink);
+    R->SetSmoothingSigmasPerLevel(smoothing);
+    R->Update();
+
+    TransformType::MatrixType m = initial->GetMatrix();
+    TransformType::OutputVectorType t = initial->GetTranslation();
+    for (int i = 0; i < 3; i++) {
+        for (int j = 0; j < 3; j++) {
+            (*transform)[3 * i + j] = m(i, j);
+        }
+        (*transform)[i + 9] = t[i];
+    }
+}
+
+extern "C" void
+register_3d_labels_u8(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_i8(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_u16(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_i16(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_u32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_i32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_u64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_i64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_f32(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  float** fixed_arr,
+  float** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_3d_labels_f64(
+  unsigned int width,
+  unsigned int height,
+  unsigned int depth,
+  double** fixed_arr,
+  double** moving_arr,
+  uint8_t** fixed_label_arr,
+  uint8_t** moving_label_arr,
+  double** transform,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image_3d(width, height, depth, *fixed_arr, id);
+    sitk::Image moving = make_image_3d(width, height, depth, *moving_arr, id);
+    sitk::PixelIDValueEnum label_id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed_label = make_image_3d(width, height, depth, *fixed_label_arr, label_id);
+    sitk::Image moving_label = make_image_3d(width, height, depth, *moving_label_arr, label_id);
+    reg_3d_labels(fixed, moving, fixed_label, moving_label, transform);
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_u8(
+  unsigned int width,
+  unsigned int height,
+  uint8_t** fixed_arr,
+  uint8_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_i8(
+  unsigned int width,
+  unsigned int height,
+  int8_t** fixed_arr,
+  int8_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt8;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_u16(
+  unsigned int width,
+  unsigned int height,
+  uint16_t** fixed_arr,
+  uint16_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_i16(
+  unsigned int width,
+  unsigned int height,
+  int16_t** fixed_arr,
+  int16_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt16;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_u32(
+  unsigned int width,
+  unsigned int height,
+  uint32_t** fixed_arr,
+  uint32_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_i32(
+  unsigned int width,
+  unsigned int height,
+  int32_t** fixed_arr,
+  int32_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_u64(
+  unsigned int width,
+  unsigned int height,
+  uint64_t** fixed_arr,
+  uint64_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkUInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_i64(
+  unsigned int width,
+  unsigned int height,
+  int64_t** fixed_arr,
+  int64_t** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkInt64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_f32(
+  unsigned int width,
+  unsigned int height,
+  float** fixed_arr,
+  float** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat32;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
+}
+
+extern "C" void
+register_info_f64(
+  unsigned int width,
+  unsigned int height,
+  double** fixed_arr,
+  double** moving_arr,
+  unsigned int mode,
+  double* center,
+  double** transform,
+  registration_options* options,
+  registration_info* info,
+  metric_callback callback,
+  stop_callback stop,
+  void* data,
+  int* status
+) {
+  try {
+    sitk::PixelIDValueEnum id = sitk::PixelIDValueEnum::sitkFloat64;
+    sitk::Image fixed = make_image(width, height, *fixed_arr, id);
+    sitk::Image moving = make_image(width, height, *moving_arr, id);
+    double spacing[2] = {1.0, 1.0};
+    double direction[4] = {1.0, 0.0, 0.0, 1.0};
+    registration(
+      fixed, moving, mode, false, center, spacing, direction, transform, options,
+      nullptr, callback, data, nullptr, nullptr, info, stop
+    );
+  } catch (const std::exception &exc) {
+    set_error(exc, status);
+  }
 }
\ No newline at end of file
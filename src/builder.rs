@@ -0,0 +1,137 @@
+use crate::Transform;
+use anyhow::{Result, anyhow};
+
+/// build a transform from its components:
+///
+/// TransformBuilder::new().rotation(angle).scale(sx, sy).translation(tx, ty).build()
+///
+/// the components are always composed in the same order, independent of the order of the calls:
+/// first rotation, then scale, then translation, so the matrix is S * R, with the translation
+/// added after that, origin and shape are copied into the transform
+#[derive(Clone, Debug, Default)]
+pub struct TransformBuilder {
+    rotation: Option<f64>,
+    scale: Option<[f64; 2]>,
+    translation: Option<[f64; 2]>,
+    matrix: Option<[f64; 4]>,
+    origin: [f64; 2],
+    shape: [usize; 2],
+}
+
+impl TransformBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// counterclockwise rotation in radians
+    pub fn rotation(mut self, angle: f64) -> Self {
+        self.rotation = Some(angle);
+        self
+    }
+
+    /// scale in x and y
+    pub fn scale(mut self, sx: f64, sy: f64) -> Self {
+        self.scale = Some([sx, sy]);
+        self
+    }
+
+    /// translation in x and y
+    pub fn translation(mut self, tx: f64, ty: f64) -> Self {
+        self.translation = Some([tx, ty]);
+        self
+    }
+
+    /// the 2x2 part of the matrix directly: [m00, m01, m10, m11], cannot be combined with rotation
+    /// or scale
+    pub fn matrix(mut self, matrix: [f64; 4]) -> Self {
+        self.matrix = Some(matrix);
+        self
+    }
+
+    pub fn origin(mut self, ox: f64, oy: f64) -> Self {
+        self.origin = [ox, oy];
+        self
+    }
+
+    pub fn shape(mut self, shape: [usize; 2]) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn build(self) -> Result<Transform> {
+        let [a, b, c, d] = match self.matrix {
+            Some(matrix) => {
+                if self.rotation.is_some() || self.scale.is_some() {
+                    return Err(anyhow!("matrix cannot be combined with rotation or scale"));
+                }
+                matrix
+            }
+            None => {
+                let [sx, sy] = self.scale.unwrap_or([1f64, 1f64]);
+                if sx == 0f64 || sy == 0f64 {
+                    return Err(anyhow!("scale cannot be zero"));
+                }
+                let (sin, cos) = self.rotation.unwrap_or(0f64).sin_cos();
+                [sx * cos, -sx * sin, sy * sin, sy * cos]
+            }
+        };
+        let [tx, ty] = self.translation.unwrap_or([0f64, 0f64]);
+        let transform = Transform::new([a, b, c, d, tx, ty], self.origin, self.shape);
+        if !transform.is_finite() {
+            return Err(anyhow!(
+                "transform has non-finite parameters: {:?}",
+                transform.parameters
+            ));
+        }
+        Ok(transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn builder_translation() -> Result<()> {
+        let t = TransformBuilder::new().translation(5.0, 3.0).build()?;
+        assert_eq!(t, Transform::from_translation([5.0, 3.0]));
+        assert!(TransformBuilder::new().build()?.is_unity());
+        Ok(())
+    }
+
+    #[test]
+    fn builder_order() -> Result<()> {
+        let t = TransformBuilder::new()
+            .translation(1.0, 2.0)
+            .scale(2.0, 3.0)
+            .rotation(FRAC_PI_2)
+            .origin(10.0, 20.0)
+            .shape([40, 20])
+            .build()?;
+        let u = TransformBuilder::new()
+            .shape([40, 20])
+            .rotation(FRAC_PI_2)
+            .origin(10.0, 20.0)
+            .scale(2.0, 3.0)
+            .translation(1.0, 2.0)
+            .build()?;
+        assert_eq!(t, u);
+        // rotate (1, 0) to (0, 1), scale to (0, 3), translate to (1, 5)
+        let [x, y] = t.apply_to_point(1.0, 0.0);
+        assert!((x - 1.0).abs() < 1e-12);
+        assert!((y - 5.0).abs() < 1e-12);
+        assert_eq!(t.origin, [10.0, 20.0]);
+        assert_eq!(t.shape, [40, 20]);
+        Ok(())
+    }
+
+    #[test]
+    fn builder_contradiction() {
+        let b = TransformBuilder::new().matrix([1.0, 0.0, 0.0, 1.0]);
+        assert!(b.clone().build().is_ok());
+        assert!(b.clone().rotation(0.1).build().is_err());
+        assert!(b.scale(1.0, 2.0).build().is_err());
+        assert!(TransformBuilder::new().scale(0.0, 1.0).build().is_err());
+    }
+}
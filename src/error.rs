@@ -0,0 +1,73 @@
+use crate::Transform;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// errors with a known cause, can be recovered from an anyhow::Error with downcast_ref
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransformError {
+    /// fixed and moving images do not have the same shape
+    ShapeMismatch {
+        fixed: Vec<usize>,
+        moving: Vec<usize>,
+    },
+    /// the number of pixels in the image does not fit in the integer type used by the adapter
+    ImageTooLarge { shape: Vec<usize> },
+    /// registration resulted in parameters which are nan or infinite
+    NonFinite { parameters: Vec<f64> },
+    /// fewer point pairs than needed to determine a transform
+    InsufficientLandmarks { required: usize, found: usize },
+    /// registration was stopped by the cancel flag in RegistrationConfig
+    Cancelled,
+    /// registration took longer than the timeout in RegistrationConfig, with the transform found
+    /// until then, unless it was not finite
+    TimedOut {
+        elapsed: Duration,
+        transform: Option<Transform>,
+    },
+    /// registration failed for some frames in a series: the transform for each frame, None where
+    /// it failed, and the index and error for each failed frame
+    Series {
+        transforms: Vec<Option<Transform>>,
+        errors: Vec<(usize, String)>,
+    },
+}
+
+impl Display for TransformError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformError::ShapeMismatch { fixed, moving } => write!(
+                f,
+                "fixed and moving must have the same shape, got {:?} and {:?}",
+                fixed, moving
+            ),
+            TransformError::ImageTooLarge { shape } => {
+                write!(f, "image with shape {:?} is too large", shape)
+            }
+            TransformError::NonFinite { parameters } => {
+                write!(
+                    f,
+                    "registration resulted in non-finite parameters: {:?}",
+                    parameters
+                )
+            }
+            TransformError::InsufficientLandmarks { required, found } => write!(
+                f,
+                "at least {} landmark pairs are needed, got {}",
+                required, found
+            ),
+            TransformError::Cancelled => write!(f, "registration was cancelled"),
+            TransformError::TimedOut { elapsed, .. } => {
+                write!(f, "registration timed out after {:?}", elapsed)
+            }
+            TransformError::Series { errors, .. } => {
+                write!(f, "registration failed for {} frames:", errors.len())?;
+                for (i, e) in errors {
+                    write!(f, " frame {}: {};", i, e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransformError {}
@@ -0,0 +1,255 @@
+#[cfg(feature = "elastix")]
+use crate::sys::register_bspline;
+use crate::sys::{register_demons, warp};
+use crate::{Interpolator, PixelType};
+use anyhow::{Result, anyhow};
+use ndarray::{Array2, AsArray, Ix2};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{from_reader, to_writer};
+use std::fs::File;
+use std::path::PathBuf;
+
+/// a dense deformation: for each pixel (x, y) in the fixed image, the pixel (x + dx, y + dy) in
+/// the moving image maps onto it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "UncheckedField")]
+pub struct DisplacementField {
+    pub dx: Array2<f64>,
+    pub dy: Array2<f64>,
+    pub shape: [usize; 2],
+}
+
+/// a displacement field as read from a file, before checking that dx and dy have its shape
+#[derive(Deserialize)]
+struct UncheckedField {
+    dx: Array2<f64>,
+    dy: Array2<f64>,
+    shape: [usize; 2],
+}
+
+impl TryFrom<UncheckedField> for DisplacementField {
+    type Error = anyhow::Error;
+
+    fn try_from(field: UncheckedField) -> Result<Self> {
+        let field = Self {
+            dx: field.dx,
+            dy: field.dy,
+            shape: field.shape,
+        };
+        field.check_shape()?;
+        Ok(field)
+    }
+}
+
+impl PartialEq<Self> for DisplacementField {
+    fn eq(&self, other: &Self) -> bool {
+        self.dx == other.dx && self.dy == other.dy && self.shape == other.shape
+    }
+}
+
+impl DisplacementField {
+    /// dx and dy: displacement of each pixel in x and y, both with the shape of the image
+    pub fn new(dx: Array2<f64>, dy: Array2<f64>) -> Result<Self> {
+        if dx.shape() != dy.shape() {
+            return Err(anyhow!(
+                "dx and dy have different shapes: {:?} and {:?}",
+                dx.shape(),
+                dy.shape()
+            ));
+        }
+        let (height, width) = dx.dim();
+        Ok(Self {
+            dx,
+            dy,
+            shape: [height, width],
+        })
+    }
+
+    /// find the deformation which transforms moving into fixed, using a bspline transform with
+    /// control points grid_spacing (rows, columns) pixels apart, requires the elastix feature
+    #[cfg(feature = "elastix")]
+    pub fn register_bspline<'a, A, T>(
+        fixed: A,
+        moving: A,
+        grid_spacing: [usize; 2],
+    ) -> Result<DisplacementField>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let fixed = fixed.into();
+        let (height, width) = fixed.dim();
+        let field = register_bspline(fixed, moving.into(), grid_spacing)?;
+        Self::from_interleaved(&field, [height, width])
+    }
+
+    /// find the deformation which transforms moving into fixed, using symmetric forces demons,
+    /// sigma: standard deviation in pixels of the gaussian smoothing of the field in each iteration
+    pub fn register_demons<'a, A, T>(
+        fixed: A,
+        moving: A,
+        iterations: u32,
+        sigma: f64,
+    ) -> Result<DisplacementField>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let fixed = fixed.into();
+        let (height, width) = fixed.dim();
+        let field = register_demons(fixed, moving.into(), iterations, sigma)?;
+        Self::from_interleaved(&field, [height, width])
+    }
+
+    /// dx and dy must both have the shape of the field, the fields are public, so check this
+    /// before the adapter reads them
+    fn check_shape(&self) -> Result<()> {
+        let shape = (self.shape[0], self.shape[1]);
+        if self.dx.dim() != shape || self.dy.dim() != shape {
+            return Err(anyhow!(
+                "dx and dy have shapes {:?} and {:?}, not the shape of the field {:?}",
+                self.dx.shape(),
+                self.dy.shape(),
+                self.shape
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn from_interleaved(field: &[f64], shape: [usize; 2]) -> Result<Self> {
+        let dx = field.iter().step_by(2).copied().collect();
+        let dy = field.iter().skip(1).step_by(2).copied().collect();
+        Self::new(
+            Array2::from_shape_vec((shape[0], shape[1]), dx)?,
+            Array2::from_shape_vec((shape[0], shape[1]), dy)?,
+        )
+    }
+
+    pub(crate) fn to_interleaved(&self) -> Vec<f64> {
+        self.dx
+            .iter()
+            .zip(self.dy.iter())
+            .flat_map(|(dx, dy)| [*dx, *dy])
+            .collect()
+    }
+
+    /// read a displacement field from a file
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let file = File::open(path)?;
+        Ok(from_reader(file)?)
+    }
+
+    /// write a displacement field to a file
+    pub fn to_file(&self, path: PathBuf) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        to_writer(&mut file, self)?;
+        Ok(())
+    }
+
+    /// deform an image using the given interpolation
+    pub fn warp_image<'a, A, T>(&self, image: A, interpolator: Interpolator) -> Result<Array2<T>>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let image = image.into();
+        if image.shape() != self.shape {
+            return Err(anyhow!(
+                "image shape {:?} does not match the shape of the field {:?}",
+                image.shape(),
+                self.shape
+            ));
+        }
+        self.check_shape()?;
+        warp(&self.to_interleaved(), image, interpolator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::julia_image;
+    use std::f64::consts::PI;
+    use tempfile::NamedTempFile;
+
+    fn smooth_field(shape: [usize; 2]) -> Result<DisplacementField> {
+        let dx = Array2::from_shape_fn((shape[0], shape[1]), |(y, _)| {
+            2f64 * (2f64 * PI * y as f64 / shape[0] as f64).sin()
+        });
+        let dy = Array2::from_shape_fn((shape[0], shape[1]), |(_, x)| {
+            1.5 * (2f64 * PI * x as f64 / shape[1] as f64).cos()
+        });
+        DisplacementField::new(dx, dy)
+    }
+
+    #[test]
+    fn test_serialization() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        let f = smooth_field([12, 10])?;
+        f.to_file(file.path().to_path_buf())?;
+        let g = DisplacementField::from_file(file.path().to_path_buf())?;
+        assert_eq!(f, g);
+        Ok(())
+    }
+
+    #[test]
+    fn inconsistent_field() -> Result<()> {
+        let mut f = smooth_field([12, 10])?;
+        f.dx = Array2::zeros((2, 2));
+        let yaml = serde_yaml::to_string(&f)?;
+        assert!(serde_yaml::from_str::<DisplacementField>(&yaml).is_err());
+        let image = Array2::<u8>::zeros((12, 10));
+        assert!(
+            f.warp_image(image.view(), Interpolator::NearestNeighbor)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_interleaved() -> Result<()> {
+        let f = smooth_field([12, 10])?;
+        let g = DisplacementField::from_interleaved(&f.to_interleaved(), f.shape)?;
+        assert_eq!(f, g);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "elastix")]
+    fn registration_bspline() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let (height, width) = j.dim();
+        let f = smooth_field([height, width])?;
+        let k = f.warp_image(j.view(), Interpolator::BSpline { order: 3 })?;
+        let g = DisplacementField::register_bspline(k.view(), j.view(), [16, 16])?;
+        let interior = ndarray::s![50..height - 50, 50..width - 50];
+        let n = ((height - 100) * (width - 100)) as f64;
+        let ex = (&g.dx.slice(interior) - &f.dx.slice(interior)).abs().sum() / n;
+        let ey = (&g.dy.slice(interior) - &f.dy.slice(interior)).abs().sum() / n;
+        assert!(ex < 0.5);
+        assert!(ey < 0.5);
+        let n = f.warp_image(j.view(), Interpolator::NearestNeighbor)?;
+        assert_eq!(n.shape(), j.shape());
+        Ok(())
+    }
+
+    #[test]
+    fn registration_demons() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let (height, width) = j.dim();
+        let f = smooth_field([height, width])?;
+        let k = f.warp_image(j.view(), Interpolator::BSpline { order: 3 })?;
+        let g = DisplacementField::register_demons(k.view(), j.view(), 200, 1.5)?;
+        let interior = ndarray::s![50..height - 50, 50..width - 50];
+        let n = ((height - 100) * (width - 100)) as f64;
+        let ex = (&g.dx.slice(interior) - &f.dx.slice(interior)).abs().sum() / n;
+        let ey = (&g.dy.slice(interior) - &f.dy.slice(interior)).abs().sum() / n;
+        assert!(ex < 1.0);
+        assert!(ey < 1.0);
+        Ok(())
+    }
+}
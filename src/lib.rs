@@ -1,24 +1,88 @@
+mod builder;
+mod error;
+mod field;
+mod options;
+mod pyramid;
+pub mod series;
+mod stack;
 mod sys;
+mod transform3d;
 
-use crate::sys::{interp, register};
+pub use crate::builder::TransformBuilder;
+pub use crate::error::TransformError;
+pub use crate::field::DisplacementField;
+pub use crate::options::{
+    Cancel, Metric, Normalization, Optimizer, Progress, RegistrationConfig,
+    RegistrationConfigBuilder, RegistrationOptions, RegistrationOptionsBuilder, ScalesFrom,
+};
+pub use crate::stack::TransformStack;
+pub use crate::transform3d::Transform3D;
+
+#[cfg(feature = "elastix")]
+use crate::sys::register_vector;
+use crate::sys::{
+    check_not_empty, interp, interp_into, register, register_3d, register_exhaustive,
+    register_info, register_masked, register_recording, register_resample,
+};
 use anyhow::{Result, anyhow};
-use ndarray::{Array2, ArrayView2, AsArray, Ix2, array, s};
+#[cfg(feature = "rayon")]
+use ndarray::parallel::prelude::*;
+use ndarray::{Array2, ArrayView1, ArrayView2, ArrayViewMut1, AsArray, Ix2, Ix3, Zip, array, s};
+use num::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use serde_yaml::{from_reader, to_writer};
 use std::fs::File;
 use std::ops::Mul;
+use std::panic::resume_unwind;
 use std::path::PathBuf;
+use std::sync::OnceLock;
+#[cfg(not(feature = "rayon"))]
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// the version of ITK this crate was built with
+pub fn itk_version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(sys::itk_version)
+}
+
+/// the version of SimpleITK this crate was built with
+pub fn simpleitk_version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(sys::simpleitk_version)
+}
 
 /// a trait marking number types that can be used in sitk:
-/// (u/i)(8/16/32/64), (u/i)size, f(32/64)
-pub trait PixelType: Clone {
+/// (u/i)(8/16/32/64), (u/i)size, f(32/64), and f16 with the half feature
+///
+/// PT is the code of the type the pixels are passed to the adapter as:
+/// 1: u8, 2: i8, 3: u16, 4: i16, 5: u32, 6: i32, 7: u64, 8: i64, 9: f32, 10: f64,
+/// 11: f16 (only with the half feature)
+///
+/// # Safety
+///
+/// the pixels are passed to the adapter as the type given by PT, so the type must have the same
+/// size and layout, a newtype can be used as pixel type when it is #[repr(transparent)]:
+///
+/// ```
+/// use sitk_registration_sys::PixelType;
+///
+/// #[derive(Clone)]
+/// #[repr(transparent)]
+/// struct Celsius(f32);
+///
+/// unsafe impl PixelType for Celsius {
+///     const PT: u8 = 9;
+/// }
+/// ```
+pub unsafe trait PixelType: Clone {
     const PT: u8;
 }
 
 macro_rules! sitk_impl {
     ($($T:ty: $sitk:expr $(,)?)*) => {
         $(
-            impl PixelType for $T {
+            unsafe impl PixelType for $T {
                 const PT: u8 = $sitk;
             }
         )*
@@ -46,6 +110,216 @@ sitk_impl!(usize: 5);
 sitk_impl!(isize: 8);
 #[cfg(target_pointer_width = "32")]
 sitk_impl!(isize: 6);
+#[cfg(feature = "half")]
+sitk_impl!(half::f16: 11);
+
+/// interleave two images in a checkerboard pattern of tiles[0] by tiles[1] tiles (x, y), like
+/// SimpleITK's CheckerBoardImageFilter: tiles with an even sum of indices come from fixed, the
+/// others from moving, so misalignment shows at the tile boundaries
+pub fn checkerboard<'a, A, T>(fixed: A, moving: A, tiles: [u32; 2]) -> Result<Array2<T>>
+where
+    T: 'a + Clone,
+    A: AsArray<'a, T, Ix2>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    if fixed.shape() != moving.shape() {
+        return Err(anyhow!("fixed and moving must have the same shape"));
+    }
+    if tiles.contains(&0) {
+        return Err(anyhow!("the number of tiles must be at least 1"));
+    }
+    let (height, width) = fixed.dim();
+    Ok(Array2::from_shape_fn((height, width), |(y, x)| {
+        let tx = x * tiles[0] as usize / width;
+        let ty = y * tiles[1] as usize / height;
+        if (tx + ty) % 2 == 0 {
+            fixed[[y, x]].clone()
+        } else {
+            moving[[y, x]].clone()
+        }
+    }))
+}
+
+/// sum of squared differences between two images
+pub fn ssd<'a, A, T>(a: A, b: A) -> Result<f64>
+where
+    T: 'a + Clone + Into<f64>,
+    A: AsArray<'a, T, Ix2>,
+{
+    let a = a.into();
+    let b = b.into();
+    if a.shape() != b.shape() {
+        return Err(anyhow!(
+            "images have different shapes: {:?} and {:?}",
+            a.shape(),
+            b.shape()
+        ));
+    }
+    Ok(a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a.clone().into() - b.clone().into()).powi(2))
+        .sum())
+}
+
+/// root mean square error between two images
+pub fn rmse<'a, A, T>(a: A, b: A) -> Result<f64>
+where
+    T: 'a + Clone + Into<f64>,
+    A: AsArray<'a, T, Ix2>,
+{
+    let a = a.into();
+    let n = a.len();
+    if n == 0 {
+        return Err(anyhow!("cannot compute the rmse of empty images"));
+    }
+    Ok((ssd(a, b.into())? / n as f64).sqrt())
+}
+
+/// crop fixed and moving to the roi in config, if any, returns the cropped images, the offset
+/// (x, y) of the roi, such that a point p in the full images is p - offset in the roi, and the
+/// shape of the full images
+#[allow(clippy::type_complexity)]
+fn crop_to_roi<'a, T>(
+    fixed: ArrayView2<'a, T>,
+    moving: ArrayView2<'a, T>,
+    config: &RegistrationConfig,
+) -> Result<(ArrayView2<'a, T>, ArrayView2<'a, T>, [f64; 2], [usize; 2])> {
+    if fixed.shape() != moving.shape() {
+        return Err(TransformError::ShapeMismatch {
+            fixed: fixed.shape().to_vec(),
+            moving: moving.shape().to_vec(),
+        }
+        .into());
+    }
+    let (height, width) = fixed.dim();
+    let Some(([row, col], [h, w])) = config.roi else {
+        return Ok((fixed, moving, [0f64, 0f64], [height, width]));
+    };
+    if h == 0 || w == 0 || row + h > height || col + w > width {
+        return Err(anyhow!(
+            "roi {:?} is empty or outside the image with shape {:?}",
+            [row, col, h, w],
+            [height, width]
+        ));
+    }
+    let slice = s![row..row + h, col..col + w];
+    Ok((
+        fixed.slice_move(slice),
+        moving.slice_move(slice),
+        [col as f64, row as f64],
+        [height, width],
+    ))
+}
+
+/// least squares fit of the parameters of an affine transform with origin, mapping src onto dst
+fn fit_affine(src: &[[f64; 2]], dst: &[[f64; 2]], origin: [f64; 2]) -> Result<[f64; 6]> {
+    // normal equations: (A^T A) p = A^T b, with rows [x - ox, y - oy, 1] in A
+    let mut ata = Array2::<f64>::zeros((3, 3));
+    let mut atb = Array2::<f64>::zeros((3, 2));
+    for (s, d) in src.iter().zip(dst.iter()) {
+        let a = [s[0] - origin[0], s[1] - origin[1], 1f64];
+        for i in 0..3 {
+            for j in 0..3 {
+                ata[[i, j]] += a[i] * a[j];
+            }
+            atb[[i, 0]] += a[i] * (d[0] - origin[0]);
+            atb[[i, 1]] += a[i] * (d[1] - origin[1]);
+        }
+    }
+    let det = |m: &Array2<f64>| {
+        m[[0, 0]] * (m[[1, 1]] * m[[2, 2]] - m[[1, 2]] * m[[2, 1]])
+            - m[[0, 1]] * (m[[1, 0]] * m[[2, 2]] - m[[1, 2]] * m[[2, 0]])
+            + m[[0, 2]] * (m[[1, 0]] * m[[2, 1]] - m[[1, 1]] * m[[2, 0]])
+    };
+    let d = det(&ata);
+    if d.abs() < 1e-12 {
+        return Err(anyhow!(
+            "points are degenerate, at least 3 non-collinear points are needed"
+        ));
+    }
+    // Cramer's rule, for x and y separately
+    let mut p = [[0f64; 3]; 2];
+    for (k, pk) in p.iter_mut().enumerate() {
+        for (i, pki) in pk.iter_mut().enumerate() {
+            let mut m = ata.clone();
+            m.column_mut(i).assign(&atb.column(k));
+            *pki = det(&m) / d;
+        }
+    }
+    Ok([p[0][0], p[0][1], p[1][0], p[1][1], p[0][2], p[1][2]])
+}
+
+/// the interpolation used when transforming an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolator {
+    /// bspline of order 0 to 5, 3 is cubic
+    BSpline {
+        order: u32,
+    },
+    NearestNeighbor,
+}
+
+/// alias for [`Interpolator`]
+pub type Interpolation = Interpolator;
+
+/// the kind of transform registration looks for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// translation only
+    Translation = 0,
+    /// full affine: translation, rotation, scale and shear
+    Affine = 1,
+    /// rigid body: rotation and translation
+    Rigid = 2,
+    /// similarity: isotropic scale, rotation and translation
+    Similarity = 3,
+    /// rotation about a fixed center only
+    Rotation = 4,
+    /// isotropic scale about a fixed center only
+    Scale = 5,
+    /// scale about a fixed center only, independently in x and y
+    AnisotropicScale = 6,
+}
+
+/// a transform found by registration, with how the optimizer ended
+#[derive(Clone, Debug)]
+pub struct RegistrationResult {
+    pub transform: Transform,
+    /// value of the metric at the end, lower is better
+    pub final_metric: f64,
+    /// number of iterations of the optimizer, summed over all resolutions, 0 when registered
+    /// with elastix, which does not report them
+    pub iterations: u32,
+    /// time taken by the registration
+    pub elapsed: Duration,
+    /// why the optimizer stopped, as described by ITK
+    pub stop_condition: String,
+    /// the value of the metric after each iteration, over all resolutions, empty when
+    /// registered with elastix
+    pub metric_history: Vec<f64>,
+}
+
+/// the components of an affine transform, the matrix is R * K * S, with R a counterclockwise
+/// rotation by angle (radians), K = [[1, shear], [0, 1]] and S = diag(scale_x, scale_y), the
+/// translation is added after that
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AffineComponents {
+    pub angle: f64,
+    pub scale_x: f64,
+    pub scale_y: f64,
+    pub shear: f64,
+    pub tx: f64,
+    pub ty: f64,
+}
+
+fn default_spacing() -> [f64; 2] {
+    [1f64, 1f64]
+}
+
+fn default_direction() -> [f64; 4] {
+    [1f64, 0f64, 0f64, 1f64]
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Transform {
@@ -53,13 +327,21 @@ pub struct Transform {
     pub dparameters: [f64; 6],
     pub origin: [f64; 2],
     pub shape: [usize; 2],
+    /// physical size of a pixel (x, y), parameters and origin are in physical units
+    #[serde(default = "default_spacing")]
+    pub spacing: [f64; 2],
+    /// direction cosine matrix of the image axes, flat and row-major
+    #[serde(default = "default_direction")]
+    pub direction: [f64; 4],
 }
 
-impl Mul for Transform {
+/// the transform which applies other first and then self: the product of the matrices, the
+/// origin, shape, spacing and direction are those of self
+impl Mul<&Transform> for &Transform {
     type Output = Transform;
 
     #[allow(clippy::suspicious_arithmetic_impl)]
-    fn mul(self, other: Transform) -> Transform {
+    fn mul(self, other: &Transform) -> Transform {
         let m = self.matrix().dot(&other.matrix());
         let dm = self.dmatrix().dot(&other.matrix()) + self.matrix().dot(&other.dmatrix());
         Transform {
@@ -68,34 +350,76 @@ impl Mul for Transform {
                 m[[0, 1]],
                 m[[1, 0]],
                 m[[1, 1]],
-                m[[2, 0]],
-                m[[2, 1]],
+                m[[0, 2]],
+                m[[1, 2]],
             ],
             dparameters: [
                 dm[[0, 0]],
                 dm[[0, 1]],
                 dm[[1, 0]],
                 dm[[1, 1]],
-                dm[[2, 0]],
-                dm[[2, 1]],
+                dm[[0, 2]],
+                dm[[1, 2]],
             ],
             origin: self.origin,
             shape: self.shape,
+            spacing: self.spacing,
+            direction: self.direction,
         }
     }
 }
 
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        &self * &other
+    }
+}
+
+impl Mul<&Transform> for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: &Transform) -> Transform {
+        &self * other
+    }
+}
+
+impl Mul<Transform> for &Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        self * &other
+    }
+}
+
+/// the identity transform
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::new([1f64, 0f64, 0f64, 1f64, 0f64, 0f64], [0f64; 2], [0usize; 2])
+    }
+}
+
 impl PartialEq<Self> for Transform {
     fn eq(&self, other: &Self) -> bool {
         self.parameters == other.parameters
             && self.dparameters == other.dparameters
             && self.origin == other.origin
             && self.shape == other.shape
+            && self.spacing == other.spacing
+            && self.direction == other.direction
     }
 }
 
 impl Eq for Transform {}
 
+/// the 3x3 matrix, like Transform::matrix
+impl From<&Transform> for Array2<f64> {
+    fn from(transform: &Transform) -> Self {
+        transform.matrix()
+    }
+}
+
 impl Transform {
     /// parameters: flat 2x2 part of matrix, translation; origin: center of rotation
     pub fn new(parameters: [f64; 6], origin: [f64; 2], shape: [usize; 2]) -> Self {
@@ -104,382 +428,4083 @@ impl Transform {
             dparameters: [0f64; 6],
             origin,
             shape,
+            spacing: default_spacing(),
+            direction: default_direction(),
         }
     }
 
-    /// find the affine transform which transforms moving into fixed
-    pub fn register_affine<'a, A, T>(fixed: A, moving: A) -> Result<Transform>
+    /// find the transform of the kind given by mode which transforms moving into fixed
+    pub fn register<'a, A, T>(
+        fixed: A,
+        moving: A,
+        mode: RegistrationMode,
+        config: &RegistrationConfig,
+    ) -> Result<Transform>
     where
         T: 'a + PixelType,
         A: AsArray<'a, T, Ix2>,
     {
-        let (parameters, origin, shape) = register(fixed, moving, true)?;
-        Ok(Transform {
+        if config.progress.is_some() || config.cancel.is_some() || config.timeout.is_some() {
+            return Ok(Self::register_with_result(fixed, moving, mode, config)?.transform);
+        }
+        let (fixed, moving, offset, shape) = crop_to_roi(fixed.into(), moving.into(), config)?;
+        let (parameters, origin, _) = register(
+            fixed,
+            moving,
+            mode,
+            false,
+            config.center.map(|[x, y]| [x - offset[0], y - offset[1]]),
+            None,
+            Some(&config.options),
+        )?;
+        Ok(Transform::new(
             parameters,
-            dparameters: [0f64; 6],
-            origin,
+            [origin[0] + offset[0], origin[1] + offset[1]],
             shape,
-        })
+        ))
     }
 
-    /// find the translation which transforms moving into fixed
-    pub fn register_translation<'a, A, T>(fixed: A, moving: A) -> Result<Transform>
+    /// like register, but with the final value of the metric, the number of iterations, the time
+    /// taken and why the optimizer stopped, this uses the same registration method as register,
+    /// so the transform is the same
+    pub fn register_with_result<'a, A, T>(
+        fixed: A,
+        moving: A,
+        mode: RegistrationMode,
+        config: &RegistrationConfig,
+    ) -> Result<RegistrationResult>
     where
         T: 'a + PixelType,
         A: AsArray<'a, T, Ix2>,
     {
-        let (parameters, origin, shape) = register(fixed, moving, false)?;
-        Ok(Transform {
-            parameters,
-            dparameters: [0f64; 6],
-            origin,
-            shape,
+        let start = Instant::now();
+        let (fixed, moving, offset, shape) = crop_to_roi(fixed.into(), moving.into(), config)?;
+        let (height, width) = fixed.dim();
+        check_not_empty(fixed.shape())?;
+        let center = config.center.unwrap_or([
+            ((width - 1) as f64) / 2f64 + offset[0],
+            ((height - 1) as f64) / 2f64 + offset[1],
+        ]);
+        let (parameters, final_metric, iterations, stop_condition, metric_history) = register_info(
+            fixed,
+            moving,
+            mode,
+            [center[0] - offset[0], center[1] - offset[1]],
+            config,
+        )
+        .map_err(|e| match e.downcast::<TransformError>() {
+            // the transform so far is on the roi
+            Ok(TransformError::TimedOut { elapsed, transform }) => TransformError::TimedOut {
+                elapsed,
+                transform: transform.map(|t| Transform::new(t.parameters, center, shape)),
+            }
+            .into(),
+            Ok(e) => e.into(),
+            Err(e) => e,
+        })?
+        // a panic in the progress callback, raised again now that the adapter is unlocked
+        .unwrap_or_else(|panic| resume_unwind(panic));
+        Ok(RegistrationResult {
+            transform: Transform::new(parameters, center, shape),
+            final_metric,
+            iterations,
+            elapsed: start.elapsed(),
+            stop_condition,
+            metric_history,
         })
     }
 
-    /// create a transform from a xy translation
-    pub fn from_translation(translation: [f64; 2]) -> Self {
-        Transform {
-            parameters: [1f64, 0f64, 0f64, 1f64, translation[0], translation[1]],
-            dparameters: [0f64; 6],
-            origin: [0f64; 2],
-            shape: [0usize; 2],
-        }
+    /// like register_affine, but with the final value of the metric, the number of iterations, the
+    /// time taken and why the optimizer stopped, using the same registration method as
+    /// register_affine
+    pub fn register_affine_with_result<'a, A, T>(fixed: A, moving: A) -> Result<RegistrationResult>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        Self::register_with_result(
+            fixed,
+            moving,
+            RegistrationMode::Affine,
+            &RegistrationConfig::default(),
+        )
     }
 
-    /// read a transform from a file
-    pub fn from_file(path: PathBuf) -> Result<Self> {
-        let file = File::open(path)?;
-        Ok(from_reader(file)?)
+    /// find the transform of the kind given by mode which transforms moving into fixed, and
+    /// transform moving with it, in one call to the adapter
+    pub fn register_and_resample<'a, A, T>(
+        fixed: A,
+        moving: A,
+        mode: RegistrationMode,
+        interpolator: Interpolator,
+    ) -> Result<(Transform, Array2<T>)>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape, image) =
+            register_resample(fixed, moving, mode, interpolator)?;
+        Ok((Transform::new(parameters, origin, shape), image))
     }
 
-    /// write a transform to a file
-    pub fn to_file(&self, path: PathBuf) -> Result<()> {
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(path)?;
-        to_writer(&mut file, self)?;
-        Ok(())
+    /// find the affine transform which transforms moving into fixed
+    pub fn register_affine<'a, A, T>(fixed: A, moving: A) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape) = register(
+            fixed,
+            moving,
+            RegistrationMode::Affine,
+            false,
+            None,
+            None,
+            None,
+        )?;
+        Ok(Transform::new(parameters, origin, shape))
     }
 
-    /// true if transform does nothing
-    pub fn is_unity(&self) -> bool {
-        self.parameters == [1f64, 0f64, 0f64, 1f64, 0f64, 0f64]
+    /// find the affine transform which transforms moving into fixed for each pair (fixed, moving),
+    /// the pairs are registered concurrently, by rayon with the rayon feature and otherwise by a
+    /// scoped thread per available core, the adapter still registers one pair at a time, but the
+    /// work around it overlaps
+    pub fn register_affine_concurrent_batch<'a, T>(
+        pairs: &[(ArrayView2<'a, T>, ArrayView2<'a, T>)],
+    ) -> Vec<Result<Transform>>
+    where
+        T: 'a + PixelType + Sync,
+    {
+        let f = |(fixed, moving): &(ArrayView2<'a, T>, ArrayView2<'a, T>)| {
+            Transform::register_affine(*fixed, *moving)
+        };
+        #[cfg(feature = "rayon")]
+        {
+            pairs.par_iter().map(f).collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            if pairs.is_empty() {
+                return Vec::new();
+            }
+            let threads = thread::available_parallelism().map_or(1, |n| n.get());
+            thread::scope(|scope| {
+                pairs
+                    .chunks(pairs.len().div_ceil(threads))
+                    .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .flat_map(|handle| handle.join().unwrap_or_else(|panic| resume_unwind(panic)))
+                    .collect()
+            })
+        }
     }
 
-    /// transform an image using nearest neighbor interpolation
-    pub fn transform_image_bspline<'a, A, T>(&self, image: A) -> Result<Array2<T>>
+    /// find the affine transform which transforms moving into fixed by running the registration
+    /// n times, first from the identity and then from random starting points, and keeping the
+    /// result with the lowest final value of the metric, like register_affine_recording this uses
+    /// the sitk registration method
+    pub fn register_affine_best_of_n<'a, A, T>(fixed: A, moving: A, n: u32) -> Result<Transform>
     where
         T: 'a + PixelType,
         A: AsArray<'a, T, Ix2>,
     {
-        interp(self.parameters, self.origin, image, false)
+        if n == 0 {
+            return Err(anyhow!("n must be at least 1"));
+        }
+        let fixed = fixed.into();
+        let moving = moving.into();
+        let (height, width) = fixed.dim();
+        check_not_empty(fixed.shape())?;
+        let center = [((width - 1) as f64) / 2f64, ((height - 1) as f64) / 2f64];
+        // splitmix64 with a fixed seed, so that the result is reproducible, returns [-1, 1)
+        let mut state = 0u64;
+        let mut random = || {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            ((z ^ (z >> 31)) >> 11) as f64 / (1u64 << 52) as f64 - 1f64
+        };
+        let mut best: Option<([f64; 6], f64)> = None;
+        let mut error = None;
+        for i in 0..n {
+            let initial = if i == 0 {
+                None
+            } else {
+                // up to 5 degrees rotation, 5% scale and 5% of the image size translation
+                let (sin, cos) = (5f64.to_radians() * random()).sin_cos();
+                let scale = 1f64 + 0.05 * random();
+                Some([
+                    scale * cos,
+                    -scale * sin,
+                    scale * sin,
+                    scale * cos,
+                    0.05 * width as f64 * random(),
+                    0.05 * height as f64 * random(),
+                ])
+            };
+            match register_recording(
+                fixed,
+                moving,
+                RegistrationMode::Affine,
+                center,
+                initial,
+                None,
+            ) {
+                Ok((parameters, history)) => {
+                    let metric = history.last().copied().unwrap_or(f64::INFINITY);
+                    if best.is_none_or(|(_, m)| metric < m) {
+                        best = Some((parameters, metric));
+                    }
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        match best {
+            Some((parameters, _)) => Ok(Transform::new(parameters, center, [height, width])),
+            None => Err(error.unwrap_or_else(|| anyhow!("registration failed"))),
+        }
     }
 
-    /// transform an image using bspline interpolation
-    pub fn transform_image_nearest_neighbor<'a, A, T>(&self, image: A) -> Result<Array2<T>>
+    /// find the affine transform which transforms moving into fixed, and its inverse
+    ///
+    /// forward: use with transform_image_* on moving to resample it onto fixed, it maps pixel
+    /// coordinates in fixed onto the corresponding coordinates in moving
+    /// inverse: use with transform_coordinates to map coordinates found in moving onto fixed
+    pub fn register_affine_both<'a, A, T>(fixed: A, moving: A) -> Result<(Transform, Transform)>
     where
         T: 'a + PixelType,
         A: AsArray<'a, T, Ix2>,
     {
-        interp(self.parameters, self.origin, image, true)
+        let forward = Transform::register_affine(fixed, moving)?;
+        let inverse = forward.inverse()?;
+        if !inverse.is_finite() {
+            return Err(anyhow!(
+                "transform matrix is singular: {:?}",
+                forward.parameters
+            ));
+        }
+        Ok((forward, inverse))
     }
 
-    /// get coordinates resulting from transforming input coordinates
-    pub fn transform_coordinates<'a, A, T>(&self, coordinates: A) -> Result<Array2<f64>>
+    /// find the affine transform which transforms moving into fixed, and the value of the metric
+    /// after each iteration of the optimizer, this uses the sitk registration method instead of
+    /// elastix, so the result can differ slightly from register_affine
+    pub fn register_affine_recording<'a, A, T>(fixed: A, moving: A) -> Result<(Transform, Vec<f64>)>
     where
-        T: 'a + Clone + Into<f64>,
+        T: 'a + PixelType,
         A: AsArray<'a, T, Ix2>,
     {
-        let coordinates = coordinates.into();
-        let s = coordinates.shape();
-        if s[1] != 2 {
-            return Err(anyhow!("coordinates must have two columns"));
+        let fixed = fixed.into();
+        let (height, width) = fixed.dim();
+        check_not_empty(fixed.shape())?;
+        let center = [((width - 1) as f64) / 2f64, ((height - 1) as f64) / 2f64];
+        let (parameters, history) = register_recording(
+            fixed,
+            moving.into(),
+            RegistrationMode::Affine,
+            center,
+            None,
+            None,
+        )?;
+        Ok((Transform::new(parameters, center, [height, width]), history))
+    }
+
+    /// find the affine transform which transforms moving into fixed, first on both images
+    /// downsampled by averaging blocks of downscale x downscale pixels, and then at full resolution
+    /// starting from the result of that, when the motion is large, the full resolution stage
+    /// needs fewer iterations than without the coarse stage
+    pub fn register_affine_coarse_to_fine<'a, A, T>(
+        fixed: A,
+        moving: A,
+        downscale: usize,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType + ToPrimitive,
+        A: AsArray<'a, T, Ix2>,
+    {
+        Ok(Self::coarse_to_fine(fixed, moving, downscale)?.0)
+    }
+
+    /// find the affine transform which transforms moving into fixed at levels scales: both images
+    /// are downsampled by 2^(levels - 1), ..., 2, 1 after gaussian smoothing, and registered at
+    /// each scale, starting from the result of the coarser scale, which helps to find large
+    /// displacements
+    pub fn register_affine_multiscale<'a, A, T>(
+        fixed: A,
+        moving: A,
+        levels: u32,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType + ToPrimitive,
+        A: AsArray<'a, T, Ix2>,
+    {
+        if levels == 0 {
+            return Err(anyhow!("levels cannot be zero"));
         }
-        let m = self.matrix();
-        let mut res = Array2::zeros([s[0], s[1]]);
-        for i in 0..s[0] {
-            let a = array![
-                coordinates[[i, 0]].clone().into(),
-                coordinates[[i, 1]].clone().into(),
-                1f64
-            ]
-            .to_owned();
-            let b = m.dot(&a);
-            res.slice_mut(s![i, ..]).assign(&b.slice(s![..2]));
+        let fixed = fixed.into();
+        let moving = moving.into();
+        let (height, width) = fixed.dim();
+        check_not_empty(fixed.shape())?;
+        let center = [((width - 1) as f64) / 2f64, ((height - 1) as f64) / 2f64];
+        // the multiscale is done here, so each scale is registered at that scale only
+        let options = RegistrationOptions::builder().pyramid_levels(1).build()?;
+        // the current transform about center in full resolution coordinates
+        let mut parameters = [1f64, 0f64, 0f64, 1f64, 0f64, 0f64];
+        for level in (0..levels).rev() {
+            // at this scale, pixel q is at f * q in the full image: the matrix is the same, but
+            // the center and translation are divided by f
+            let f = (1usize << level) as f64;
+            let mut initial = parameters;
+            initial[4] /= f;
+            initial[5] /= f;
+            let (p, _) = register_recording(
+                &pyramid::gaussian_downsample(fixed, 1 << level)?,
+                &pyramid::gaussian_downsample(moving, 1 << level)?,
+                RegistrationMode::Affine,
+                center.map(|c| c / f),
+                Some(initial),
+                Some(&options),
+            )?;
+            parameters = p;
+            parameters[4] *= f;
+            parameters[5] *= f;
         }
-        Ok(res)
+        Ok(Transform::new(parameters, center, [height, width]))
     }
 
-    /// get the matrix defining the transform
-    pub fn matrix(&self) -> Array2<f64> {
-        Array2::from_shape_vec(
-            (3, 3),
-            vec![
-                self.parameters[0],
-                self.parameters[1],
-                self.parameters[4],
-                self.parameters[2],
-                self.parameters[3],
-                self.parameters[5],
-                0f64,
-                0f64,
-                1f64,
-            ],
-        )
-        .unwrap()
+    /// register_affine_coarse_to_fine, also returning the metric after each iteration of the full
+    /// resolution stage
+    fn coarse_to_fine<'a, A, T>(
+        fixed: A,
+        moving: A,
+        downscale: usize,
+    ) -> Result<(Transform, Vec<f64>)>
+    where
+        T: 'a + PixelType + ToPrimitive,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let fixed = fixed.into();
+        let moving = moving.into();
+        check_not_empty(fixed.shape())?;
+        let coarse = Transform::register_affine(
+            &pyramid::downsample(fixed, downscale)?,
+            &pyramid::downsample(moving, downscale)?,
+        )?;
+        // the coarse transform for the full image, the matrix stays the same
+        let coarse = coarse.rescaled(downscale as f64);
+        let origin = coarse.origin;
+        let p = coarse.parameters;
+        let translation = [p[4], p[5]];
+        // the same transform about the center of the full image
+        let (height, width) = fixed.dim();
+        let center = [((width - 1) as f64) / 2f64, ((height - 1) as f64) / 2f64];
+        let d = [center[0] - origin[0], center[1] - origin[1]];
+        let initial = [
+            p[0],
+            p[1],
+            p[2],
+            p[3],
+            translation[0] + p[0] * d[0] + p[1] * d[1] - d[0],
+            translation[1] + p[2] * d[0] + p[3] * d[1] - d[1],
+        ];
+        let (parameters, history) = register_recording(
+            fixed,
+            moving,
+            RegistrationMode::Affine,
+            center,
+            Some(initial),
+            None,
+        )?;
+        Ok((Transform::new(parameters, center, [height, width]), history))
     }
 
-    /// get the matrix describing the error of the transform
-    pub fn dmatrix(&self) -> Array2<f64> {
-        Array2::from_shape_vec(
-            (3, 3),
-            vec![
-                self.dparameters[0],
-                self.dparameters[1],
-                self.dparameters[4],
-                self.dparameters[2],
-                self.dparameters[3],
-                self.dparameters[5],
-                0f64,
-                0f64,
-                1f64,
-            ],
-        )
-        .unwrap()
+    /// find the affine transform which transforms moving into fixed by registering overlapping
+    /// tiles of tile_size (rows, columns) pixels, and fitting an affine transform to the
+    /// translations found for each tile in the least squares sense, tiles that cannot be
+    /// registered are skipped
+    pub fn register_affine_tiled<'a, A, T>(
+        fixed: A,
+        moving: A,
+        tile_size: [usize; 2],
+        tile_overlap: usize,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let fixed = fixed.into();
+        let moving = moving.into();
+        if fixed.shape() != moving.shape() {
+            return Err(TransformError::ShapeMismatch {
+                fixed: fixed.shape().to_vec(),
+                moving: moving.shape().to_vec(),
+            }
+            .into());
+        }
+        if tile_size.iter().any(|&s| s <= tile_overlap) {
+            return Err(anyhow!("tile_size must be larger than tile_overlap"));
+        }
+        let (height, width) = fixed.dim();
+        if tile_size[0] > height || tile_size[1] > width {
+            return Err(anyhow!("tile_size must not be larger than the images"));
+        }
+        let starts = |size: usize, tile: usize| {
+            let mut starts: Vec<usize> =
+                (0..size - tile + 1).step_by(tile - tile_overlap).collect();
+            if let Some(&last) = starts.last() {
+                if last + tile < size {
+                    starts.push(size - tile);
+                }
+            }
+            starts
+        };
+        let mut src = Vec::new();
+        let mut dst = Vec::new();
+        for y in starts(height, tile_size[0]) {
+            for x in starts(width, tile_size[1]) {
+                let tile = s![y..y + tile_size[0], x..x + tile_size[1]];
+                if let Ok(t) =
+                    Transform::register_translation(fixed.slice(tile), moving.slice(tile))
+                {
+                    let center = [
+                        x as f64 + ((tile_size[1] - 1) as f64) / 2f64,
+                        y as f64 + ((tile_size[0] - 1) as f64) / 2f64,
+                    ];
+                    src.push(center);
+                    dst.push([center[0] + t.parameters[4], center[1] + t.parameters[5]]);
+                }
+            }
+        }
+        let origin = [((width - 1) as f64) / 2f64, ((height - 1) as f64) / 2f64];
+        Ok(Transform::new(
+            fit_affine(&src, &dst, origin)?,
+            origin,
+            [height, width],
+        ))
     }
 
-    /// get the inverse transform
-    pub fn inverse(&self) -> Result<Transform> {
-        fn det(a: ArrayView2<f64>) -> f64 {
-            (a[[0, 0]] * a[[1, 1]]) - (a[[0, 1]] * a[[1, 0]])
-        }
+    /// find the affine transform which transforms moving into fixed using only the pixels in
+    /// roi: [row, column, height, width], the transform has the origin and shape of the full
+    /// images, so that it can be applied to them directly
+    pub fn register_affine_roi<'a, A, T>(fixed: A, moving: A, roi: [usize; 4]) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let [row, col, height, width] = roi;
+        let config = RegistrationConfig {
+            roi: Some(([row, col], [height, width])),
+            ..Default::default()
+        };
+        Transform::register(fixed, moving, RegistrationMode::Affine, &config)
+    }
 
-        let m = self.matrix();
-        let d = det(m.slice(s![..2, ..2]));
-        if d == 0f64 {
-            return Err(anyhow!("transform matrix is not invertible"));
+    /// find the translation which transforms moving into fixed by evaluating the correlation
+    /// for every translation on a grid: -range..=range in steps of step, for x and y, this is
+    /// slow, but cannot end up in a local optimum, which makes it suitable as initial transform
+    /// for register_affine_from when the images have repetitive texture
+    pub fn pre_align_exhaustive<'a, A, T>(
+        fixed: A,
+        moving: A,
+        step: [f64; 2],
+        range: [f64; 2],
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        if !step.iter().all(|&s| s > 0f64) || !range.iter().all(|&r| r >= 0f64) {
+            return Err(anyhow!(
+                "step must be positive and range not negative, got {:?} and {:?}",
+                step,
+                range
+            ));
         }
-        let parameters = [
-            det(m.slice(s![1.., 1..])) / d,
-            -det(m.slice(s![..;2, 1..])) / d,
-            -det(m.slice(s![1.., ..;2])) / d,
-            det(m.slice(s![..;2, ..;2])) / d,
-            det(m.slice(s![..2, 1..])) / d,
-            -det(m.slice(s![..2, ..;2])) / d,
-        ];
-
-        Ok(Transform {
-            parameters,
-            dparameters: [0f64; 6],
-            origin: self.origin,
-            shape: self.shape,
-        })
+        let (parameters, origin, shape) = register_exhaustive(fixed, moving, step, range)?;
+        Ok(Transform::new(parameters, origin, shape))
     }
 
-    /// adapt the transform to a new origin and shape
-    pub fn adapt(&mut self, origin: [f64; 2], shape: [usize; 2]) {
-        self.origin = [
-            origin[0] + (((self.shape[0] - shape[0]) as f64) / 2f64),
-            origin[1] + (((self.shape[1] - shape[1]) as f64) / 2f64),
-        ];
-        self.shape = shape;
+    /// find the translation which transforms moving into fixed
+    pub fn register_translation<'a, A, T>(fixed: A, moving: A) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape) = register(
+            fixed,
+            moving,
+            RegistrationMode::Translation,
+            false,
+            None,
+            None,
+            None,
+        )?;
+        Ok(Transform::new(parameters, origin, shape))
     }
-}
+
+    /// find the affine transform which transforms moving into fixed, with the optimizer starting
+    /// from initial instead of the identity, which helps when the motion is large but
+    /// approximately known, for example from the previous frame, the transform found is the
+    /// total transform, not the change from initial, and has the origin of initial
+    pub fn register_affine_from<'a, A, T>(
+        fixed: A,
+        moving: A,
+        initial: &Transform,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let fixed = fixed.into();
+        let (height, width) = fixed.dim();
+        let (parameters, _) = register_recording(
+            fixed,
+            moving.into(),
+            RegistrationMode::Affine,
+            initial.origin,
+            Some(initial.parameters),
+            None,
+        )?;
+        Ok(Transform::new(parameters, initial.origin, [height, width]))
+    }
+
+    /// find the affine transform which transforms moving into fixed, using only the pixels where
+    /// the masks are nonzero, the masks must have the same shape as the images
+    pub fn register_affine_masked<'a, A, T>(
+        fixed: A,
+        moving: A,
+        fixed_mask: Option<ArrayView2<u8>>,
+        moving_mask: Option<ArrayView2<u8>>,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape) = register_masked(
+            fixed,
+            moving,
+            fixed_mask,
+            moving_mask,
+            RegistrationMode::Affine,
+        )?;
+        Ok(Transform::new(parameters, origin, shape))
+    }
+
+    /// find the affine transform which transforms moving into fixed, with options for the
+    /// optimizer
+    pub fn register_affine_with<'a, A, T>(
+        fixed: A,
+        moving: A,
+        options: &RegistrationOptions,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape) = register(
+            fixed,
+            moving,
+            RegistrationMode::Affine,
+            false,
+            None,
+            None,
+            Some(options),
+        )?;
+        Ok(Transform::new(parameters, origin, shape))
+    }
+
+    /// find the translation which transforms moving into fixed, with options for the optimizer
+    pub fn register_translation_with<'a, A, T>(
+        fixed: A,
+        moving: A,
+        options: &RegistrationOptions,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape) = register(
+            fixed,
+            moving,
+            RegistrationMode::Translation,
+            false,
+            None,
+            None,
+            Some(options),
+        )?;
+        Ok(Transform::new(parameters, origin, shape))
+    }
+
+    /// find the affine transform which transforms moving into fixed, registering the results of
+    /// preprocessor on both images instead of the images themselves, the transform has the shape
+    /// of those results
+    pub fn register_affine_with_preprocessor<'a, A, T, F>(
+        fixed: A,
+        moving: A,
+        preprocessor: F,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+        F: Fn(ArrayView2<T>) -> Array2<f64>,
+    {
+        let fixed = preprocessor(fixed.into());
+        let moving = preprocessor(moving.into());
+        Transform::register_affine(fixed.view(), moving.view())
+    }
+
+    /// find the affine transform which transforms moving into fixed, registering the gradient
+    /// magnitudes of both images instead of the images themselves, which is more robust when the
+    /// intensities of both images are related non-linearly, but edges are preserved
+    pub fn register_affine_edge<'a, A, T>(fixed: A, moving: A) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape) = register(
+            fixed,
+            moving,
+            RegistrationMode::Affine,
+            true,
+            None,
+            None,
+            None,
+        )?;
+        Ok(Transform::new(parameters, origin, shape))
+    }
+
+    /// find the rigid transform (rotation and translation) which transforms moving into fixed
+    pub fn register_rigid<'a, A, T>(fixed: A, moving: A) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape) = register(
+            fixed,
+            moving,
+            RegistrationMode::Rigid,
+            false,
+            None,
+            None,
+            None,
+        )?;
+        Ok(Transform::new(parameters, origin, shape))
+    }
+
+    /// find the similarity transform (isotropic scale, rotation and translation) which transforms
+    /// moving into fixed
+    pub fn register_similarity<'a, A, T>(fixed: A, moving: A) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let (parameters, origin, shape) = register(
+            fixed,
+            moving,
+            RegistrationMode::Similarity,
+            false,
+            None,
+            None,
+            None,
+        )?;
+        Ok(Transform::new(parameters, origin, shape))
+    }
+
+    /// find the rotation about center (x, y), defaulting to the center of the image, which
+    /// transforms moving into fixed, the transform has no translation and center as origin
+    pub fn register_rotation<'a, A, T>(
+        fixed: A,
+        moving: A,
+        center: Option<[f64; 2]>,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        Self::register_about_center(fixed, moving, RegistrationMode::Rotation, center)
+    }
+
+    /// find the scale about the center of the image which transforms moving into fixed, the
+    /// scale is the same in x and y unless anisotropic, the transform has no translation, no
+    /// rotation and the center of the image as origin
+    pub fn register_scale<'a, A, T>(fixed: A, moving: A, anisotropic: bool) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let mode = if anisotropic {
+            RegistrationMode::AnisotropicScale
+        } else {
+            RegistrationMode::Scale
+        };
+        Self::register_about_center(fixed, moving, mode, None)
+    }
+
+    fn register_about_center<'a, A, T>(
+        fixed: A,
+        moving: A,
+        mode: RegistrationMode,
+        center: Option<[f64; 2]>,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let fixed = fixed.into();
+        let (height, width) = fixed.dim();
+        check_not_empty(fixed.shape())?;
+        let center = center.unwrap_or([((width - 1) as f64) / 2f64, ((height - 1) as f64) / 2f64]);
+        let (parameters, _, shape) =
+            register(fixed, moving.into(), mode, false, Some(center), None, None)?;
+        Ok(Transform::new(parameters, center, shape))
+    }
+
+    /// find the transform which transforms moving into fixed, both images have shape
+    /// [rows, columns, channels] and all channels are registered jointly to find one transform,
+    /// requires the elastix feature
+    #[cfg(feature = "elastix")]
+    pub fn register_multichannel<'a, A, T>(
+        fixed: A,
+        moving: A,
+        mode: RegistrationMode,
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix3>,
+    {
+        let fixed = fixed.into();
+        let moving = moving.into();
+        if fixed.shape() != moving.shape() {
+            return Err(TransformError::ShapeMismatch {
+                fixed: fixed.shape().to_vec(),
+                moving: moving.shape().to_vec(),
+            }
+            .into());
+        }
+        if matches!(
+            mode,
+            RegistrationMode::Rotation
+                | RegistrationMode::Scale
+                | RegistrationMode::AnisotropicScale
+        ) {
+            return Err(anyhow!(
+                "{:?} mode is not supported for multichannel images",
+                mode
+            ));
+        }
+        let (parameters, origin, shape) = register_vector(fixed, moving, mode)?;
+        Ok(Transform::new(parameters, origin, shape))
+    }
+
+    /// find the affine transform (12 parameters) which transforms moving into fixed, both images
+    /// have shape [depth, rows, columns], the origin is the geometric center of fixed
+    pub fn register_affine_3d<'a, A, T>(fixed: A, moving: A) -> Result<Transform3D>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix3>,
+    {
+        Self::register_3d(fixed, moving, RegistrationMode::Affine)
+    }
+
+    /// find the rigid transform (3 rotations and a translation) which transforms moving into
+    /// fixed, both images have shape [depth, rows, columns], the center of rotation is the
+    /// geometric center of fixed
+    pub fn register_affine_3d_rigid<'a, A, T>(fixed: A, moving: A) -> Result<Transform3D>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix3>,
+    {
+        Self::register_3d(fixed, moving, RegistrationMode::Rigid)
+    }
+
+    fn register_3d<'a, A, T>(fixed: A, moving: A, mode: RegistrationMode) -> Result<Transform3D>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix3>,
+    {
+        let fixed = fixed.into();
+        let (depth, height, width) = fixed.dim();
+        check_not_empty(fixed.shape())?;
+        let parameters = register_3d(fixed, moving.into(), mode)?;
+        Ok(Transform3D::new(
+            parameters,
+            [
+                ((width - 1) as f64) / 2f64,
+                ((height - 1) as f64) / 2f64,
+                ((depth - 1) as f64) / 2f64,
+            ],
+            [depth, height, width],
+        ))
+    }
+
+    /// find the transform which transforms moving into fixed, taking into account the physical
+    /// size of the pixels: spacing (x, y), and the orientation of the image axes: direction (flat
+    /// and row-major), the parameters and origin of the transform are in physical units
+    pub fn register_physical<'a, A, T>(
+        fixed: A,
+        moving: A,
+        mode: RegistrationMode,
+        spacing: [f64; 2],
+        direction: [f64; 4],
+    ) -> Result<Transform>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let fixed = fixed.into();
+        let (height, width) = fixed.dim();
+        check_not_empty(fixed.shape())?;
+        let (parameters, _, shape) = register(
+            fixed,
+            moving.into(),
+            mode,
+            false,
+            None,
+            Some((spacing, direction)),
+            None,
+        )?;
+        // the geometric center of the fixed image in physical coordinates
+        let c = [
+            spacing[0] * ((width - 1) as f64) / 2f64,
+            spacing[1] * ((height - 1) as f64) / 2f64,
+        ];
+        let origin = [
+            direction[0] * c[0] + direction[1] * c[1],
+            direction[2] * c[0] + direction[3] * c[1],
+        ];
+        let mut transform = Transform::new(parameters, origin, shape);
+        transform.spacing = spacing;
+        transform.direction = direction;
+        Ok(transform)
+    }
+
+    /// create a transform from a xy translation
+    pub fn from_translation(translation: [f64; 2]) -> Self {
+        Transform {
+            parameters: [1f64, 0f64, 0f64, 1f64, translation[0], translation[1]],
+            dparameters: [0f64; 6],
+            origin: [0f64; 2],
+            shape: [0usize; 2],
+            spacing: default_spacing(),
+            direction: default_direction(),
+        }
+    }
+
+    /// the translation which maps the points src (x, y) onto dst in the least squares sense: the
+    /// mean displacement of the two pairs
+    pub fn from_translation_landmarks(src: [[f64; 2]; 2], dst: [[f64; 2]; 2]) -> Self {
+        Transform::from_translation(
+            [0, 1].map(|i| ((dst[0][i] - src[0][i]) + (dst[1][i] - src[1][i])) / 2f64),
+        )
+    }
+
+    /// the affine transform which maps the points src onto dst in the least squares sense, both
+    /// with shape [n, 2]: one (x, y) per row, at least 3 non-collinear pairs are needed, origin
+    /// and shape are like from_translation
+    pub fn from_landmarks(src: ArrayView2<f64>, dst: ArrayView2<f64>) -> Result<Self> {
+        if src.shape() != dst.shape() || src.ncols() != 2 {
+            return Err(anyhow!(
+                "src and dst must both have shape [n, 2], got {:?} and {:?}",
+                src.shape(),
+                dst.shape()
+            ));
+        }
+        if src.nrows() < 3 {
+            return Err(TransformError::InsufficientLandmarks {
+                required: 3,
+                found: src.nrows(),
+            }
+            .into());
+        }
+        let points = |a: ArrayView2<f64>| -> Vec<[f64; 2]> {
+            a.rows().into_iter().map(|r| [r[0], r[1]]).collect()
+        };
+        Ok(Transform {
+            parameters: fit_affine(&points(src), &points(dst), [0f64; 2])?,
+            ..Default::default()
+        })
+    }
+
+    /// scale by sx and sy about the point (cx, cy), which stays in place
+    pub fn scale_about(sx: f64, sy: f64, cx: f64, cy: f64) -> Self {
+        Self::about(
+            array![[sx, 0f64, 0f64], [0f64, sy, 0f64], [0f64, 0f64, 1f64]],
+            cx,
+            cy,
+        )
+    }
+
+    /// rotate counterclockwise by angle (radians) about the point (cx, cy), which stays in place
+    pub fn rotate_about(angle: f64, cx: f64, cy: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::about(
+            array![[cos, -sin, 0f64], [sin, cos, 0f64], [0f64, 0f64, 1f64]],
+            cx,
+            cy,
+        )
+    }
+
+    /// T(cx, cy) * m * T(-cx, -cy), the translation includes the pivot, so the parameters
+    /// describe the transform on their own, as in apply_to_point, origin and shape are like
+    /// from_translation, so that the pivot is not applied a second time through the origin
+    fn about(m: Array2<f64>, cx: f64, cy: f64) -> Self {
+        let to = array![[1f64, 0f64, cx], [0f64, 1f64, cy], [0f64, 0f64, 1f64]];
+        let from = array![[1f64, 0f64, -cx], [0f64, 1f64, -cy], [0f64, 0f64, 1f64]];
+        let m = to.dot(&m).dot(&from);
+        let mut transform = Transform::from_translation([m[[0, 2]], m[[1, 2]]]);
+        transform.parameters[..4].copy_from_slice(&[m[[0, 0]], m[[0, 1]], m[[1, 0]], m[[1, 1]]]);
+        transform
+    }
+
+    /// create a transform from its components, with origin and shape like from_translation
+    pub fn from_components(components: &AffineComponents) -> Self {
+        let AffineComponents {
+            angle,
+            scale_x,
+            scale_y,
+            shear,
+            tx,
+            ty,
+        } = *components;
+        let (sin, cos) = angle.sin_cos();
+        let mut transform = Transform::from_translation([tx, ty]);
+        transform.parameters[..4].copy_from_slice(&[
+            cos * scale_x,
+            (cos * shear - sin) * scale_y,
+            sin * scale_x,
+            (sin * shear + cos) * scale_y,
+        ]);
+        transform
+    }
+
+    /// decompose the transform into rotation, shear, scale and translation, a reflection results
+    /// in a negative scale_y, the decomposition is not meaningful for a singular matrix
+    pub fn decompose(&self) -> AffineComponents {
+        let [a, b, c, d, tx, ty] = self.parameters;
+        let scale_x = a.hypot(c);
+        let angle = c.atan2(a);
+        let (sin, cos) = angle.sin_cos();
+        // R^T M = K S
+        let scale_y = cos * d - sin * b;
+        let shear = (cos * b + sin * d) / scale_y;
+        AffineComponents {
+            angle,
+            scale_x,
+            scale_y,
+            shear,
+            tx,
+            ty,
+        }
+    }
+
+    /// get the parameters as the row-major 2x3 matrix used by OpenCV (warpAffine):
+    /// [m00, m01, tx, m10, m11, ty]; OpenCV has no origin or shape, the origin is folded into the
+    /// translation
+    pub fn to_opencv_mat_params(&self) -> [f64; 6] {
+        let [a, b, c, d, tx, ty] = self.parameters;
+        let [ox, oy] = self.origin;
+        [
+            a,
+            b,
+            tx + ox - a * ox - b * oy,
+            c,
+            d,
+            ty + oy - c * ox - d * oy,
+        ]
+    }
+
+    /// create a transform from the row-major 2x3 matrix used by OpenCV (warpAffine):
+    /// [m00, m01, tx, m10, m11, ty]; origin and shape are unused in this convention and set to 0
+    pub fn from_opencv_mat_params(params: [f64; 6]) -> Self {
+        Transform::new(
+            [
+                params[0], params[1], params[3], params[4], params[2], params[5],
+            ],
+            [0f64; 2],
+            [0usize; 2],
+        )
+    }
+
+    /// read a transform from a file, fields added in later versions of this crate are optional
+    /// and get their default value when missing, so files written by older versions, with only
+    /// parameters, dparameters, origin and shape, can still be read
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let file = File::open(path)?;
+        Ok(from_reader(file)?)
+    }
+
+    /// write a transform to a file
+    pub fn to_file(&self, path: PathBuf) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        to_writer(&mut file, self)?;
+        Ok(())
+    }
+
+    /// true if transform does nothing
+    #[inline]
+    pub fn is_unity(&self) -> bool {
+        self.parameters == [1f64, 0f64, 0f64, 1f64, 0f64, 0f64]
+    }
+
+    /// true if the 2x2 part of the matrix is the identity within 1e-12, the translation can be
+    /// anything
+    pub fn is_pure_translation(&self) -> bool {
+        self.parameters[..4]
+            .iter()
+            .zip([1f64, 0f64, 0f64, 1f64])
+            .all(|(p, q)| (p - q).abs() < 1e-12)
+    }
+
+    /// true if the 2x2 part of the matrix is a rotation: orthogonal with determinant 1, within
+    /// 1e-12, so without scaling, shear or reflection
+    pub fn is_identity_rotation(&self) -> bool {
+        let [a, b, c, d, _, _] = self.parameters;
+        // M^T M = I and det(M) = 1
+        [
+            a * a + c * c - 1f64,
+            a * b + c * d,
+            b * b + d * d - 1f64,
+            a * d - b * c - 1f64,
+        ]
+        .iter()
+        .all(|x| x.abs() < 1e-12)
+    }
+
+    /// the parameters in the order of itk's AffineTransform: the matrix row-major, then the
+    /// translation, the center of which is the origin
+    pub fn to_parameters_vec(&self) -> Vec<f64> {
+        self.parameters.to_vec()
+    }
+
+    /// the parameters in canonical order: m00, m01, m10, m11, tx, ty
+    pub fn iter_parameters(&self) -> impl Iterator<Item = f64> + '_ {
+        self.parameters.iter().copied()
+    }
+
+    /// the errors of the parameters in the same order as iter_parameters
+    pub fn iter_dparameters(&self) -> impl Iterator<Item = f64> + '_ {
+        self.dparameters.iter().copied()
+    }
+
+    /// the parameters in the same order as iter_parameters, for changing them in place
+    pub fn iter_parameters_mut(&mut self) -> impl Iterator<Item = &mut f64> + '_ {
+        self.parameters.iter_mut()
+    }
+
+    /// true if none of the parameters is nan or infinite
+    pub fn is_finite(&self) -> bool {
+        self.parameters.iter().all(|p| p.is_finite())
+    }
+
+    /// true if the 2x2 part of the matrix is a pure rotation: orthonormal with determinant 1
+    pub fn is_rigid(&self) -> bool {
+        let [a, b, c, d, _, _] = self.parameters;
+        ((a * a + b * b) - 1f64).abs() < 1e-6
+            && ((c * c + d * d) - 1f64).abs() < 1e-6
+            && (a * c + b * d).abs() < 1e-6
+            && ((a * d - b * c) - 1f64).abs() < 1e-6
+    }
+
+    /// get the rigid transform closest to this transform, keeping the translation
+    ///
+    /// the rotation minimizes the Frobenius distance to the 2x2 part of the matrix (orthogonal
+    /// Procrustes): with SVD(M) = U S V^T, R = U diag(1, det(U V^T)) V^T, which for a 2x2 matrix
+    /// reduces to a rotation by atan2(m10 - m01, m00 + m11)
+    pub fn nearest_rigid(&self) -> Transform {
+        let [a, b, c, d, tx, ty] = self.parameters;
+        let (sin, cos) = (c - b).atan2(a + d).sin_cos();
+        Transform {
+            parameters: [cos, -sin, sin, cos, tx, ty],
+            dparameters: self.dparameters,
+            origin: self.origin,
+            shape: self.shape,
+            spacing: self.spacing,
+            direction: self.direction,
+        }
+    }
+
+    /// the same transform with the translation clamped to [-max_tx, max_tx] and [-max_ty, max_ty],
+    /// max_tx and max_ty must not be negative or NaN
+    pub fn clamp_translation(&self, max_tx: f64, max_ty: f64) -> Result<Transform> {
+        let mut bounds = [(f64::NEG_INFINITY, f64::INFINITY); 6];
+        bounds[4] = (-max_tx, max_tx);
+        bounds[5] = (-max_ty, max_ty);
+        self.clamp_all_parameters(bounds)
+    }
+
+    /// the same transform with each parameter clamped to its (min, max) in bounds, min must not
+    /// be greater than max and neither may be NaN
+    pub fn clamp_all_parameters(&self, bounds: [(f64, f64); 6]) -> Result<Transform> {
+        if let Some((min, max)) = bounds
+            .iter()
+            .find(|(min, max)| min.is_nan() || max.is_nan() || min > max)
+        {
+            return Err(anyhow!("invalid bounds: min {} and max {}", min, max));
+        }
+        let mut transform = self.clone();
+        for (p, (min, max)) in transform.parameters.iter_mut().zip(bounds) {
+            *p = p.clamp(min, max);
+        }
+        Ok(transform)
+    }
+
+    /// transform an image using the given interpolation, the image may have any memory layout,
+    /// the output is always C-contiguous (row-major)
+    pub fn transform_image<'a, A, T>(
+        &self,
+        image: A,
+        interpolator: Interpolator,
+    ) -> Result<Array2<T>>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let image = image.into();
+        let (height, width) = image.dim();
+        interp(
+            self.parameters,
+            self.origin,
+            self.spacing,
+            self.direction,
+            image,
+            interpolator,
+            [0, 0, height, width],
+        )
+    }
+
+    /// transform an image using cubic bspline interpolation
+    pub fn transform_image_bspline<'a, A, T>(&self, image: A) -> Result<Array2<T>>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        self.transform_image(image, Interpolator::BSpline { order: 3 })
+    }
+
+    /// transform an image using bspline interpolation, and the mean square error between the
+    /// result and reference, computed directly from the pixels without converting the images
+    pub fn apply_to_image_bspline_with_mse<T>(
+        &self,
+        image: ArrayView2<T>,
+        reference: ArrayView2<T>,
+    ) -> Result<(Array2<T>, f64)>
+    where
+        T: PixelType + ToPrimitive,
+    {
+        if image.shape() != reference.shape() {
+            return Err(anyhow!(
+                "images have different shapes: {:?} and {:?}",
+                image.shape(),
+                reference.shape()
+            ));
+        }
+        if image.is_empty() {
+            return Err(anyhow!("cannot compute the mse of empty images"));
+        }
+        let warped = self.transform_image_bspline(image)?;
+        let sum: f64 = warped
+            .iter()
+            .zip(reference.iter())
+            .map(|(a, b)| (a.to_f64().unwrap_or(f64::NAN) - b.to_f64().unwrap_or(f64::NAN)).powi(2))
+            .sum();
+        let mse = sum / warped.len() as f64;
+        Ok((warped, mse))
+    }
+
+    /// transform an image using bspline interpolation on tokio's blocking thread pool, so that
+    /// the async runtime is not blocked, the view must be 'static because it is moved to that pool
+    #[cfg(feature = "tokio")]
+    pub async fn transform_image_bspline_async<T>(
+        &self,
+        image: ArrayView2<'static, T>,
+    ) -> Result<Array2<T>>
+    where
+        T: 'static + PixelType + Send + Sync,
+    {
+        let transform = self.clone();
+        tokio::task::spawn_blocking(move || transform.transform_image_bspline(image)).await?
+    }
+
+    /// transform an image using nearest neighbor interpolation
+    pub fn transform_image_nearest_neighbor<'a, A, T>(&self, image: A) -> Result<Array2<T>>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        self.transform_image(image, Interpolator::NearestNeighbor)
+    }
+
+    /// transform an image and write the result into out, which must have the shape of the image,
+    /// this avoids allocating a new image for every call if out is contiguous
+    pub fn transform_image_into<'a, A, T>(
+        &self,
+        image: A,
+        out: &mut Array2<T>,
+        interpolator: Interpolator,
+    ) -> Result<()>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let image = image.into();
+        if out.shape() != image.shape() {
+            return Err(anyhow!(
+                "output shape {:?} does not match image shape {:?}",
+                out.shape(),
+                image.shape()
+            ));
+        }
+        let (height, width) = image.dim();
+        match out.as_slice_mut() {
+            Some(out) => interp_into(
+                self.parameters,
+                self.origin,
+                self.spacing,
+                self.direction,
+                image,
+                interpolator,
+                [0, 0, height, width],
+                out,
+            ),
+            None => {
+                out.assign(&self.transform_image(image, interpolator)?);
+                Ok(())
+            }
+        }
+    }
+
+    /// transform an image, but only compute the output within roi: [row_min, col_min, row_max,
+    /// col_max], max exclusive, the result has shape [row_max - row_min, col_max - col_min]
+    pub fn apply_to_image_roi<'a, A, T>(
+        &self,
+        image: A,
+        roi: [usize; 4],
+        interpolator: Interpolator,
+    ) -> Result<Array2<T>>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let image = image.into();
+        let (height, width) = image.dim();
+        if roi[0] >= roi[2] || roi[1] >= roi[3] || roi[2] > height || roi[3] > width {
+            return Err(anyhow!(
+                "roi {:?} is empty or outside the image with shape {:?}",
+                roi,
+                [height, width]
+            ));
+        }
+        interp(
+            self.parameters,
+            self.origin,
+            self.spacing,
+            self.direction,
+            image,
+            interpolator,
+            roi,
+        )
+    }
+
+    /// transform an image which does not fit in memory tile by tile: the output with
+    /// output_shape is computed in tiles of at most tile_size (rows, columns), for each tile the
+    /// part of the input which is needed is requested with read_tile, and the result is passed to
+    /// write_tile, regions are given as [row_min, col_min, row_max, col_max], max exclusive
+    ///
+    /// the input is assumed to have output_shape too, spacing and direction must be the default
+    pub fn apply_to_image_streaming<T, R, W>(
+        &self,
+        read_tile: R,
+        mut write_tile: W,
+        output_shape: [usize; 2],
+        tile_size: [usize; 2],
+        interpolator: Interpolator,
+    ) -> Result<()>
+    where
+        T: PixelType,
+        R: Fn([usize; 4]) -> Result<Array2<T>>,
+        W: FnMut([usize; 4], Array2<T>) -> Result<()>,
+    {
+        // the bspline coefficients depend on a neighborhood of each pixel, with this margin the
+        // result does not depend on the tiling
+        const MARGIN: f64 = 16f64;
+        if tile_size.contains(&0) {
+            return Err(anyhow!("tile_size cannot be zero"));
+        }
+        if self.spacing != default_spacing() || self.direction != default_direction() {
+            return Err(anyhow!(
+                "streaming requires the default spacing and direction"
+            ));
+        }
+        let [height, width] = output_shape;
+        let [a, b, c, d, tx, ty] = self.parameters;
+        let [ox, oy] = self.origin;
+        for r0 in (0..height).step_by(tile_size[0]) {
+            for c0 in (0..width).step_by(tile_size[1]) {
+                let r1 = (r0 + tile_size[0]).min(height);
+                let c1 = (c0 + tile_size[1]).min(width);
+                // bounding box in the input of the corners of the output tile
+                let (mut x_min, mut x_max, mut y_min, mut y_max) =
+                    (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+                for (x, y) in [(c0, r0), (c1 - 1, r0), (c0, r1 - 1), (c1 - 1, r1 - 1)] {
+                    let (x, y) = (x as f64 - ox, y as f64 - oy);
+                    let (u, v) = (a * x + b * y + ox + tx, c * x + d * y + oy + ty);
+                    x_min = x_min.min(u);
+                    x_max = x_max.max(u);
+                    y_min = y_min.min(v);
+                    y_max = y_max.max(v);
+                }
+                let clamp = |v: f64, n: usize| v.clamp(0f64, n as f64) as usize;
+                let ir0 = clamp((y_min - MARGIN).floor(), height - 1);
+                let ic0 = clamp((x_min - MARGIN).floor(), width - 1);
+                let ir1 = clamp((y_max + MARGIN).ceil() + 1f64, height).max(ir0 + 1);
+                let ic1 = clamp((x_max + MARGIN).ceil() + 1f64, width).max(ic0 + 1);
+                let region = [ir0, ic0, ir1, ic1];
+                let tile = read_tile(region)?;
+                if tile.dim() != (ir1 - ir0, ic1 - ic0) {
+                    return Err(anyhow!(
+                        "read_tile returned shape {:?} for region {:?}",
+                        tile.shape(),
+                        region
+                    ));
+                }
+                // express the transform in the coordinates of the input tile for an output which
+                // starts at the corner of the output tile
+                let origin = [ox - c0 as f64, oy - r0 as f64];
+                let mut parameters = self.parameters;
+                parameters[4] += c0 as f64 - ic0 as f64;
+                parameters[5] += r0 as f64 - ir0 as f64;
+                let out = interp(
+                    parameters,
+                    origin,
+                    self.spacing,
+                    self.direction,
+                    tile.view(),
+                    interpolator,
+                    [0, 0, r1 - r0, c1 - c0],
+                )?;
+                write_tile([r0, c0, r1, c1], out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// get coordinates resulting from transforming input coordinates, the rows are transformed in
+    /// parallel with the rayon feature
+    pub fn transform_coordinates<'a, A, T>(&self, coordinates: A) -> Result<Array2<f64>>
+    where
+        T: 'a + Clone + Into<f64>,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let coordinates = coordinates.into();
+        if coordinates.ncols() != 2 {
+            return Err(anyhow!("coordinates must have two columns"));
+        }
+        let coordinates = coordinates.mapv(|c| c.into());
+        let mut res = Array2::zeros(coordinates.raw_dim());
+        let zip = Zip::from(res.rows_mut()).and(coordinates.rows());
+        let f = |mut r: ArrayViewMut1<f64>, c: ArrayView1<f64>| {
+            let [x, y] = self.apply_to_point(c[0], c[1]);
+            r[0] = x;
+            r[1] = y;
+        };
+        #[cfg(feature = "rayon")]
+        zip.par_for_each(f);
+        #[cfg(not(feature = "rayon"))]
+        zip.for_each(f);
+        Ok(res)
+    }
+
+    /// the root mean square of the distances between the transformed points src and the points
+    /// dst, both with shape [n, 2], a measure of how well the transform maps the landmarks
+    pub fn residual_at_landmarks(&self, src: ArrayView2<f64>, dst: ArrayView2<f64>) -> Result<f64> {
+        let d2 = self.squared_distances(src, dst)?;
+        Ok((d2.iter().sum::<f64>() / d2.len() as f64).sqrt())
+    }
+
+    /// the largest distance between a transformed point in src and the point in dst, both with
+    /// shape [n, 2], to find outliers
+    pub fn max_residual_at_landmarks(
+        &self,
+        src: ArrayView2<f64>,
+        dst: ArrayView2<f64>,
+    ) -> Result<f64> {
+        Ok(self
+            .squared_distances(src, dst)?
+            .into_iter()
+            .fold(0f64, f64::max)
+            .sqrt())
+    }
+
+    fn squared_distances(&self, src: ArrayView2<f64>, dst: ArrayView2<f64>) -> Result<Vec<f64>> {
+        if src.shape() != dst.shape() {
+            return Err(anyhow!(
+                "src and dst must have the same shape, got {:?} and {:?}",
+                src.shape(),
+                dst.shape()
+            ));
+        }
+        if src.nrows() == 0 {
+            return Err(anyhow!("no landmarks"));
+        }
+        let mapped = self.transform_coordinates(src)?;
+        Ok(mapped
+            .rows()
+            .into_iter()
+            .zip(dst.rows())
+            .map(|(a, b)| (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2))
+            .collect())
+    }
+
+    /// transform a single point (x, y), like transform_coordinates
+    #[inline]
+    pub fn apply_to_point(&self, x: f64, y: f64) -> [f64; 2] {
+        let p = &self.parameters;
+        [p[0] * x + p[1] * y + p[4], p[2] * x + p[3] * y + p[5]]
+    }
+
+    /// the axis-aligned bounding box ([x_min, y_min], [x_max, y_max]) of the image after
+    /// transformation: the four corners of [0, 0]..=[width, height] are transformed like in
+    /// transform_coordinates
+    pub fn transform_bbox(&self) -> ([f64; 2], [f64; 2]) {
+        let (h, w) = (self.shape[0] as f64, self.shape[1] as f64);
+        self.bounding_box_of_transformed_rect([0f64, 0f64], [w, h])
+    }
+
+    /// transform the corners of the axis-aligned rectangle from top_left to bottom_right (x, y)
+    /// with apply_to_point, in the order top left, top right, bottom right, bottom left
+    pub fn apply_to_rect(&self, top_left: [f64; 2], bottom_right: [f64; 2]) -> [[f64; 2]; 4] {
+        let [left, top] = top_left;
+        let [right, bottom] = bottom_right;
+        [[left, top], [right, top], [right, bottom], [left, bottom]]
+            .map(|[x, y]| self.apply_to_point(x, y))
+    }
+
+    /// the axis-aligned bounding box ([x_min, y_min], [x_max, y_max]) of the rectangle from
+    /// top_left to bottom_right after transformation with apply_to_rect
+    pub fn bounding_box_of_transformed_rect(
+        &self,
+        top_left: [f64; 2],
+        bottom_right: [f64; 2],
+    ) -> ([f64; 2], [f64; 2]) {
+        let mut min = [f64::INFINITY; 2];
+        let mut max = [f64::NEG_INFINITY; 2];
+        for p in self.apply_to_rect(top_left, bottom_right) {
+            for i in 0..2 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        (min, max)
+    }
+
+    /// transform a single point (x, y), and apply the error matrix to it too
+    #[inline]
+    pub fn apply_to_point_with_uncertainty(&self, x: f64, y: f64) -> ([f64; 2], [f64; 2]) {
+        let d = &self.dparameters;
+        (
+            self.apply_to_point(x, y),
+            [d[0] * x + d[1] * y + d[4], d[2] * x + d[3] * y + d[5]],
+        )
+    }
+
+    /// transform points (x, y), one per row, and propagate the errors of the parameters: the
+    /// standard errors in the dmatrix, assumed to be independent, returns the transformed points
+    /// and their standard errors in x and y
+    pub fn transform_points_with_error<'a, A, T>(
+        &self,
+        points: A,
+    ) -> Result<(Array2<f64>, Array2<f64>)>
+    where
+        T: 'a + Clone + Into<f64>,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let points = points.into();
+        let transformed = self.transform_coordinates(points)?;
+        let dm = self.dmatrix();
+        let mut errors = Array2::zeros(transformed.raw_dim());
+        for (mut e, p) in errors.rows_mut().into_iter().zip(points.rows()) {
+            let (x, y) = (p[0].clone().into(), p[1].clone().into());
+            for i in 0..2 {
+                e[i] = ((dm[[i, 0]] * x).powi(2) + (dm[[i, 1]] * y).powi(2) + dm[[i, 2]].powi(2))
+                    .sqrt();
+            }
+        }
+        Ok((transformed, errors))
+    }
+
+    /// the transform which applies the transforms in order, first transforms[0], that is
+    /// transforms[n - 1] * ... * transforms[0], with the origin, shape, spacing and direction of
+    /// the last, None if there are no transforms
+    pub fn compose_many(transforms: &[Transform]) -> Option<Transform> {
+        let (first, rest) = transforms.split_first()?;
+        Some(rest.iter().fold(first.clone(), |combined, t| t * &combined))
+    }
+
+    /// linear interpolation between self (t = 0) and other (t = 1): every parameter and the
+    /// origin are interpolated as self * (1 - t) + other * t, shape, spacing and direction are taken
+    /// from the closest
+    ///
+    /// this is exact for the translation, but interpolating the 2x2 part of the matrix linearly
+    /// does not keep rotations rigid: halfway between rotations of +90 and -90 degrees is a matrix
+    /// with zero scale, so for large differences in angle a slerp of the rotation is preferable
+    pub fn interpolate(&self, other: &Transform, t: f64) -> Transform {
+        let lerp = |a: f64, b: f64| a * (1f64 - t) + b * t;
+        let mut parameters = [0f64; 6];
+        let mut dparameters = [0f64; 6];
+        for i in 0..6 {
+            parameters[i] = lerp(self.parameters[i], other.parameters[i]);
+            dparameters[i] = lerp(self.dparameters[i], other.dparameters[i]);
+        }
+        Transform {
+            parameters,
+            dparameters,
+            origin: [
+                lerp(self.origin[0], other.origin[0]),
+                lerp(self.origin[1], other.origin[1]),
+            ],
+            shape: if t <= 0.5 { self.shape } else { other.shape },
+            spacing: if t <= 0.5 {
+                self.spacing
+            } else {
+                other.spacing
+            },
+            direction: if t <= 0.5 {
+                self.direction
+            } else {
+                other.direction
+            },
+        }
+    }
+
+    /// get the matrix defining the transform
+    pub fn matrix(&self) -> Array2<f64> {
+        Array2::from_shape_vec(
+            (3, 3),
+            vec![
+                self.parameters[0],
+                self.parameters[1],
+                self.parameters[4],
+                self.parameters[2],
+                self.parameters[3],
+                self.parameters[5],
+                0f64,
+                0f64,
+                1f64,
+            ],
+        )
+        .unwrap()
+    }
+
+    /// get the matrix describing the error of the transform
+    pub fn dmatrix(&self) -> Array2<f64> {
+        Array2::from_shape_vec(
+            (3, 3),
+            vec![
+                self.dparameters[0],
+                self.dparameters[1],
+                self.dparameters[4],
+                self.dparameters[2],
+                self.dparameters[3],
+                self.dparameters[5],
+                0f64,
+                0f64,
+                1f64,
+            ],
+        )
+        .unwrap()
+    }
+
+    /// get the inverse transform
+    pub fn inverse(&self) -> Result<Transform> {
+        fn det(a: ArrayView2<f64>) -> f64 {
+            (a[[0, 0]] * a[[1, 1]]) - (a[[0, 1]] * a[[1, 0]])
+        }
+
+        let m = self.matrix();
+        let d = det(m.slice(s![..2, ..2]));
+        if d == 0f64 {
+            return Err(anyhow!("transform matrix is not invertible"));
+        }
+        let parameters = [
+            det(m.slice(s![1.., 1..])) / d,
+            -det(m.slice(s![..;2, 1..])) / d,
+            -det(m.slice(s![1.., ..;2])) / d,
+            det(m.slice(s![..;2, ..;2])) / d,
+            det(m.slice(s![..2, 1..])) / d,
+            -det(m.slice(s![..2, ..;2])) / d,
+        ];
+
+        Ok(Transform {
+            parameters,
+            dparameters: [0f64; 6],
+            origin: self.origin,
+            shape: self.shape,
+            spacing: self.spacing,
+            direction: self.direction,
+        })
+    }
+
+    /// get the inverse transform, or the identity with a warning if the transform is not
+    /// invertible
+    pub fn inverse_or_identity(&self) -> Transform {
+        match self.inverse() {
+            Ok(inverse) => inverse,
+            Err(e) => {
+                log::warn!("using the identity instead of the inverse: {}", e);
+                Transform::from_translation([0f64, 0f64])
+            }
+        }
+    }
+
+    /// adapt the transform to a new origin and shape
+    pub fn adapt(&mut self, origin: [f64; 2], shape: [usize; 2]) {
+        self.origin = [
+            origin[0] + (((self.shape[0] - shape[0]) as f64) / 2f64),
+            origin[1] + (((self.shape[1] - shape[1]) as f64) / 2f64),
+        ];
+        self.shape = shape;
+    }
+
+    /// the same transform for images scaled by factor, for example to apply a transform found on
+    /// images downsampled by 2 to the full resolution images with factor 2, pixel q in the original
+    /// image is at factor * q + (factor - 1) / 2 in the scaled image, the shape is scaled and the
+    /// matrix stays the same, in pixel units (the default spacing and direction) the translation,
+    /// its error and the origin are scaled too, in physical units the spacing is divided by factor
+    /// instead, and only the origin moves with the half pixel shift of the scaled image
+    pub fn rescaled(&self, factor: f64) -> Transform {
+        let mut transform = self.clone();
+        transform.shape = self.shape.map(|s| (s as f64 * factor).round() as usize);
+        if self.spacing == default_spacing() && self.direction == default_direction() {
+            for i in 4..6 {
+                transform.parameters[i] *= factor;
+                transform.dparameters[i] *= factor;
+            }
+            transform.origin = self.origin.map(|o| factor * o + (factor - 1f64) / 2f64);
+        } else {
+            let shift = self.spacing.map(|s| s * (factor - 1f64) / (2f64 * factor));
+            let d = &self.direction;
+            transform.origin = [
+                self.origin[0] + d[0] * shift[0] + d[1] * shift[1],
+                self.origin[1] + d[2] * shift[0] + d[3] * shift[1],
+            ];
+            transform.spacing = self.spacing.map(|s| s / factor);
+        }
+        transform
+    }
+
+    /// the same transform for images with a pixel spacing of new_spacing, when it was found on
+    /// images with a spacing of old_spacing, for example on images of 0.5 mm/pixel to apply it to
+    /// images of 1 mm/pixel
+    pub fn rescale_for_spacing(&self, new_spacing: f64, old_spacing: f64) -> Transform {
+        self.rescaled(old_spacing / new_spacing)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use anyhow::Result;
-    use ndarray::Array2;
+    use ndarray::{Array2, Array3};
     use num::Complex;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
     use tempfile::NamedTempFile;
 
-    /// An example of generating julia fractals.
-    fn julia_image(shift_x: f32, shift_y: f32) -> Result<Array2<u8>> {
-        let imgx = 800;
-        let imgy = 600;
+    /// An example of generating julia fractals.
+    pub(crate) fn julia_image(shift_x: f32, shift_y: f32) -> Result<Array2<u8>> {
+        let imgx = 800;
+        let imgy = 600;
+
+        let scalex = 3.0 / imgx as f32;
+        let scaley = 3.0 / imgy as f32;
+
+        let mut im = Array2::<u8>::zeros((imgy, imgx));
+        for x in 0..imgx {
+            for y in 0..imgy {
+                let cy = (y as f32 + shift_y) * scalex - 1.5;
+                let cx = (x as f32 + shift_x) * scaley - 1.5;
+
+                let c = Complex::new(-0.4, 0.6);
+                let mut z = Complex::new(cy, cx);
+
+                let mut i = 0;
+                while i < 255 && z.norm() <= 2.0 {
+                    z = z * z + c;
+                    i += 1;
+                }
+
+                im[[y, x]] = i as u8;
+            }
+        }
+        Ok(im)
+    }
+
+    /// image with uniform noise in [-amplitude, amplitude] added, the same seed gives the same noise
+    pub(crate) fn noisy_image(image: &Array2<u8>, amplitude: f64, seed: u64) -> Array2<f64> {
+        let mut state = seed;
+        image.mapv(|v| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            v as f64 + amplitude * (((state >> 11) as f64 / (1u64 << 53) as f64) * 2.0 - 1.0)
+        })
+    }
+
+    /// a few gaussian blobs of different sizes in a 48x48x48 volume, shifted by (x, y, z)
+    pub(crate) fn blobs_image(shift: [f64; 3]) -> Array3<f64> {
+        let blobs = [
+            ([20.0, 18.0, 24.0], 6.0, 200.0),
+            ([30.0, 26.0, 20.0], 4.0, 150.0),
+            ([16.0, 30.0, 28.0], 3.0, 255.0),
+            ([28.0, 14.0, 30.0], 5.0, 100.0),
+        ];
+        Array3::from_shape_fn((48, 48, 48), |(z, y, x)| {
+            blobs
+                .iter()
+                .map(|([bx, by, bz], sigma, amplitude)| {
+                    let d2 = (x as f64 - bx - shift[0]).powi(2)
+                        + (y as f64 - by - shift[1]).powi(2)
+                        + (z as f64 - bz - shift[2]).powi(2);
+                    amplitude * (-d2 / (2f64 * sigma * sigma)).exp()
+                })
+                .sum()
+        })
+    }
+
+    #[test]
+    fn test_serialization() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        let t = Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [59.5, 49.5], [120, 100]);
+        t.to_file(file.path().to_path_buf())?;
+        let s = Transform::from_file(file.path().to_path_buf())?;
+        assert_eq!(s, t);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialization_old_version() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        std::fs::write(
+            file.path(),
+            "parameters: [1.2, 0.3, -0.4, 0.9, 10.2, -9.5]\n\
+             dparameters: [0.0, 0.0, 0.0, 0.0, 0.0, 0.0]\n\
+             origin: [59.5, 49.5]\n\
+             shape: [120, 100]\n",
+        )?;
+        let t = Transform::from_file(file.path().to_path_buf())?;
+        assert_eq!(
+            t,
+            Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [59.5, 49.5], [120, 100])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_nearest_rigid() {
+        let t = Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [59.5, 49.5], [120, 100]);
+        assert!(!t.is_rigid());
+        let r = t.nearest_rigid();
+        assert!(r.is_rigid());
+        assert_eq!(r.parameters[4..], t.parameters[4..]);
+        let distance = |p: &[f64; 6]| {
+            p[..4]
+                .iter()
+                .zip(t.parameters[..4].iter())
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<f64>()
+        };
+        let d = distance(&r.parameters);
+        for i in 0..3600 {
+            let (sin, cos) = (i as f64 * std::f64::consts::PI / 1800f64).sin_cos();
+            assert!(d <= distance(&[cos, -sin, sin, cos, 0f64, 0f64]) + 1e-12);
+        }
+        let (sin, cos) = 0.3f64.sin_cos();
+        let s = Transform::new([cos, -sin, sin, cos, 1., 2.], [0., 0.], [0, 0]);
+        assert!(
+            (s.nearest_rigid().matrix() - s.matrix())
+                .iter()
+                .all(|x| x.abs() < 1e-12)
+        );
+    }
+
+    macro_rules! interp_tests_bspline {
+        ($($name:ident: $t:ty $(,)?)*) => {
+            $(
+                #[test]
+                fn $name() -> Result<()> {
+                    let j = julia_image(-120f32, 10f32)?.mapv(|x| x as $t);
+                    let k = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
+                    let shape = j.shape();
+                    let origin = [
+                        ((shape[1] - 1) as f64) / 2f64,
+                        ((shape[0] - 1) as f64) / 2f64,
+                    ];
+                    let transform = Transform::new([1., 0., 0., 1., 120., -10.], origin, [shape[0], shape[1]]);
+                    let n = transform.transform_image_bspline(j.view())?;
+                    let d = (k.mapv(|x| x as f64) - n.mapv(|x| x as f64)).powi(2).sum();
+                    assert!(d <= (shape[0] * shape[1]) as f64);
+                    Ok(())
+                }
+            )*
+        }
+    }
+
+    interp_tests_bspline! {
+        interpbs_u8: u8,
+        interpbs_i8: i8,
+        interpbs_u16: u16,
+        interpbs_i16: i16,
+        interpbs_u32: u32,
+        interpbs_i32: i32,
+        interpbs_u64: u64,
+        interpbs_i64: i64,
+        interpbs_f32: f32,
+        interpbs_f64: f64,
+    }
+
+    macro_rules! interp_tests_nearest_neighbor {
+        ($($name:ident: $t:ty $(,)?)*) => {
+            $(
+                #[test]
+                fn $name() -> Result<()> {
+                    let j = julia_image(-120f32, 10f32)?.mapv(|x| x as $t);
+                    let k = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
+                    let shape = j.shape();
+                    let origin = [
+                        ((shape[1] - 1) as f64) / 2f64,
+                        ((shape[0] - 1) as f64) / 2f64,
+                    ];
+                    let j0 = j.clone();
+                    let k0 = k.clone();
+                    let transform = Transform::new([1., 0., 0., 1., 120., -10.], origin, [shape[0], shape[1]]);
+                    // make sure j & k weren't mutated
+                    assert!(j.iter().zip(j0.iter()).map(|(a, b)| a == b).all(|x| x));
+                    assert!(k.iter().zip(k0.iter()).map(|(a, b)| a == b).all(|x| x));
+                    let n = transform.transform_image_nearest_neighbor(j.view())?;
+                    let d = (k.mapv(|x| x as f64) - n.mapv(|x| x as f64)).powi(2).sum();
+                    assert!(d <= (shape[0] * shape[1]) as f64);
+                    Ok(())
+                }
+            )*
+        }
+    }
+
+    interp_tests_nearest_neighbor! {
+        interpnn_u8: u8,
+        interpnn_i8: i8,
+        interpnn_u16: u16,
+        interpnn_i16: i16,
+        interpnn_u32: u32,
+        interpnn_i32: i32,
+        interpnn_u64: u64,
+        interpnn_i64: i64,
+        interpnn_f32: f32,
+        interpnn_f64: f64,
+    }
+
+    macro_rules! registration_tests_translation {
+        ($($name:ident: $t:ty $(,)?)*) => {
+            $(
+                #[test]
+                fn $name() -> Result<()> {
+                    let j = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
+                    let k = julia_image(10f32, 20f32)?.mapv(|x| x as $t);
+                    let j0 = j.clone();
+                    let k0 = k.clone();
+                    let t = Transform::register_translation(j.view(), k.view())?;
+                    // make sure j & k weren't mutated
+                    assert!(j.iter().zip(j0.iter()).map(|(a, b)| a == b).all(|x| x));
+                    assert!(k.iter().zip(k0.iter()).map(|(a, b)| a == b).all(|x| x));
+                    let mut m = Array2::eye(3);
+                    m[[0, 2]] = -10f64;
+                    m[[1, 2]] = -20f64;
+                    let d = (t.matrix() - m).powi(2).sum();
+                    assert!(d < 0.01);
+                    Ok(())
+                }
+            )*
+        }
+    }
+
+    registration_tests_translation! {
+        registration_translation_u8: u8,
+        registration_translation_i8: i8,
+        registration_translation_u16: u16,
+        registration_translation_i16: i16,
+        registration_translation_u32: u32,
+        registration_translation_i32: i32,
+        registration_translation_u64: u64,
+        registration_translation_i64: i64,
+        registration_translation_f32: f32,
+        registration_translation_f64: f64,
+    }
+
+    macro_rules! registration_tests_affine {
+        ($($name:ident: $t:ty $(,)?)*) => {
+            $(
+                #[test]
+                fn $name() -> Result<()> {
+                    let j = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
+                    let shape = j.shape();
+                    let origin = [
+                        ((shape[1] - 1) as f64) / 2f64,
+                        ((shape[0] - 1) as f64) / 2f64,
+                    ];
+                    let s = Transform::new([1.2, 0., 0., 1., 5., 7.], origin, [shape[0], shape[1]]);
+                    let k = s.transform_image_bspline(j.view())?;
+                    let t = Transform::register_affine(j.view(), k.view())?.inverse()?;
+                    let d = (t.matrix() - s.matrix()).powi(2).sum();
+                    assert!(d < 0.01);
+                    Ok(())
+                }
+            )*
+        }
+    }
+
+    registration_tests_affine! {
+        registration_tests_affine_u8: u8,
+        registration_tests_affine_i8: i8,
+        registration_tests_affine_u16: u16,
+        registration_tests_affine_i16: i16,
+        registration_tests_affine_u32: u32,
+        registration_tests_affine_i32: i32,
+        registration_tests_affine_u64: u64,
+        registration_tests_affine_i64: i64,
+        registration_tests_affine_f32: f32,
+        registration_tests_affine_f64: f64,
+    }
+
+    macro_rules! registration_tests_rigid {
+        ($($name:ident: $t:ty $(,)?)*) => {
+            $(
+                #[test]
+                fn $name() -> Result<()> {
+                    let j = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
+                    let shape = j.shape();
+                    let origin = [
+                        ((shape[1] - 1) as f64) / 2f64,
+                        ((shape[0] - 1) as f64) / 2f64,
+                    ];
+                    let angle = 0.05f64;
+                    let (sin, cos) = angle.sin_cos();
+                    let s = Transform::new([cos, -sin, sin, cos, 5., 7.], origin, [shape[0], shape[1]]);
+                    let k = s.transform_image_bspline(j.view())?;
+                    let t = Transform::register_rigid(j.view(), k.view())?.inverse()?;
+                    let m = t.matrix();
+                    let o = m.slice(s![..2, ..2]).dot(&m.slice(s![..2, ..2]).t()) - Array2::<f64>::eye(2);
+                    assert!(o.iter().all(|x| x.abs() < 1e-6));
+                    assert!((m[[1, 0]].atan2(m[[0, 0]]) - angle).abs() < 0.005);
+                    assert!((m[[0, 2]] - 5.).abs() < 0.1);
+                    assert!((m[[1, 2]] - 7.).abs() < 0.1);
+                    Ok(())
+                }
+            )*
+        }
+    }
+
+    registration_tests_rigid! {
+        registration_tests_rigid_u8: u8,
+        registration_tests_rigid_i8: i8,
+        registration_tests_rigid_u16: u16,
+        registration_tests_rigid_i16: i16,
+        registration_tests_rigid_u32: u32,
+        registration_tests_rigid_i32: i32,
+        registration_tests_rigid_u64: u64,
+        registration_tests_rigid_i64: i64,
+        registration_tests_rigid_f32: f32,
+        registration_tests_rigid_f64: f64,
+    }
+
+    #[test]
+    fn registration_affine_edge() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let s = Transform::new([1.2, 0., 0., 1., 5., 7.], origin, [shape[0], shape[1]]);
+        // invert the intensities: the edges stay in place
+        let k = s.transform_image_bspline(j.view())?.mapv(|x| 255 - x);
+        let t = Transform::register_affine_edge(j.view(), k.view())?.inverse()?;
+        let d = (t.matrix() - s.matrix()).powi(2).sum();
+        assert!(d < 0.01);
+        Ok(())
+    }
+
+    #[test]
+    fn registration_affine_with_preprocessor() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        // the absolute deviation from the mean is the same for an image and its inverse
+        let l = k.mapv(|x| 255 - x);
+        let deviation = |image: ArrayView2<u8>| {
+            let mean = image.mapv(|x| x as f64).mean().unwrap_or(0f64);
+            image.mapv(|x| (x as f64 - mean).abs())
+        };
+        let t = Transform::register_affine_with_preprocessor(j.view(), l.view(), deviation)?;
+        let u = Transform::register_affine_with_preprocessor(j.view(), k.view(), deviation)?;
+        assert_eq!(t.shape, [600, 800]);
+        for transform in [t, u] {
+            assert!((transform.parameters[4] + 10f64).abs() < 0.5);
+            assert!((transform.parameters[5] + 20f64).abs() < 0.5);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkerboard() -> Result<()> {
+        let a = Array2::<u8>::zeros((6, 8));
+        let b = Array2::<u8>::ones((6, 8));
+        let c = checkerboard(a.view(), b.view(), [4, 3])?;
+        for y in 0..6 {
+            for x in 0..8 {
+                assert_eq!(c[[y, x]], (((x / 2) + (y / 2)) % 2) as u8);
+            }
+        }
+        assert!(checkerboard(a.view(), b.t(), [4, 3]).is_err());
+        assert!(checkerboard(a.view(), b.view(), [0, 3]).is_err());
+        Ok(())
+    }
+
+    macro_rules! registration_tests_similarity {
+        ($($name:ident: $t:ty $(,)?)*) => {
+            $(
+                #[test]
+                fn $name() -> Result<()> {
+                    let j = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
+                    let shape = j.shape();
+                    let origin = [
+                        ((shape[1] - 1) as f64) / 2f64,
+                        ((shape[0] - 1) as f64) / 2f64,
+                    ];
+                    let s = Transform::new([1.03, 0., 0., 1.03, 5., 7.], origin, [shape[0], shape[1]]);
+                    let k = s.transform_image_bspline(j.view())?;
+                    let t = Transform::register_similarity(j.view(), k.view())?.inverse()?;
+                    let [a, b, c, d, _, _] = t.parameters;
+                    // equal singular values
+                    assert!((a - d).abs() < 1e-6);
+                    assert!((b + c).abs() < 1e-6);
+                    assert!(((a * d - b * c).sqrt() - 1.03).abs() < 0.002);
+                    Ok(())
+                }
+            )*
+        }
+    }
+
+    registration_tests_similarity! {
+        registration_tests_similarity_u8: u8,
+        registration_tests_similarity_u16: u16,
+        registration_tests_similarity_f32: f32,
+        registration_tests_similarity_f64: f64,
+    }
+
+    #[test]
+    fn test_apply_to_image_roi() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let t = Transform::new(
+            [1.1, 0.1, -0.1, 0.9, 12., -8.],
+            origin,
+            [shape[0], shape[1]],
+        );
+        for interpolator in [
+            Interpolator::BSpline { order: 3 },
+            Interpolator::NearestNeighbor,
+        ] {
+            let full = t.transform_image(j.view(), interpolator)?;
+            let roi = t.apply_to_image_roi(j.view(), [100, 200, 250, 420], interpolator)?;
+            assert_eq!(roi.shape(), &[150, 220]);
+            assert_eq!(roi, full.slice(s![100..250, 200..420]));
+        }
+        assert!(
+            t.apply_to_image_roi(
+                j.view(),
+                [100, 200, 50, 420],
+                Interpolator::BSpline { order: 3 }
+            )
+            .is_err()
+        );
+        assert!(
+            t.apply_to_image_roi(
+                j.view(),
+                [100, 200, 250, 900],
+                Interpolator::BSpline { order: 3 }
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ssd_rmse() -> Result<()> {
+        let a = array![[1u8, 2], [3, 4]];
+        let b = array![[1u8, 0], [6, 8]];
+        assert_eq!(ssd(a.view(), b.view())?, 29f64);
+        assert_eq!(rmse(a.view(), b.view())?, 29f64.sqrt() / 2f64);
+        assert_eq!(ssd(a.view(), a.view())?, 0f64);
+        let c = array![[0.5f32, -1.5, 2.]];
+        let d = array![[1f32, 0.5, 2.]];
+        assert_eq!(ssd(c.view(), d.view())?, 4.25);
+        assert_eq!(rmse(c.view(), d.view())?, (4.25f64 / 3f64).sqrt());
+        assert!(ssd(a.view(), c.mapv(|x| x as u8).view()).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn interpbs_f16() -> Result<()> {
+        let j = julia_image(-120f32, 10f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let transform = Transform::new([1., 0., 0., 1., 120., -10.], origin, [shape[0], shape[1]]);
+        let a =
+            transform.transform_image_bspline(j.mapv(|x| half::f16::from_f32(x as f32)).view())?;
+        let b = transform.transform_image_bspline(j.mapv(|x| x as f32).view())?;
+        assert!(
+            a.iter()
+                .zip(b.iter())
+                .all(|(a, b)| (a.to_f32() - b).abs() <= 0.5)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_affine() -> Result<()> {
+        let t = Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [5., 7.], [0, 0]);
+        let src = [[0., 0.], [10., 0.], [0., 10.], [20., 30.]];
+        let dst: Vec<[f64; 2]> = src
+            .iter()
+            .map(|&[x, y]| {
+                let [a, b, c, d, tx, ty] = t.parameters;
+                [
+                    a * (x - 5.) + b * (y - 7.) + tx + 5.,
+                    c * (x - 5.) + d * (y - 7.) + ty + 7.,
+                ]
+            })
+            .collect();
+        let p = fit_affine(&src, &dst, t.origin)?;
+        assert!(
+            p.iter()
+                .zip(t.parameters.iter())
+                .all(|(a, b)| (a - b).abs() < 1e-9)
+        );
+        assert!(fit_affine(&src[..2], &dst[..2], t.origin).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn registration_affine_tiled() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let s = Transform::new([1.02, 0., 0., 1., 5., 7.], origin, [shape[0], shape[1]]);
+        let k = s.transform_image_bspline(j.view())?;
+        let t = Transform::register_affine_tiled(j.view(), k.view(), [200, 200], 50)?.inverse()?;
+        let d = (t.matrix() - s.matrix()).powi(2).sum();
+        assert!(d < 0.1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_opencv_mat_params() {
+        // cv2.getRotationMatrix2D((50, 40), 30, 1)
+        let (b, a) = 30f64.to_radians().sin_cos();
+        let m = [
+            a,
+            b,
+            (1f64 - a) * 50f64 - b * 40f64,
+            -b,
+            a,
+            b * 50f64 + (1f64 - a) * 40f64,
+        ];
+        let t = Transform::from_opencv_mat_params(m);
+        assert_eq!(t.origin, [0f64; 2]);
+        assert_eq!(t.to_opencv_mat_params(), m);
+        let s = Transform::new([a, b, -b, a, 0., 0.], [50., 40.], [80, 100]);
+        assert!(
+            s.to_opencv_mat_params()
+                .iter()
+                .zip(m.iter())
+                .all(|(p, q)| (p - q).abs() < 1e-12)
+        );
+    }
+
+    #[test]
+    fn registration_rotation() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let angle = 3f64.to_radians();
+        let (sin, cos) = angle.sin_cos();
+        let s = Transform::new([cos, -sin, sin, cos, 0., 0.], origin, [shape[0], shape[1]]);
+        let k = s.transform_image_bspline(j.view())?;
+        let t = Transform::register_rotation(j.view(), k.view(), None)?.inverse()?;
+        assert_eq!(t.origin, origin);
+        assert!(t.is_rigid());
+        assert!(t.parameters[4..].iter().all(|x| x.abs() < 1e-12));
+        let m = t.matrix();
+        assert!((m[[1, 0]].atan2(m[[0, 0]]) - angle).abs() < 0.05f64.to_radians());
+        Ok(())
+    }
+
+    #[test]
+    fn mul_translation() {
+        let a = Transform::new([1.1, 0.2, -0.3, 0.9, 3.5, -2.25], [0.0, 0.0], [100, 100]);
+        let b = Transform::new([0.8, -0.5, 0.4, 1.2, -1.0, 4.0], [0.0, 0.0], [100, 100]);
+        // [A a; 0 1] * [B b; 0 1] = [A B, A b + a; 0 1]
+        let expected = [
+            1.1 * 0.8 + 0.2 * 0.4,
+            1.1 * -0.5 + 0.2 * 1.2,
+            -0.3 * 0.8 + 0.9 * 0.4,
+            -0.3 * -0.5 + 0.9 * 1.2,
+            -1.1 + 0.2 * 4.0 + 3.5,
+            0.3 + 0.9 * 4.0 - 2.25,
+        ];
+        let product = a * b;
+        assert!(
+            product
+                .parameters
+                .iter()
+                .zip(expected)
+                .all(|(p, q)| (p - q).abs() < 1e-12)
+        );
+    }
+
+    #[test]
+    fn registration_origin_non_square() -> Result<()> {
+        // 600 rows and 800 columns, the origin is (x, y), so (column, row)
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let t = Transform::register_affine(j.view(), k.view())?;
+        assert_eq!(t.origin, [399.5, 299.5]);
+        assert_eq!(t.shape, [600, 800]);
+        let r = t.transform_image_bspline(k.view())?;
+        let interior = s![50..550, 50..750];
+        assert!(
+            rmse(j.slice(interior), r.slice(interior))?
+                < 0.2 * rmse(j.slice(interior), k.slice(interior))?
+        );
+        let t = Transform::register_translation(j.view(), k.view())?;
+        assert_eq!(t.origin, [399.5, 299.5]);
+        Ok(())
+    }
+
+    #[test]
+    fn registration_translation_non_contiguous() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let t = Transform::register_translation(
+            j.slice(s![50..450, 100..700]),
+            k.slice(s![50..450, 100..700]),
+        )?;
+        let mut m = Array2::eye(3);
+        m[[0, 2]] = -10f64;
+        m[[1, 2]] = -20f64;
+        let d = (t.matrix() - m).powi(2).sum();
+        assert!(d < 0.01);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "elastix")]
+    fn registration_multichannel() -> Result<()> {
+        let channels = |j: Array2<u8>| {
+            ndarray::stack(
+                ndarray::Axis(2),
+                &[
+                    j.view(),
+                    j.mapv(|x| 255 - x).view(),
+                    j.mapv(|x| x / 2).view(),
+                ],
+            )
+        };
+        let j = channels(julia_image(0f32, 0f32)?)?;
+        let k = channels(julia_image(10f32, 20f32)?)?;
+        let t =
+            Transform::register_multichannel(j.view(), k.view(), RegistrationMode::Translation)?;
+        let mut m = Array2::eye(3);
+        m[[0, 2]] = -10f64;
+        m[[1, 2]] = -20f64;
+        let d = (t.matrix() - m).powi(2).sum();
+        assert!(d < 0.01);
+        let shape = j.shape();
+        for c in 0..3 {
+            let l = t.transform_image_bspline(k.index_axis(ndarray::Axis(2), c))?;
+            let r = rmse(
+                l.slice(s![50..shape[0] - 50, 50..shape[1] - 50])
+                    .mapv(|x| x as f64)
+                    .view(),
+                j.slice(s![50..shape[0] - 50, 50..shape[1] - 50, c])
+                    .mapv(|x| x as f64)
+                    .view(),
+            )?;
+            assert!(r < 10f64);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_interpolate() -> Result<()> {
+        let a = Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [59.5, 49.5], [120, 100]);
+        let b = Transform::new([0.8, -0.1, 0.2, 1.1, -4.2, 3.5], [59.5, 49.5], [120, 100]);
+        assert_eq!(a.interpolate(&b, 0f64), a);
+        assert_eq!(a.interpolate(&b, 1f64), b);
+        let c = Transform::from_translation([0., 0.]);
+        let d = Transform::from_translation([20., 10.]);
+        let p = array![[3f64, 4f64]];
+        let q = c.interpolate(&d, 0.5).transform_coordinates(p.view())?;
+        assert_eq!(q, array![[13f64, 9f64]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_without_spacing() -> Result<()> {
+        let t: Transform = serde_yaml::from_str(
+            "parameters: [1.2, 0.3, -0.4, 0.9, 10.2, -9.5]\n\
+             dparameters: [0, 0, 0, 0, 0, 0]\n\
+             origin: [59.5, 49.5]\n\
+             shape: [120, 100]\n",
+        )?;
+        assert_eq!(
+            t,
+            Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [59.5, 49.5], [120, 100])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_physical() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let spacing = [0.5, 2.0];
+        let t = Transform::register_physical(
+            j.view(),
+            k.view(),
+            RegistrationMode::Translation,
+            spacing,
+            [1., 0., 0., 1.],
+        )?;
+        assert_eq!(t.spacing, spacing);
+        assert!((t.parameters[4] + 5f64).abs() < 0.1);
+        assert!((t.parameters[5] + 40f64).abs() < 0.4);
+        let u = Transform::register_translation(j.view(), k.view())?;
+        let l = t.transform_image_bspline(k.view())?;
+        let m = u.transform_image_bspline(k.view())?;
+        let d = rmse(l.mapv(|x| x as f64).view(), m.mapv(|x| x as f64).view())?;
+        assert!(d < 1f64);
+        Ok(())
+    }
+
+    #[test]
+    fn test_itk_version() {
+        let version = itk_version();
+        assert!(version.starts_with(|c: char| c.is_ascii_digit()));
+        assert_eq!(version.split('.').count(), 3);
+        assert!(std::ptr::eq(version, itk_version()));
+    }
+
+    #[test]
+    fn test_simpleitk_version() {
+        let version = simpleitk_version();
+        assert!(version.starts_with(|c: char| c.is_ascii_digit()));
+        assert!(std::ptr::eq(version, simpleitk_version()));
+    }
+
+    #[test]
+    fn registration_scale() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let s = Transform::new([1.05, 0., 0., 0.98, 0., 0.], origin, [shape[0], shape[1]]);
+        let k = s.transform_image_bspline(j.view())?;
+        let t = Transform::register_scale(j.view(), k.view(), true)?.inverse()?;
+        assert_eq!(t.origin, origin);
+        assert!((t.parameters[0] - 1.05).abs() < 1e-3);
+        assert!((t.parameters[3] - 0.98).abs() < 1e-3);
+        assert!(t.parameters[1..3].iter().all(|x| *x == 0f64));
+        let t = Transform::register_scale(j.view(), k.view(), false)?.inverse()?;
+        let mean = (1.05f64 * 0.98).sqrt();
+        assert!((t.parameters[0] - mean).abs() < 1e-3);
+        assert_eq!(t.parameters[0], t.parameters[3]);
+        Ok(())
+    }
+
+    #[test]
+    fn registration_constant_image() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let c = Array2::<u8>::from_elem(j.dim(), 100);
+        let error = Transform::register_translation(c.view(), j.view()).unwrap_err();
+        assert!(error.to_string().contains("constant intensity"));
+        Ok(())
+    }
+
+    #[test]
+    fn registration_shape_mismatch() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = j.slice(s![..500, ..]);
+        let error = Transform::register_translation(j.view(), k).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TransformError>(),
+            Some(&TransformError::ShapeMismatch {
+                fixed: vec![600, 800],
+                moving: vec![500, 800]
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn apply_to_point() -> Result<()> {
+        let mut t = Transform::new(
+            [1.1, 0.2, -0.3, 0.9, 12.5, -7.25],
+            [300.0, 400.0],
+            [600, 800],
+        );
+        t.dparameters = [0.01, 0.02, 0.03, 0.04, 0.5, 0.6];
+        let points = array![[0.0, 0.0], [1.5, -2.0], [123.4, 567.8], [-50.0, 1e4]];
+        let c = t.transform_coordinates(&points)?;
+        for (point, expected) in points.outer_iter().zip(c.outer_iter()) {
+            let (p, dp) = t.apply_to_point_with_uncertainty(point[0], point[1]);
+            assert_eq!(p, t.apply_to_point(point[0], point[1]));
+            assert!((p[0] - expected[0]).abs() < 1e-9);
+            assert!((p[1] - expected[1]).abs() < 1e-9);
+            let d = t.dmatrix().dot(&array![point[0], point[1], 1.0]);
+            assert!((dp[0] - d[0]).abs() < 1e-9);
+            assert!((dp[1] - d[1]).abs() < 1e-9);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn registration_affine_recording() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let s = Transform::new(
+            [1.02, 0.03, -0.02, 0.98, 4.0, -3.0],
+            origin,
+            [shape[0], shape[1]],
+        );
+        let k = s.transform_image_bspline(j.view())?;
+        let (t, history) = Transform::register_affine_recording(j.view(), k.view())?;
+        assert!(history.len() > 1);
+        assert!(history.iter().all(|m| m.is_finite()));
+        // mutual information is negative, lower is better
+        assert!(history.last().unwrap() < history.first().unwrap());
+        let t = t.inverse()?;
+        assert!(
+            t.parameters[..4]
+                .iter()
+                .zip(&s.parameters)
+                .all(|(a, b)| (a - b).abs() < 0.01)
+        );
+        assert!(
+            t.parameters[4..]
+                .iter()
+                .zip(&s.parameters[4..])
+                .all(|(a, b)| (a - b).abs() < 0.5)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn non_finite() -> Result<()> {
+        let mut t = Transform::new([1.0, 0.0, 0.0, 1.0, 2.0, 3.0], [0.0, 0.0], [10, 10]);
+        assert!(t.is_finite());
+        assert!(crate::sys::check_finite(&t.parameters).is_ok());
+        t.parameters[4] = f64::NAN;
+        assert!(!t.is_finite());
+        let error = crate::sys::check_finite(&t.parameters).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TransformError>(),
+            Some(TransformError::NonFinite { .. })
+        ));
+        t.parameters[4] = f64::INFINITY;
+        assert!(!t.is_finite());
+        Ok(())
+    }
+
+    #[test]
+    fn non_finite_initial() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let mut initial =
+            Transform::new([1.0, 0.0, 0.0, 1.0, 0.0, 0.0], [399.5, 299.5], [600, 800]);
+        for value in [f64::NAN, f64::INFINITY] {
+            initial.parameters[4] = value;
+            let error = Transform::register_affine_from(j.view(), k.view(), &initial).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<TransformError>(),
+                Some(TransformError::NonFinite { .. })
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn registration_affine_both() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let s = Transform::new([1.1, 0., 0., 1., 5., 7.], origin, [shape[0], shape[1]]);
+        let k = s.transform_image_bspline(j.view())?;
+        let (forward, inverse) = Transform::register_affine_both(j.view(), k.view())?;
+        assert!((inverse.matrix() - s.matrix()).powi(2).sum() < 0.01);
+        assert!(
+            (forward.matrix().dot(&inverse.matrix()) - Array2::<f64>::eye(3))
+                .abs()
+                .sum()
+                < 1e-9
+        );
+        let r = forward.transform_image_bspline(k.view())?;
+        let interior = s![100..500, 100..700];
+        assert!(
+            rmse(j.slice(interior), r.slice(interior))?
+                < 0.2 * rmse(j.slice(interior), k.slice(interior))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_affine_best_of_n() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let s = Transform::new(
+            [1.02, 0.03, -0.02, 0.98, 4.0, -3.0],
+            origin,
+            [shape[0], shape[1]],
+        );
+        let k = s.transform_image_bspline(j.view())?;
+        let t = Transform::register_affine_best_of_n(j.view(), k.view(), 3)?.inverse()?;
+        assert!((t.matrix() - s.matrix()).powi(2).sum() < 0.1);
+        assert!(Transform::register_affine_best_of_n(j.view(), k.view(), 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn registration_center() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let center = [250.0, 200.0];
+        let angle = 3f64.to_radians();
+        let (sin, cos) = angle.sin_cos();
+        let s = Transform::new([cos, -sin, sin, cos, 0., 0.], center, [shape[0], shape[1]]);
+        let k = s.transform_image_bspline(j.view())?;
+        let interior = s![100..500, 100..700];
+
+        let config = RegistrationConfig::builder().center(center).build()?;
+        let t = Transform::register(j.view(), k.view(), RegistrationMode::Rotation, &config)?;
+        assert_eq!(t.origin, center);
+        let m = t.inverse()?.matrix();
+        assert!((m[[1, 0]].atan2(m[[0, 0]]) - angle).abs() < 0.05f64.to_radians());
+        let r = t.transform_image_bspline(k.view())?;
+        let error = rmse(j.slice(interior), r.slice(interior))?;
+
+        // a rotation about the center of the image cannot undo a rotation about another point
+        let u = Transform::register(
+            j.view(),
+            k.view(),
+            RegistrationMode::Rotation,
+            &RegistrationConfig::default(),
+        )?;
+        assert_eq!(
+            u.origin,
+            [
+                ((shape[1] - 1) as f64) / 2f64,
+                ((shape[0] - 1) as f64) / 2f64
+            ]
+        );
+        let r = u.transform_image_bspline(k.view())?;
+        assert!(error < rmse(j.slice(interior), r.slice(interior))?);
+
+        let t = Transform::register(j.view(), k.view(), RegistrationMode::Rigid, &config)?;
+        assert_eq!(t.origin, center);
+        assert!(t.parameters[4..].iter().all(|x| x.abs() < 0.5));
+        Ok(())
+    }
+
+    #[test]
+    fn apply_to_image_streaming() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = [j.shape()[0], j.shape()[1]];
+        let origin = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let angle = 10f64.to_radians();
+        let (sin, cos) = angle.sin_cos();
+        let t = Transform::new(
+            [1.1 * cos, -sin, sin, 0.9 * cos, 12.3, -45.6],
+            origin,
+            shape,
+        );
+        for interpolator in [
+            Interpolator::NearestNeighbor,
+            Interpolator::BSpline { order: 3 },
+        ] {
+            let full = t.transform_image(j.view(), interpolator)?;
+            let mut streamed = Array2::<u8>::zeros(full.dim());
+            t.apply_to_image_streaming(
+                |r| Ok(j.slice(s![r[0]..r[2], r[1]..r[3]]).to_owned()),
+                |r, tile| {
+                    streamed.slice_mut(s![r[0]..r[2], r[1]..r[3]]).assign(&tile);
+                    Ok(())
+                },
+                shape,
+                [128, 96],
+                interpolator,
+            )?;
+            let max_difference = full
+                .iter()
+                .zip(streamed.iter())
+                .map(|(a, b)| (*a as i32 - *b as i32).abs())
+                .max()
+                .unwrap();
+            match interpolator {
+                Interpolator::NearestNeighbor => assert_eq!(max_difference, 0),
+                _ => assert!(max_difference <= 1),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn inverse_or_identity() -> Result<()> {
+        let identity = Transform::from_translation([0f64, 0f64]);
+        assert!(identity.inverse_or_identity().is_unity());
+        let t = Transform::new([2., 0.5, -0.3, 1.5, 4., -7.], [10., 20.], [40, 30]);
+        assert_eq!(t.inverse_or_identity(), t.inverse()?);
+        let singular = Transform::new([0., 0., 0., 1., 4., -7.], [10., 20.], [40, 30]);
+        assert!(singular.inverse().is_err());
+        assert_eq!(singular.inverse_or_identity(), identity);
+        Ok(())
+    }
+
+    #[test]
+    fn transform_image_into() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let t = Transform::new([1.1, 0.1, -0.05, 0.95, 3., -4.], [400., 300.], [600, 800]);
+        let mut out = Array2::<u8>::zeros(j.dim());
+        for image in [&j, &k, &j] {
+            t.transform_image_into(image.view(), &mut out, Interpolator::BSpline { order: 3 })?;
+            assert_eq!(
+                out,
+                t.transform_image(image.view(), Interpolator::BSpline { order: 3 })?
+            );
+        }
+        let mut transposed = out.t().to_owned();
+        t.transform_image_into(j.t(), &mut transposed, Interpolator::NearestNeighbor)?;
+        assert_eq!(
+            transposed,
+            t.transform_image(j.t(), Interpolator::NearestNeighbor)?
+        );
+        let mut wrong = Array2::<u8>::zeros((10, 10));
+        assert!(
+            t.transform_image_into(j.view(), &mut wrong, Interpolator::BSpline { order: 3 })
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_3d_rigid() -> Result<()> {
+        let shift = [3.0, -2.0, 1.5];
+        let j = blobs_image([0.0; 3]);
+        let k = blobs_image(shift);
+        let t = Transform::register_affine_3d_rigid(j.view(), k.view())?;
+        assert_eq!(t.shape, [48, 48, 48]);
+        assert_eq!(t.origin, [23.5, 23.5, 23.5]);
+        let m = t.matrix();
+        let d = (&m.slice(s![..3, ..3]) - &Array2::<f64>::eye(3))
+            .powi(2)
+            .sum();
+        assert!(d < 1e-3);
+        for i in 0..3 {
+            assert!((m[[i, 3]] - shift[i]).abs() < 0.2);
+        }
+        let c = Array3::<f64>::from_elem((48, 48, 48), 1.0);
+        assert!(Transform::register_affine_3d_rigid(j.view(), c.view()).is_err());
+        assert!(Transform::register_affine_3d_rigid(j.view(), j.slice(s![1.., .., ..])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(
+            Transform::default(),
+            Transform::from_translation([0.0, 0.0])
+        );
+        assert!(Transform::default().is_unity());
+    }
+
+    #[test]
+    fn transform_coordinates_order() -> Result<()> {
+        let t = Transform::new([1.1, 0.2, -0.3, 0.9, 3.5, -2.25], [10.0, 20.0], [100, 100]);
+        let coordinates =
+            Array2::from_shape_fn((100_000, 2), |(i, j)| ((i * (j + 3)) % 1013) as f64 * 0.37);
+        let res = t.transform_coordinates(&coordinates)?;
+        for (c, r) in coordinates.rows().into_iter().zip(res.rows()) {
+            assert_eq!([r[0], r[1]], t.apply_to_point(c[0], c[1]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn registration_coarse_to_fine() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(40f32, -30f32)?;
+        let t = Transform::register_affine(j.view(), k.view())?;
+        let (u, history) = Transform::coarse_to_fine(j.view(), k.view(), 4)?;
+        assert_eq!(u.origin, t.origin);
+        assert_eq!(u.shape, t.shape);
+        let d = (t.matrix() - u.matrix()).powi(2).sum();
+        assert!(d < 0.01);
+        assert!(
+            Transform::register_affine_coarse_to_fine(j.view(), k.view(), 4)?
+                .parameters
+                .iter()
+                .zip(u.parameters)
+                .all(|(a, b)| (a - b).abs() < 1e-6)
+        );
+        // seeded close to the optimum, the full resolution stage converges sooner
+        let (_, full) = Transform::register_affine_recording(j.view(), k.view())?;
+        assert!(history.len() <= full.len());
+        assert!(Transform::register_affine_coarse_to_fine(j.view(), k.view(), 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn transform_bbox() {
+        let t = Transform::new([1.0, 0.0, 0.0, 1.0, 5.0, -3.0], [0.0, 0.0], [100, 200]);
+        assert_eq!(t.transform_bbox(), ([5.0, -3.0], [205.0, 97.0]));
+        // a square rotated by 45 degrees grows by a factor sqrt(2)
+        let (sin, cos) = std::f64::consts::FRAC_PI_4.sin_cos();
+        let t = Transform::new([cos, -sin, sin, cos, 0.0, 0.0], [0.0, 0.0], [100, 100]);
+        let (min, max) = t.transform_bbox();
+        let size = 100.0 * 2f64.sqrt();
+        assert!((max[0] - min[0] - size).abs() < 1e-9);
+        assert!((max[1] - min[1] - size).abs() < 1e-9);
+        assert!((min[0] + size / 2.0).abs() < 1e-9);
+        assert!(min[1].abs() < 1e-9);
+    }
+
+    #[test]
+    fn iter_parameters() {
+        let mut t = Transform::new([1.1, 0.2, -0.3, 0.9, 12.5, -7.25], [0.0, 0.0], [10, 10]);
+        t.dparameters = [0.01, 0.02, 0.03, 0.04, 0.5, 0.6];
+        assert_eq!(
+            t.iter_parameters()
+                .zip(t.iter_dparameters())
+                .collect::<Vec<_>>(),
+            t.parameters
+                .into_iter()
+                .zip(t.dparameters)
+                .collect::<Vec<_>>()
+        );
+        t.iter_parameters_mut().for_each(|p| *p *= 2.0);
+        assert_eq!(t.parameters, [2.2, 0.4, -0.6, 1.8, 25.0, -14.5]);
+    }
+
+    #[test]
+    fn registration_3d_affine() -> Result<()> {
+        let shift = [-2.0, 2.5, 1.0];
+        let j = blobs_image([0.0; 3]);
+        let k = blobs_image(shift);
+        let t = Transform::register_affine_3d(j.view(), k.view())?;
+        assert_eq!(t.origin, [23.5, 23.5, 23.5]);
+        let m = t.matrix();
+        let d = (&m.slice(s![..3, ..3]) - &Array2::<f64>::eye(3))
+            .powi(2)
+            .sum();
+        assert!(d < 1e-3);
+        for i in 0..3 {
+            assert!((m[[i, 3]] - shift[i]).abs() < 0.2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn registration_with_options() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let error = |t: &Transform| {
+            ((t.parameters[4] + 10.0).powi(2) + (t.parameters[5] + 20.0).powi(2)).sqrt()
+        };
+        // default options reproduce the results without options
+        let default = RegistrationOptions::default();
+        assert_eq!(
+            Transform::register_translation_with(j.view(), k.view(), &default)?,
+            Transform::register_translation(j.view(), k.view())?
+        );
+        assert_eq!(
+            Transform::register_affine_with(j.view(), k.view(), &default)?,
+            Transform::register_affine(j.view(), k.view())?
+        );
+
+        let one = RegistrationOptions::builder().max_iterations(1).build()?;
+        let many = RegistrationOptions::builder().max_iterations(200).build()?;
+        let t1 = Transform::register_translation_with(j.view(), k.view(), &one)?;
+        let t200 = Transform::register_translation_with(j.view(), k.view(), &many)?;
+        assert!(error(&t200) < 0.1);
+        assert!(error(&t1) > 1.0);
+        let a1 = Transform::register_affine_with(j.view(), k.view(), &one)?;
+        let a200 = Transform::register_affine_with(j.view(), k.view(), &many)?;
+        assert!(error(&a1) > error(&a200));
+        Ok(())
+    }
+
+    #[test]
+    fn clamp_translation() -> Result<()> {
+        let t = Transform::new(
+            [1.1, 0.2, -0.3, 0.9, 120.0, -7.25],
+            [10.0, 20.0],
+            [100, 100],
+        );
+        let c = t.clamp_translation(50.0, 5.0)?;
+        assert_eq!(c.parameters, [1.1, 0.2, -0.3, 0.9, 50.0, -5.0]);
+        assert_eq!(c.origin, t.origin);
+        assert_eq!(t.clamp_translation(500.0, 500.0)?, t);
+        let c = t.clamp_all_parameters([
+            (0.9, 1.0),
+            (-0.1, 0.1),
+            (-1.0, 1.0),
+            (0.0, 2.0),
+            (-200.0, 200.0),
+            (-10.0, 10.0),
+        ])?;
+        assert_eq!(c.parameters, [1.0, 0.1, -0.3, 0.9, 120.0, -7.25]);
+        assert!(t.clamp_translation(-1.0, 5.0).is_err());
+        assert!(t.clamp_translation(50.0, f64::NAN).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn registration_mi_bins() -> Result<()> {
+        // different intensities for the same structure, as in images of different modalities
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?.mapv(|x| 255 - x / 2);
+        let options = RegistrationOptions::builder().mi_bins(16).build()?;
+        let t = Transform::register_translation_with(j.view(), k.view(), &options)?;
+        assert!((t.parameters[4] + 10.0).abs() < 0.5);
+        assert!((t.parameters[5] + 20.0).abs() < 0.5);
+        Ok(())
+    }
+
+    #[test]
+    fn registration_metric() -> Result<()> {
+        // inverted and gamma-remapped intensities, as in images of different modalities
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?
+            .mapv(|x| (255.0 * (1.0 - (x as f64 / 255.0).powf(0.3))) as u8);
+        let error = |metric: Metric| -> Result<f64> {
+            let options = RegistrationOptions::builder().metric(metric).build()?;
+            let t = Transform::register_translation_with(j.view(), k.view(), &options)?;
+            Ok(((t.parameters[4] + 10.0).powi(2) + (t.parameters[5] + 20.0).powi(2)).sqrt())
+        };
+        assert!(error(Metric::MeanSquares)? > 2.0);
+        assert!(error(Metric::MutualInformation)? < 0.5);
+        Ok(())
+    }
+
+    #[test]
+    fn registration_multiscale() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(60f32, -80f32)?;
+        let error = |t: &Transform| {
+            ((t.parameters[4] + 60.0).powi(2) + (t.parameters[5] - 80.0).powi(2)).sqrt()
+        };
+        let t1 = Transform::register_affine_multiscale(j.view(), k.view(), 1)?;
+        let t3 = Transform::register_affine_multiscale(j.view(), k.view(), 3)?;
+        assert!(error(&t3) < 1.0);
+        assert!(error(&t3) < error(&t1));
+        assert!(Transform::register_affine_multiscale(j.view(), k.view(), 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn registration_optimizer() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        // a single resolution, so that the iterations are those of one level
+        let run = |optimizer: Optimizer| -> Result<RegistrationResult> {
+            let config = RegistrationConfig::builder()
+                .pyramid_levels(1)
+                .optimizer(optimizer)
+                .build()?;
+            Transform::register_with_result(j.view(), k.view(), RegistrationMode::Affine, &config)
+        };
+        let error = |t: &Transform| {
+            ((t.parameters[4] + 10.0).powi(2) + (t.parameters[5] + 20.0).powi(2)).sqrt()
+        };
+        let step = run(Optimizer::RegularStepGradientDescent)?;
+        let line = run(Optimizer::GradientDescentLineSearch)?;
+        let conjugate = run(Optimizer::ConjugateGradientLineSearch)?;
+        for result in [&step, &line, &conjugate] {
+            assert!(error(&result.transform) < 0.5);
+            assert!(result.iterations > 0);
+        }
+        assert!(line.iterations <= step.iterations);
+        assert!(conjugate.iterations <= step.iterations);
+
+        // with an optimizer, register_affine_with uses the sitk registration method, not elastix
+        let options = RegistrationOptions::builder()
+            .optimizer(Optimizer::ConjugateGradientLineSearch)
+            .build()?;
+        assert!(
+            error(&Transform::register_affine_with(
+                j.view(),
+                k.view(),
+                &options
+            )?) < 0.5
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_masked() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let mut k = julia_image(10f32, 20f32)?;
+        // a bright artifact in moving covering most of the image, with structure that matches
+        // fixed without any shift
+        let artifact = s![100..550, 100..750];
+        k.slice_mut(artifact)
+            .assign(&j.slice(artifact).mapv(|x| x.saturating_add(100)));
+        let mut mask = Array2::<u8>::ones(k.dim());
+        mask.slice_mut(artifact).fill(0);
+        let error = |t: &Transform| {
+            ((t.parameters[4] + 10.0).powi(2) + (t.parameters[5] + 20.0).powi(2)).sqrt()
+        };
+
+        let unmasked = Transform::register_affine_masked(j.view(), k.view(), None, None)?;
+        assert!(error(&unmasked) > 2.0);
+        let masked =
+            Transform::register_affine_masked(j.view(), k.view(), None, Some(mask.view()))?;
+        assert!(error(&masked) < 0.5);
+
+        let wrong = Array2::<u8>::ones((10, 10));
+        assert!(
+            Transform::register_affine_masked(j.view(), k.view(), Some(wrong.view()), None)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_from() -> Result<()> {
+        // too large a shift to be found starting from the identity
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(150f32, 0f32)?;
+        let mut initial = Transform::from_translation([-140.0, 0.0]);
+        initial.origin = [399.5, 299.5];
+        let t = Transform::register_affine_from(j.view(), k.view(), &initial)?;
+        assert_eq!(t.origin, initial.origin);
+        assert!((t.parameters[4] + 150.0).abs() < 0.5);
+        assert!(t.parameters[5].abs() < 0.5);
+
+        // from the identity, registration fails or ends up away from the shift
+        let identity = Transform::new([1.0, 0.0, 0.0, 1.0, 0.0, 0.0], initial.origin, [600, 800]);
+        if let Ok(t) = Transform::register_affine_from(j.view(), k.view(), &identity) {
+            assert!((t.parameters[4] + 150.0).abs() > 0.5 || t.parameters[5].abs() > 0.5);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rescaled() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let t =
+            Transform::register_affine(&pyramid::downsample(&j, 2)?, &pyramid::downsample(&k, 2)?)?;
+        assert_eq!(t.shape, [300, 400]);
+        let u = t.rescaled(2f64);
+        assert_eq!(u.shape, [600, 800]);
+        assert_eq!(u.origin, t.origin.map(|o| 2f64 * o + 0.5));
+        assert_eq!(u.origin, [399.5, 299.5]);
+        assert_eq!(u.parameters[..4], t.parameters[..4]);
+        assert!((u.parameters[4] + 10f64).abs() < 0.5);
+        assert!((u.parameters[5] + 20f64).abs() < 0.5);
+        let l = u.transform_image_bspline(k.view())?;
+        let interior = s![50..550, 50..750];
+        assert!(
+            rmse(j.slice(interior), l.slice(interior))?
+                < 0.2 * rmse(j.slice(interior), k.slice(interior))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_empty() -> Result<()> {
+        let e = Array2::<u8>::zeros((0, 10));
+        let config = RegistrationConfig::default();
+        let mode = RegistrationMode::Affine;
+        assert!(Transform::register_with_result(e.view(), e.view(), mode, &config).is_err());
+        assert!(Transform::register_affine_best_of_n(e.view(), e.view(), 2).is_err());
+        assert!(Transform::register_affine_multiscale(e.view(), e.view(), 2).is_err());
+        assert!(Transform::register_affine_recording(e.view(), e.view()).is_err());
+        assert!(Transform::register_scale(e.view(), e.view(), false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn registration_roi() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let roi = [100, 200, 300, 400];
+        let t = Transform::register_affine_roi(j.view(), k.view(), roi)?;
+        assert_eq!(t.shape, [600, 800]);
+        assert_eq!(t.origin, [399.5, 249.5]);
+        assert!((t.parameters[4] + 10f64).abs() < 0.5);
+        assert!((t.parameters[5] + 20f64).abs() < 0.5);
+        let l = t.transform_image_bspline(k.view())?;
+        let inside = s![150..350, 250..550];
+        assert!(
+            rmse(j.slice(inside), l.slice(inside))? < 0.2 * rmse(j.slice(inside), k.slice(inside))?
+        );
+        for roi in [[100, 200, 600, 400], [100, 700, 300, 200], [0, 0, 0, 10]] {
+            assert!(Transform::register_affine_roi(j.view(), k.view(), roi).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn affine_components() {
+        let close = |a: &Transform, b: &Transform| {
+            a.parameters
+                .iter()
+                .zip(b.parameters)
+                .all(|(p, q)| (p - q).abs() < 1e-12)
+        };
+        let (sin, cos) = 0.5f64.sin_cos();
+        let rotation = Transform::new([cos, -sin, sin, cos, 0.0, 0.0], [0.0; 2], [0; 2]);
+        let c = rotation.decompose();
+        assert!((c.angle - 0.5).abs() < 1e-12);
+        assert!((c.scale_x - 1.0).abs() < 1e-12);
+        assert!((c.scale_y - 1.0).abs() < 1e-12);
+        assert!(c.shear.abs() < 1e-12);
+
+        let scale = Transform::new([2.0, 0.0, 0.0, 0.5, 3.0, -4.0], [0.0; 2], [0; 2]);
+        let c = scale.decompose();
+        assert_eq!(
+            c,
+            AffineComponents {
+                angle: 0.0,
+                scale_x: 2.0,
+                scale_y: 0.5,
+                shear: 0.0,
+                tx: 3.0,
+                ty: -4.0
+            }
+        );
+
+        let shear = Transform::new([1.0, 0.3, 0.0, 1.0, 0.0, 0.0], [0.0; 2], [0; 2]);
+        let c = shear.decompose();
+        assert!(c.angle.abs() < 1e-12);
+        assert!((c.shear - 0.3).abs() < 1e-12);
+
+        for t in [
+            rotation,
+            scale,
+            shear,
+            Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [0.0; 2], [0; 2]),
+            Transform::new([-1.0, 0.0, 0.0, 1.0, 1.0, 2.0], [0.0; 2], [0; 2]),
+        ] {
+            assert!(close(&Transform::from_components(&t.decompose()), &t));
+        }
+    }
+
+    #[test]
+    fn registration_seed() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let seeded = RegistrationOptions::builder().seed(12345).build()?;
+        let t = Transform::register_affine_with(j.view(), k.view(), &seeded)?;
+        for _ in 0..2 {
+            let u = Transform::register_affine_with(j.view(), k.view(), &seeded)?;
+            assert_eq!(u.parameters, t.parameters);
+        }
+        // without a seed the result is the same as without options
+        let default = RegistrationOptions::builder().build()?;
+        assert_eq!(default.seed, None);
+        assert_eq!(
+            Transform::register_affine_with(j.view(), k.view(), &default)?,
+            Transform::register_affine(j.view(), k.view())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_and_resample() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        for interpolator in [
+            Interpolator::BSpline { order: 3 },
+            Interpolator::NearestNeighbor,
+        ] {
+            let (t, l) = Transform::register_and_resample(
+                j.view(),
+                k.view(),
+                RegistrationMode::Affine,
+                interpolator,
+            )?;
+            assert_eq!(t, Transform::register_affine(j.view(), k.view())?);
+            assert_eq!(l, t.transform_image(k.view(), interpolator)?);
+        }
+        let c = Array2::<u8>::zeros((10, 10));
+        assert!(
+            Transform::register_and_resample(
+                j.view(),
+                c.view(),
+                RegistrationMode::Affine,
+                Interpolator::BSpline { order: 3 }
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scale_rotate_about() {
+        let t = Transform::scale_about(2.0, 2.0, 0.5, 0.5);
+        assert_eq!(t.apply_to_point(0.5, 0.5), [0.5, 0.5]);
+        assert_eq!(t.apply_to_point(1.5, 0.5), [2.5, 0.5]);
+        assert_eq!(t.origin, [0.0, 0.0]);
+        assert_eq!(t.shape, [0, 0]);
+
+        let t = Transform::rotate_about(std::f64::consts::FRAC_PI_2, 10.0, 20.0);
+        let [x, y] = t.apply_to_point(10.0, 20.0);
+        assert!((x - 10.0).abs() < 1e-12 && (y - 20.0).abs() < 1e-12);
+        // (11, 20) is rotated to (10, 21)
+        let [x, y] = t.apply_to_point(11.0, 20.0);
+        assert!((x - 10.0).abs() < 1e-12 && (y - 21.0).abs() < 1e-12);
+        assert_eq!(t.origin, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn rotate_image_about() -> Result<()> {
+        for (x, y) in [(300usize, 200usize), (650, 420), (399, 299)] {
+            // a single bright pixel at the pivot
+            let mut j = Array2::<u8>::zeros((600, 800));
+            j[[y, x]] = 255;
+            let t = Transform::rotate_about(0.3, x as f64, y as f64);
+            let r = t.transform_image(j.view(), Interpolator::NearestNeighbor)?;
+            assert_eq!(r, j);
+            let t = Transform::scale_about(1.5, 0.5, x as f64, y as f64);
+            let r = t.transform_image(j.view(), Interpolator::NearestNeighbor)?;
+            assert_eq!(r[[y, x]], 255);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn pre_alignment_exhaustive() -> Result<()> {
+        // a periodic pattern with a period of 24 pixels, and a weak blob which makes only one of
+        // the periodic optima the global optimum
+        let pattern = |dx: f64| {
+            Array2::from_shape_fn((200, 200), |(y, x)| {
+                let (x, y) = (x as f64 + dx, y as f64);
+                let tau = std::f64::consts::TAU;
+                (tau * x / 24.0).cos() * (tau * y / 24.0).cos()
+                    + 0.5 * (-((x - 100.0).powi(2) + (y - 100.0).powi(2)) / 1800.0).exp()
+            })
+        };
+        let j = pattern(0.0);
+        let k = pattern(20.0);
+        // starting from the identity, the optimizer ends up in the optimum 24 pixels away
+        let single = RegistrationOptions::builder().pyramid_levels(1).build()?;
+        let t = Transform::register_translation_with(j.view(), k.view(), &single)?;
+        assert!((t.parameters[4] + 20.0).abs() > 10.0);
+
+        let initial =
+            Transform::pre_align_exhaustive(j.view(), k.view(), [2.0, 2.0], [30.0, 30.0])?;
+        assert!((initial.parameters[4] + 20.0).abs() < 2.0);
+        assert!(initial.parameters[5].abs() < 2.0);
+        let t = Transform::register_affine_from(j.view(), k.view(), &initial)?;
+        assert!((t.parameters[4] + 20.0).abs() < 0.5);
+        assert!(t.parameters[5].abs() < 0.5);
+
+        assert!(
+            Transform::pre_align_exhaustive(j.view(), k.view(), [0.0, 2.0], [30.0, 30.0]).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_convergence() -> Result<()> {
+        let j = noisy_image(&julia_image(0f32, 0f32)?, 60.0, 12345);
+        let k = noisy_image(&julia_image(10f32, 20f32)?, 60.0, 12346);
+        let error = |t: &Transform| {
+            ((t.parameters[4] + 10.0).powi(2) + (t.parameters[5] + 20.0).powi(2)).sqrt()
+        };
+        let options = |tolerance: f64, min_step: f64| {
+            RegistrationOptions::builder()
+                .optimizer(Optimizer::RegularStepGradientDescent)
+                .convergence_tolerance(tolerance)
+                .min_step_length(min_step)
+                .max_step_length(4.0)
+                .build()
+        };
+        let loose = Transform::register_translation_with(j.view(), k.view(), &options(1e3, 2.0)?)?;
+        let tight =
+            Transform::register_translation_with(j.view(), k.view(), &options(1e-8, 1e-4)?)?;
+        assert!(error(&tight) < 0.5);
+        assert!(error(&loose) > error(&tight));
+        Ok(())
+    }
+
+    #[test]
+    fn pure_translation_rotation() {
+        let t = Transform::from_translation([3.0, -4.0]);
+        assert!(t.is_pure_translation());
+        assert!(t.is_identity_rotation());
+        let almost = Transform::new(
+            [1.0 + 1e-15, 1e-15, -1e-15, 1.0, 5.0, 0.0],
+            [0.0; 2],
+            [0; 2],
+        );
+        assert!(almost.is_pure_translation());
+        assert!(almost.is_identity_rotation());
+        let off = Transform::new([1.0 + 1e-5, 0.0, 0.0, 1.0, 5.0, 0.0], [0.0; 2], [0; 2]);
+        assert!(!off.is_pure_translation());
+        assert!(!off.is_identity_rotation());
+        let off = Transform::new([1.0, 1e-5, 0.0, 1.0, 0.0, 0.0], [0.0; 2], [0; 2]);
+        assert!(!off.is_pure_translation());
+        assert!(!off.is_identity_rotation());
+
+        let rotation = Transform::rotate_about(0.3, 10.0, 20.0);
+        assert!(!rotation.is_pure_translation());
+        assert!(rotation.is_identity_rotation());
+        // a reflection is orthogonal, but not a rotation
+        let reflection = Transform::new([-1.0, 0.0, 0.0, 1.0, 0.0, 0.0], [0.0; 2], [0; 2]);
+        assert!(!reflection.is_identity_rotation());
+        assert!(!Transform::scale_about(2.0, 2.0, 0.0, 0.0).is_identity_rotation());
+    }
+
+    #[test]
+    fn registration_smoothing() -> Result<()> {
+        let j = noisy_image(&julia_image(0f32, 0f32)?, 2000.0, 54321);
+        let k = noisy_image(&julia_image(10f32, 20f32)?, 2000.0, 54322);
+        let error = |t: &Transform| {
+            ((t.parameters[4] + 10.0).powi(2) + (t.parameters[5] + 20.0).powi(2)).sqrt()
+        };
+        let plain = RegistrationOptions::builder().pyramid_levels(1).build()?;
+        let smooth = RegistrationOptions::builder()
+            .pyramid_levels(1)
+            .smoothing_sigma(2.0)
+            .build()?;
+        let t = Transform::register_translation_with(j.view(), k.view(), &plain)?;
+        let u = Transform::register_translation_with(j.view(), k.view(), &smooth)?;
+        assert!(error(&t) > 1.0);
+        assert!(error(&u) < 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn pixel_type_newtype() -> Result<()> {
+        #[derive(Clone, Debug, PartialEq)]
+        #[repr(transparent)]
+        struct Celsius(f32);
 
-        let scalex = 3.0 / imgx as f32;
-        let scaley = 3.0 / imgy as f32;
+        unsafe impl PixelType for Celsius {
+            const PT: u8 = 9;
+        }
 
-        let mut im = Array2::<u8>::zeros((imgy, imgx));
-        for x in 0..imgx {
-            for y in 0..imgy {
-                let cy = (y as f32 + shift_y) * scalex - 1.5;
-                let cx = (x as f32 + shift_x) * scaley - 1.5;
+        let j = julia_image(0f32, 0f32)?.mapv(|x| x as f32);
+        let c = j.mapv(Celsius);
+        let t = Transform::new(
+            [1.2, 0.3, -0.4, 0.9, 10.2, -9.5],
+            [399.5, 299.5],
+            [600, 800],
+        );
+        for interpolator in [
+            Interpolator::BSpline { order: 3 },
+            Interpolator::NearestNeighbor,
+        ] {
+            assert_eq!(
+                t.transform_image(c.view(), interpolator)?,
+                t.transform_image(j.view(), interpolator)?.mapv(Celsius)
+            );
+        }
+        Ok(())
+    }
 
-                let c = Complex::new(-0.4, 0.6);
-                let mut z = Complex::new(cy, cx);
+    #[test]
+    fn from_landmarks() -> Result<()> {
+        let t = Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [0.0; 2], [0; 2]);
+        let src = array![[0.0, 0.0], [10.0, 0.0], [0.0, 10.0]];
+        let dst = t.transform_coordinates(&src)?;
+        let u = Transform::from_landmarks(src.view(), dst.view())?;
+        assert!(
+            u.parameters
+                .iter()
+                .zip(t.parameters)
+                .all(|(a, b)| (a - b).abs() < 1e-9)
+        );
 
-                let mut i = 0;
-                while i < 255 && z.norm() <= 2.0 {
-                    z = z * z + c;
-                    i += 1;
-                }
+        // with noise, the fit is better than the true transform
+        let src = array![
+            [0.0, 0.0],
+            [100.0, 0.0],
+            [0.0, 100.0],
+            [100.0, 100.0],
+            [50.0, 30.0],
+            [20.0, 80.0]
+        ];
+        let noise = array![
+            [0.3, -0.2],
+            [-0.1, 0.4],
+            [0.2, 0.1],
+            [-0.4, -0.3],
+            [0.1, 0.2],
+            [0.0, -0.3]
+        ];
+        let dst = t.transform_coordinates(&src)? + &noise;
+        let u = Transform::from_landmarks(src.view(), dst.view())?;
+        let residual = |t: &Transform| -> Result<f64> {
+            Ok((t.transform_coordinates(&src)? - &dst).powi(2).sum())
+        };
+        assert!(residual(&u)? < residual(&t)?);
+        assert!(
+            u.parameters
+                .iter()
+                .zip(t.parameters)
+                .all(|(a, b)| (a - b).abs() < 0.5)
+        );
 
-                im[[y, x]] = i as u8;
-            }
+        let error =
+            Transform::from_landmarks(src.slice(s![..2, ..]), dst.slice(s![..2, ..])).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TransformError>(),
+            Some(&TransformError::InsufficientLandmarks {
+                required: 3,
+                found: 2
+            })
+        );
+        assert!(Transform::from_landmarks(src.view(), dst.slice(s![..3, ..])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn registration_config_default() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(6f32, -4f32)?;
+        let config = RegistrationConfig::builder().build()?;
+        assert_eq!(config, RegistrationConfig::default());
+        let t = Transform::register(j.view(), k.view(), RegistrationMode::Affine, &config)?;
+        let u = Transform::register_affine(j.view(), k.view())?;
+        assert_eq!(t, u);
+
+        let config = RegistrationConfig::builder()
+            .metric(Metric::MeanSquares)
+            .optimizer(Optimizer::RegularStepGradientDescent)
+            .max_iterations(200)
+            .build()?;
+        let t = Transform::register(j.view(), k.view(), RegistrationMode::Translation, &config)?;
+        assert!((t.parameters[4] + 6f64).abs() < 0.1);
+        assert!((t.parameters[5] - 4f64).abs() < 0.1);
+        Ok(())
+    }
+
+    #[test]
+    fn registration_histogram_match() -> Result<()> {
+        // a dimmer copy, like a photobleached frame
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(5f32, 8f32)?.mapv(|v| (v as f64 * 0.4).round() as u8);
+        let options = RegistrationOptions::builder()
+            .metric(Metric::MeanSquares)
+            .histogram_match(true)
+            .build()?;
+        let t = Transform::register_translation_with(j.view(), k.view(), &options)?;
+        assert!((t.parameters[4] + 5.0).abs() < 0.2);
+        assert!((t.parameters[5] + 8.0).abs() < 0.2);
+
+        // the full range of a wider integer type does not overflow
+        let j = j.mapv(|v| v as u16 * 257);
+        let k = k.mapv(|v| v as u16 * 257);
+        let t = Transform::register_translation_with(j.view(), k.view(), &options)?;
+        assert!((t.parameters[4] + 5.0).abs() < 0.2);
+        assert!((t.parameters[5] + 8.0).abs() < 0.2);
+        Ok(())
+    }
+
+    #[test]
+    fn from_translation_landmarks() {
+        let src = [[10.0, 20.0], [-5.0, 3.0]];
+        let t = Transform::from_translation_landmarks(src, [[13.0, 16.0], [-2.0, -1.0]]);
+        assert_eq!(t, Transform::from_translation([3.0, -4.0]));
+
+        let dst = [[12.0, 21.0], [-1.0, 3.5]];
+        let t = Transform::from_translation_landmarks(src, dst);
+        assert_eq!(t, Transform::from_translation([3.0, 0.75]));
+        let residual = |t: &Transform| {
+            src.iter()
+                .zip(dst)
+                .map(|(s, d)| {
+                    let p = t.apply_to_point(s[0], s[1]);
+                    (p[0] - d[0]).powi(2) + (p[1] - d[1]).powi(2)
+                })
+                .sum::<f64>()
+        };
+        for [dx, dy] in [[0.1, 0.0], [-0.1, 0.0], [0.0, 0.1], [0.0, -0.1]] {
+            let u = Transform::from_translation([3.0 + dx, 0.75 + dy]);
+            assert!(residual(&t) < residual(&u));
         }
-        Ok(im)
     }
 
     #[test]
-    fn test_serialization() -> Result<()> {
-        let file = NamedTempFile::new()?;
-        let t = Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [59.5, 49.5], [120, 100]);
-        t.to_file(file.path().to_path_buf())?;
-        let s = Transform::from_file(file.path().to_path_buf())?;
-        assert_eq!(s, t);
+    fn registration_estimate_scales() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let shape = j.shape();
+        let center = [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ];
+        let (sin, cos) = 4f64.to_radians().sin_cos();
+        let s = Transform::new(
+            [cos, -sin, sin, cos, 6.0, -4.0],
+            center,
+            [shape[0], shape[1]],
+        );
+        let k = s.transform_image_bspline(j.view())?;
+        let interior = s![100..500, 100..700];
+        let error = |scales: ScalesFrom| -> Result<f64> {
+            // without an optimizer, estimate_scales alone selects the sitk registration method
+            let config = RegistrationConfig::builder()
+                .estimate_scales(scales)
+                .build()?;
+            let t = Transform::register(j.view(), k.view(), RegistrationMode::Affine, &config)?;
+            rmse(
+                j.slice(interior),
+                t.transform_image_bspline(k.view())?.slice(interior),
+            )
+        };
+        let physical = error(ScalesFrom::PhysicalShift)?;
+        assert!(physical < error(ScalesFrom::None)?);
+        assert!(physical < 0.5 * rmse(j.slice(interior), k.slice(interior))?);
         Ok(())
     }
 
-    macro_rules! interp_tests_bspline {
-        ($($name:ident: $t:ty $(,)?)*) => {
-            $(
-                #[test]
-                fn $name() -> Result<()> {
-                    let j = julia_image(-120f32, 10f32)?.mapv(|x| x as $t);
-                    let k = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
-                    let shape = j.shape();
-                    let origin = [
-                        ((shape[1] - 1) as f64) / 2f64,
-                        ((shape[0] - 1) as f64) / 2f64,
-                    ];
-                    let transform = Transform::new([1., 0., 0., 1., 120., -10.], origin, [shape[0], shape[1]]);
-                    let n = transform.transform_image_bspline(j.view())?;
-                    let d = (k.mapv(|x| x as f64) - n.mapv(|x| x as f64)).powi(2).sum();
-                    assert!(d <= (shape[0] * shape[1]) as f64);
-                    Ok(())
-                }
-            )*
+    #[test]
+    fn registration_normalization() -> Result<()> {
+        // the same image with a large offset in intensity
+        let j = julia_image(0f32, 0f32)?.mapv(|v| v as f32);
+        let k = julia_image(5f32, 8f32)?.mapv(|v| v as f32 + 1000.0);
+        for normalization in [Normalization::ZScore, Normalization::MinMax] {
+            let options = RegistrationOptions::builder()
+                .metric(Metric::MeanSquares)
+                .normalization(normalization)
+                .build()?;
+            let t = Transform::register_translation_with(j.view(), k.view(), &options)?;
+            assert!((t.parameters[4] + 5.0).abs() < 0.2);
+            assert!((t.parameters[5] + 8.0).abs() < 0.2);
         }
+
+        let c = Array2::<f32>::from_elem(j.dim(), 100.0);
+        let options = RegistrationOptions::builder()
+            .normalization(Normalization::ZScore)
+            .build()?;
+        let error = Transform::register_translation_with(j.view(), c.view(), &options).unwrap_err();
+        assert!(error.to_string().contains("constant intensity"));
+        Ok(())
     }
 
-    interp_tests_bspline! {
-        interpbs_u8: u8,
-        interpbs_i8: i8,
-        interpbs_u16: u16,
-        interpbs_i16: i16,
-        interpbs_u32: u32,
-        interpbs_i32: i32,
-        interpbs_u64: u64,
-        interpbs_i64: i64,
-        interpbs_f32: f32,
-        interpbs_f64: f64,
+    #[test]
+    fn residual_at_landmarks() -> Result<()> {
+        let src = array![[0.0, 0.0], [10.0, 0.0], [0.0, 10.0], [10.0, 10.0]];
+        let t = Transform::new([1.1, 0.2, -0.1, 0.9, 3.0, -2.0], [0.0; 2], [0; 2]);
+        let dst = t.transform_coordinates(&src)?;
+        let u = Transform::from_landmarks(src.view(), dst.view())?;
+        assert!(u.residual_at_landmarks(src.view(), dst.view())? < 1e-9);
+        assert!(u.max_residual_at_landmarks(src.view(), dst.view())? < 1e-9);
+
+        let mut perturbed = dst.clone();
+        perturbed[[2, 0]] += 3.0;
+        perturbed[[2, 1]] += 4.0;
+        assert!((t.residual_at_landmarks(src.view(), perturbed.view())? - 2.5).abs() < 1e-9);
+        assert!((t.max_residual_at_landmarks(src.view(), perturbed.view())? - 5.0).abs() < 1e-9);
+        assert!(
+            t.residual_at_landmarks(src.view(), dst.slice(s![..3, ..]))
+                .is_err()
+        );
+        Ok(())
     }
 
-    macro_rules! interp_tests_nearest_neighbor {
-        ($($name:ident: $t:ty $(,)?)*) => {
-            $(
-                #[test]
-                fn $name() -> Result<()> {
-                    let j = julia_image(-120f32, 10f32)?.mapv(|x| x as $t);
-                    let k = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
-                    let shape = j.shape();
-                    let origin = [
-                        ((shape[1] - 1) as f64) / 2f64,
-                        ((shape[0] - 1) as f64) / 2f64,
-                    ];
-                    let j0 = j.clone();
-                    let k0 = k.clone();
-                    let transform = Transform::new([1., 0., 0., 1., 120., -10.], origin, [shape[0], shape[1]]);
-                    // make sure j & k weren't mutated
-                    assert!(j.iter().zip(j0.iter()).map(|(a, b)| a == b).all(|x| x));
-                    assert!(k.iter().zip(k0.iter()).map(|(a, b)| a == b).all(|x| x));
-                    let n = transform.transform_image_nearest_neighbor(j.view())?;
-                    let d = (k.mapv(|x| x as f64) - n.mapv(|x| x as f64)).powi(2).sum();
-                    assert!(d <= (shape[0] * shape[1]) as f64);
-                    Ok(())
-                }
-            )*
+    #[test]
+    fn transform_points_with_error() -> Result<()> {
+        let mut t = Transform::new([1.1, 0.2, -0.1, 0.9, 3.0, -2.0], [0.0; 2], [0; 2]);
+        let points = array![[0.0, 0.0], [3.0, 4.0], [-10.0, 2.0]];
+        let (p, e) = t.transform_points_with_error(&points)?;
+        assert_eq!(p, t.transform_coordinates(&points)?);
+        assert!(e.iter().all(|x| *x == 0.0));
+
+        t.dparameters = [0.01, 0.02, 0.03, 0.04, 0.5, 0.6];
+        let (q, e) = t.transform_points_with_error(&points)?;
+        assert_eq!(q, p);
+        assert_eq!(e.row(0), array![0.5, 0.6]);
+        assert!((e[[1, 0]] - (0.03f64.powi(2) + 0.08f64.powi(2) + 0.25).sqrt()).abs() < 1e-12);
+        assert!((e[[1, 1]] - (0.09f64.powi(2) + 0.16f64.powi(2) + 0.36).sqrt()).abs() < 1e-12);
+
+        // the errors scale with the errors of the parameters
+        t.dparameters = t.dparameters.map(|d| 3.0 * d);
+        let (_, f) = t.transform_points_with_error(&points)?;
+        assert!((&f - &(3.0 * &e)).iter().all(|x| x.abs() < 1e-12));
+        assert!(
+            t.transform_points_with_error(&array![[1.0, 2.0, 3.0]])
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_with_result() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let result = Transform::register_affine_with_result(j.view(), k.view())?;
+        assert!(result.final_metric.is_finite());
+        assert!(!result.stop_condition.is_empty());
+        assert!(result.elapsed > Duration::ZERO);
+        assert_eq!(result.iterations as usize, result.metric_history.len());
+        // elastix does not report iterations, the sitk registration method does
+        assert_eq!(result.iterations > 0, cfg!(not(feature = "elastix")));
+        let t = Transform::register_affine(j.view(), k.view())?;
+        assert!(
+            result
+                .transform
+                .parameters
+                .iter()
+                .zip(t.parameters)
+                .all(|(a, b)| (a - b).abs() < 1e-9)
+        );
+        assert_eq!(result.transform.origin, t.origin);
+
+        let config = RegistrationConfig::builder()
+            .optimizer(Optimizer::RegularStepGradientDescent)
+            .build()?;
+        let result = Transform::register_with_result(
+            j.view(),
+            k.view(),
+            RegistrationMode::Translation,
+            &config,
+        )?;
+        let t = Transform::register(j.view(), k.view(), RegistrationMode::Translation, &config)?;
+        assert_eq!(result.transform.parameters, t.parameters);
+        assert_eq!(result.transform.origin, t.origin);
+        Ok(())
+    }
+
+    #[test]
+    fn mul() -> Result<()> {
+        let mut t = Transform::new([1.1, 0.2, -0.3, 0.9, 3.5, -2.25], [10.0, 20.0], [100, 100]);
+        t.dparameters = [0.01, 0.02, 0.03, 0.04, 0.1, 0.2];
+        let u = Transform::new([0.8, -0.5, 0.4, 1.2, -1.0, 4.0], [5.0, 5.0], [50, 50]);
+        let points = array![[1.0, 0.0], [0.0, 1.0], [2.0, -3.0]];
+        let product = t.clone() * u.clone();
+        let a = product.transform_coordinates(&points)?;
+        let b = t.transform_coordinates(&u.transform_coordinates(&points)?)?;
+        assert!((a - b).iter().all(|x| x.abs() < 1e-12));
+        assert_eq!(product.origin, t.origin);
+        assert_eq!(product.shape, t.shape);
+        assert_eq!(&t * &u, product);
+        assert_eq!(t.clone() * &u, product);
+        assert_eq!(&t * u.clone(), product);
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn transform_image_bspline_async() -> Result<()> {
+        let j: &'static Array2<u8> = Box::leak(Box::new(julia_image(0f32, 0f32)?));
+        let t = Transform::new([1.1, 0.1, -0.05, 0.95, 3., -4.], [400., 300.], [600, 800]);
+        let k = t.transform_image_bspline_async(j.view()).await?;
+        assert_eq!(k, t.transform_image_bspline(j.view())?);
+        Ok(())
+    }
+
+    #[test]
+    fn transform_image_layout() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let t = Transform::new([1.1, 0.1, -0.05, 0.95, 3., -4.], [400., 300.], [600, 800]);
+        // the same image, stored in fortran order
+        let jt = j.t().as_standard_layout().to_owned();
+        let f = jt.t();
+        assert!(!f.is_standard_layout());
+        for interpolator in [
+            Interpolator::BSpline { order: 3 },
+            Interpolator::NearestNeighbor,
+        ] {
+            let a = t.transform_image(f, interpolator)?;
+            assert!(a.is_standard_layout());
+            assert_eq!(a, t.transform_image(j.view(), interpolator)?);
         }
+        // a transposed view is a different image
+        let a = t.transform_image(j.t(), Interpolator::BSpline { order: 3 })?;
+        assert!(a.is_standard_layout());
+        assert_eq!(
+            a,
+            t.transform_image(
+                &j.t().as_standard_layout(),
+                Interpolator::BSpline { order: 3 }
+            )?
+        );
+        Ok(())
     }
 
-    interp_tests_nearest_neighbor! {
-        interpnn_u8: u8,
-        interpnn_i8: i8,
-        interpnn_u16: u16,
-        interpnn_i16: i16,
-        interpnn_u32: u32,
-        interpnn_i32: i32,
-        interpnn_u64: u64,
-        interpnn_i64: i64,
-        interpnn_f32: f32,
-        interpnn_f64: f64,
+    #[test]
+    fn registration_metric_history() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(4f32, -3f32)?;
+        // elastix has no metric history, an optimizer selects the sitk registration method
+        let config = RegistrationConfig::builder()
+            .pyramid_levels(1)
+            .optimizer(Optimizer::RegularStepGradientDescent)
+            .build()?;
+        let result = Transform::register_with_result(
+            j.view(),
+            k.view(),
+            RegistrationMode::Translation,
+            &config,
+        )?;
+        let history = &result.metric_history;
+        assert_eq!(history.len(), result.iterations as usize);
+        assert!(history.len() > 4);
+        // mostly decreasing, the optimizer can overshoot now and then
+        let quarter = history.len() / 4;
+        let mean = |h: &[f64]| h.iter().sum::<f64>() / h.len() as f64;
+        assert!(mean(&history[history.len() - quarter..]) < mean(&history[..quarter]));
+        let decreasing = history.windows(2).filter(|w| w[1] <= w[0]).count();
+        assert!(2 * decreasing >= history.len() - 1);
+        Ok(())
     }
 
-    macro_rules! registration_tests_translation {
-        ($($name:ident: $t:ty $(,)?)*) => {
-            $(
-                #[test]
-                fn $name() -> Result<()> {
-                    let j = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
-                    let k = julia_image(10f32, 20f32)?.mapv(|x| x as $t);
-                    let j0 = j.clone();
-                    let k0 = k.clone();
-                    let t = Transform::register_translation(j.view(), k.view())?;
-                    // make sure j & k weren't mutated
-                    assert!(j.iter().zip(j0.iter()).map(|(a, b)| a == b).all(|x| x));
-                    assert!(k.iter().zip(k0.iter()).map(|(a, b)| a == b).all(|x| x));
-                    let mut m = Array2::eye(3);
-                    m[[0, 2]] = -10f64;
-                    m[[1, 2]] = -20f64;
-                    let d = (t.matrix() - m).powi(2).sum();
-                    assert!(d < 0.01);
-                    Ok(())
-                }
-            )*
+    #[test]
+    fn transform_image_identity() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let t = Transform::new([1., 0., 0., 1., 0., 0.], [400., 300.], [600, 800]);
+        assert!(t.is_unity());
+        assert_eq!(t.transform_image_bspline(j.view())?, j);
+        let n = t.transform_image_nearest_neighbor(j.t())?;
+        assert_eq!(n, j.t());
+        assert!(n.is_standard_layout());
+        assert_eq!(
+            t.apply_to_image_roi(
+                j.view(),
+                [10, 20, 110, 220],
+                Interpolator::BSpline { order: 3 }
+            )?,
+            j.slice(s![10..110, 20..220])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_time_limit() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        // the sitk registration method without time limit, to compare the iterations
+        let config = RegistrationConfig::builder()
+            .optimizer(Optimizer::RegularStepGradientDescent)
+            .build()?;
+        let free =
+            Transform::register_with_result(j.view(), k.view(), RegistrationMode::Affine, &config)?;
+        let config = RegistrationConfig::builder()
+            .time_limit(Duration::from_millis(1))
+            .build()?;
+        let limited =
+            Transform::register_with_result(j.view(), k.view(), RegistrationMode::Affine, &config)?;
+        assert!(limited.stop_condition.starts_with("time limit"));
+        assert!(limited.iterations < free.iterations);
+        assert!(limited.elapsed < Duration::from_secs(10));
+        assert!(limited.transform.parameters.iter().all(|p| p.is_finite()));
+        assert!(!free.stop_condition.starts_with("time limit"));
+        Ok(())
+    }
+
+    #[test]
+    fn registration_progress() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let c = calls.clone();
+        let config = RegistrationConfig::builder()
+            .progress(move |iteration, metric| c.lock().unwrap().push((iteration, metric)))
+            .build()?;
+        let result = Transform::register_with_result(
+            j.view(),
+            k.view(),
+            RegistrationMode::Translation,
+            &config,
+        )?;
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), result.iterations as usize);
+        assert!(
+            calls
+                .iter()
+                .enumerate()
+                .all(|(i, (iteration, _))| *iteration == i as u32)
+        );
+        assert_eq!(
+            calls.iter().map(|(_, metric)| *metric).collect::<Vec<_>>(),
+            result.metric_history
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_progress_panic() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let config = RegistrationConfig::builder()
+            .progress(|_, _| panic!("stop"))
+            .build()?;
+        let result = std::panic::catch_unwind(|| {
+            Transform::register(j.view(), k.view(), RegistrationMode::Translation, &config)
+        });
+        assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"stop"));
+        // the panic did not leave the adapter locked
+        let t = Transform::register(
+            j.view(),
+            k.view(),
+            RegistrationMode::Translation,
+            &RegistrationConfig::default(),
+        )?;
+        assert!((t.parameters[4] + 10f64).abs() < 0.5);
+        assert!((t.parameters[5] + 20f64).abs() < 0.5);
+        let config = RegistrationConfig::builder().progress(|_, _| {}).build()?;
+        assert!(
+            Transform::register(j.view(), k.view(), RegistrationMode::Translation, &config).is_ok()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn transform_image_bspline_with_mse() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let transform = Transform::new(
+            [1f64, 0f64, 0f64, 1f64, -10f64, -20f64],
+            [400f64, 300f64],
+            [600, 800],
+        );
+        let (warped, mse) = transform.apply_to_image_bspline_with_mse(k.view(), j.view())?;
+        assert_eq!(warped, transform.transform_image_bspline(k.view())?);
+        let expected = rmse(
+            warped.mapv(|x| x as f64).view(),
+            j.mapv(|x| x as f64).view(),
+        )?
+        .powi(2);
+        assert!((mse - expected).abs() < 1e-9 * expected.max(1f64));
+        assert!(
+            transform
+                .apply_to_image_bspline_with_mse(k.view(), j.slice(s![1.., ..]))
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rescale_for_spacing() {
+        let t = Transform::new(
+            [1f64, 0f64, 0f64, 1f64, 10f64, 20f64],
+            [399.5, 299.5],
+            [600, 800],
+        );
+        let u = t.rescale_for_spacing(1f64, 0.5);
+        assert_eq!(u.parameters, [1f64, 0f64, 0f64, 1f64, 5f64, 10f64]);
+        assert_eq!(u.origin, [199.5, 149.5]);
+        assert_eq!(u.shape, [300, 400]);
+        // the errors of the translation are scaled like the translation
+        let mut t = t;
+        t.dparameters = [0.1, 0.2, 0.3, 0.4, 2f64, 4f64];
+        let u = t.rescaled(2f64);
+        assert_eq!(u.dparameters, [0.1, 0.2, 0.3, 0.4, 4f64, 8f64]);
+        // in physical units only the spacing and the origin change
+        t.spacing = [0.5, 2f64];
+        t.direction = [0f64, -1f64, 1f64, 0f64];
+        let u = t.rescaled(2f64);
+        assert_eq!(u.parameters, t.parameters);
+        assert_eq!(u.dparameters, t.dparameters);
+        assert_eq!(u.direction, t.direction);
+        assert_eq!(u.spacing, [0.25, 1f64]);
+        assert_eq!(u.shape, [1200, 1600]);
+        assert_eq!(u.origin, [399.5 - 0.5, 299.5 + 0.125]);
+    }
+
+    #[test]
+    fn registration_cancel() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let flag = Arc::new(AtomicBool::new(false));
+        let f = flag.clone();
+        let iterations = Arc::new(AtomicU32::new(0));
+        let i = iterations.clone();
+        // a small tolerance, so that it does not converge before the flag is set
+        let config = RegistrationConfig::builder()
+            .convergence_tolerance(1e-12)
+            .max_iterations(100000)
+            .progress(move |_, _| {
+                i.fetch_add(1, Ordering::Relaxed);
+            })
+            .cancel(flag.clone())
+            .build()?;
+        let setter = std::thread::spawn(move || {
+            while iterations.load(Ordering::Relaxed) < 3 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            f.store(true, Ordering::Relaxed);
+        });
+        let start = Instant::now();
+        let error =
+            Transform::register(j.view(), k.view(), RegistrationMode::Affine, &config).unwrap_err();
+        setter.join().unwrap();
+        assert_eq!(
+            error.downcast_ref::<TransformError>(),
+            Some(&TransformError::Cancelled)
+        );
+        assert!(start.elapsed() < Duration::from_secs(60));
+        // an already cancelled registration does not start
+        let error =
+            Transform::register(j.view(), k.view(), RegistrationMode::Affine, &config).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TransformError>(),
+            Some(&TransformError::Cancelled)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_config_roi() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let full = Transform::register_with_result(
+            j.view(),
+            k.view(),
+            RegistrationMode::Translation,
+            &RegistrationConfig::default(),
+        )?;
+        let config = RegistrationConfig::builder()
+            .roi([150, 250], [300, 300])
+            .build()?;
+        let roi = Transform::register_with_result(
+            j.view(),
+            k.view(),
+            RegistrationMode::Translation,
+            &config,
+        )?;
+        assert_eq!(roi.transform.shape, [600, 800]);
+        assert_eq!(roi.transform.origin, [399.5, 299.5]);
+        for i in [4, 5] {
+            assert!((roi.transform.parameters[i] - full.transform.parameters[i]).abs() < 0.5);
         }
+        // only the roi is registered
+        let (fixed, moving, offset, shape) = crop_to_roi(j.view(), k.view(), &config)?;
+        assert_eq!(fixed, j.slice(s![150..450, 250..550]));
+        assert_eq!(moving, k.slice(s![150..450, 250..550]));
+        assert_eq!(offset, [250.0, 150.0]);
+        assert_eq!(shape, [600, 800]);
+        let t = Transform::register(j.view(), k.view(), RegistrationMode::Translation, &config)?;
+        assert_eq!(t.origin, [399.5, 299.5]);
+        assert!((t.parameters[4] + 10f64).abs() < 0.5);
+        assert!((t.parameters[5] + 20f64).abs() < 0.5);
+        let config = RegistrationConfig::builder()
+            .roi([400, 250], [300, 300])
+            .build()?;
+        assert!(
+            Transform::register(j.view(), k.view(), RegistrationMode::Translation, &config)
+                .is_err()
+        );
+        Ok(())
     }
 
-    registration_tests_translation! {
-        registration_translation_u8: u8,
-        registration_translation_i8: i8,
-        registration_translation_u16: u16,
-        registration_translation_i16: i16,
-        registration_translation_u32: u32,
-        registration_translation_i32: i32,
-        registration_translation_u64: u64,
-        registration_translation_i64: i64,
-        registration_translation_f32: f32,
-        registration_translation_f64: f64,
+    #[test]
+    fn to_parameters_vec() {
+        let t = Transform::new([1.1, 0.1, -0.05, 0.95, 3., -4.], [400., 300.], [600, 800]);
+        let m = Array2::from(&t);
+        assert_eq!(m, t.matrix());
+        // itk: the matrix row-major, m[row, column], then the translation
+        let itk = vec![
+            m[[0, 0]],
+            m[[0, 1]],
+            m[[1, 0]],
+            m[[1, 1]],
+            m[[0, 2]],
+            m[[1, 2]],
+        ];
+        assert_eq!(t.to_parameters_vec(), itk);
+        assert_eq!(t.to_parameters_vec(), vec![1.1, 0.1, -0.05, 0.95, 3., -4.]);
+        assert_eq!(m.row(2).to_vec(), vec![0., 0., 1.]);
     }
 
-    macro_rules! registration_tests_affine {
-        ($($name:ident: $t:ty $(,)?)*) => {
-            $(
-                #[test]
-                fn $name() -> Result<()> {
-                    let j = julia_image(0f32, 0f32)?.mapv(|x| x as $t);
-                    let shape = j.shape();
-                    let origin = [
-                        ((shape[1] - 1) as f64) / 2f64,
-                        ((shape[0] - 1) as f64) / 2f64,
-                    ];
-                    let s = Transform::new([1.2, 0., 0., 1., 5., 7.], origin, [shape[0], shape[1]]);
-                    let k = s.transform_image_bspline(j.view())?;
-                    let t = Transform::register_affine(j.view(), k.view())?.inverse()?;
-                    let d = (t.matrix() - s.matrix()).powi(2).sum();
-                    assert!(d < 0.01);
-                    Ok(())
-                }
-            )*
+    #[test]
+    fn compose_many() {
+        let a = Transform::new([1.1, 0.1, -0.05, 0.95, 3., -4.], [400., 300.], [600, 800]);
+        let b = Transform::rotate_about(0.3, 10., 20.);
+        let c = Transform::from_translation([5., -7.]);
+        assert_eq!(Transform::compose_many(&[]), None);
+        assert_eq!(
+            Transform::compose_many(std::slice::from_ref(&a)),
+            Some(a.clone())
+        );
+        assert_eq!(
+            Transform::compose_many(&[a.clone(), b.clone(), c.clone()]),
+            Some(&c * &(&b * &a))
+        );
+    }
+
+    #[test]
+    fn apply_to_rect() {
+        let close = |a: [[f64; 2]; 4], b: [[f64; 2]; 4]| {
+            a.iter()
+                .flatten()
+                .zip(b.iter().flatten())
+                .all(|(p, q)| (p - q).abs() < 1e-12)
+        };
+        let t = Transform::from_translation([5.0, -3.0]);
+        assert_eq!(
+            t.apply_to_rect([10.0, 20.0], [30.0, 60.0]),
+            [[15.0, 17.0], [35.0, 17.0], [35.0, 57.0], [15.0, 57.0]]
+        );
+        assert_eq!(
+            t.bounding_box_of_transformed_rect([10.0, 20.0], [30.0, 60.0]),
+            ([15.0, 17.0], [35.0, 57.0])
+        );
+        // 90 degrees about the origin: (x, y) -> (-y, x)
+        let r = Transform::rotate_about(std::f64::consts::FRAC_PI_2, 0.0, 0.0);
+        assert!(close(
+            r.apply_to_rect([10.0, 20.0], [30.0, 60.0]),
+            [[-20.0, 10.0], [-20.0, 30.0], [-60.0, 30.0], [-60.0, 10.0]]
+        ));
+        let (min, max) = r.bounding_box_of_transformed_rect([10.0, 20.0], [30.0, 60.0]);
+        assert!(close(
+            [min, max, [0.0; 2], [0.0; 2]],
+            [[-60.0, 10.0], [-20.0, 30.0], [0.0; 2], [0.0; 2]]
+        ));
+    }
+
+    #[test]
+    fn registration_timeout() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let config = RegistrationConfig::builder()
+            .timeout(Duration::from_millis(1))
+            .roi([100, 200], [400, 400])
+            .build()?;
+        let error =
+            Transform::register(j.view(), k.view(), RegistrationMode::Affine, &config).unwrap_err();
+        match error.downcast_ref::<TransformError>() {
+            Some(TransformError::TimedOut { elapsed, transform }) => {
+                assert!(*elapsed >= Duration::from_millis(1));
+                let transform = transform.as_ref().unwrap();
+                assert_eq!(transform.shape, [600, 800]);
+                assert_eq!(transform.origin, [399.5, 299.5]);
+            }
+            _ => panic!("expected TransformError::TimedOut, got {:?}", error),
         }
+        // the timeout replaces the time limit
+        let config = RegistrationConfig::builder()
+            .time_limit(Duration::from_secs(600))
+            .timeout(Duration::from_millis(1))
+            .build()?;
+        let error =
+            Transform::register(j.view(), k.view(), RegistrationMode::Affine, &config).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TransformError>(),
+            Some(TransformError::TimedOut { .. })
+        ));
+        let config = RegistrationConfig::builder()
+            .timeout(Duration::from_secs(600))
+            .build()?;
+        let t = Transform::register(j.view(), k.view(), RegistrationMode::Translation, &config)?;
+        assert!((t.parameters[4] + 10f64).abs() < 0.5);
+        assert!((t.parameters[5] + 20f64).abs() < 0.5);
+        Ok(())
     }
 
-    registration_tests_affine! {
-        registration_tests_affine_u8: u8,
-        registration_tests_affine_i8: i8,
-        registration_tests_affine_u16: u16,
-        registration_tests_affine_i16: i16,
-        registration_tests_affine_u32: u32,
-        registration_tests_affine_i32: i32,
-        registration_tests_affine_u64: u64,
-        registration_tests_affine_i64: i64,
-        registration_tests_affine_f32: f32,
-        registration_tests_affine_f64: f64,
+    #[test]
+    fn registration_affine_concurrent_batch() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let k = julia_image(10f32, 20f32)?;
+        let l = julia_image(-5f32, 8f32)?;
+        let c = Array2::<u8>::from_elem(j.dim(), 100);
+        let pairs = [
+            (j.view(), k.view()),
+            (j.view(), c.view()),
+            (j.view(), l.view()),
+        ];
+        let transforms = Transform::register_affine_concurrent_batch(&pairs);
+        assert_eq!(transforms.len(), 3);
+        assert!(transforms[1].is_err());
+        assert!(Transform::register_affine_concurrent_batch::<u8>(&[]).is_empty());
+        for (transform, [x, y]) in [
+            (&transforms[0], [10f64, 20f64]),
+            (&transforms[2], [-5f64, 8f64]),
+        ] {
+            let transform = transform.as_ref().unwrap();
+            assert!((transform.parameters[4] + x).abs() < 0.5);
+            assert!((transform.parameters[5] + y).abs() < 0.5);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn transform_image_bspline_order() -> Result<()> {
+        let step = Array2::from_shape_fn((64, 64), |(_, x)| if x < 32 { 0f64 } else { 200f64 });
+        let t = Transform::from_translation([0.5, 0.0]);
+        let interior = s![8..56, 8..56];
+        let linear = t.transform_image(step.view(), Interpolator::BSpline { order: 1 })?;
+        let quintic = t.transform_image(step.view(), Interpolator::BSpline { order: 5 })?;
+        let range = |image: &Array2<f64>| {
+            image
+                .slice(interior)
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &x| {
+                    (min.min(x), max.max(x))
+                })
+        };
+        // linear interpolation stays within the step, higher orders ring around it
+        let (min, max) = range(&linear);
+        assert!(min >= -1e-9 && max <= 200f64 + 1e-9);
+        let (min, max) = range(&quintic);
+        assert!(min < -1f64 && max > 201f64);
+        assert_eq!(
+            t.transform_image(step.view(), Interpolator::BSpline { order: 3 })?,
+            t.transform_image_bspline(step.view())?
+        );
+        assert!(
+            t.transform_image(step.view(), Interpolator::BSpline { order: 6 })
+                .is_err()
+        );
+        Ok(())
     }
 }
@@ -0,0 +1,694 @@
+use anyhow::{Result, anyhow};
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// the metric describing how well the transformed moving image matches the fixed image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// mean squared difference, for images with the same intensities
+    MeanSquares = 1,
+    /// normalized correlation, for images with linearly related intensities
+    NormalizedCorrelation = 2,
+    /// mutual information, for images with any relation between the intensities, such as images
+    /// of different modalities
+    MutualInformation = 3,
+}
+
+/// the optimizer of the sitk registration method
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Optimizer {
+    /// gradient descent, with the step halved each time the direction of the gradient reverses
+    RegularStepGradientDescent = 1,
+    /// gradient descent, with a line search along the gradient for the step size
+    GradientDescentLineSearch = 2,
+    /// conjugate gradient descent with a line search for the step size
+    ConjugateGradientLineSearch = 3,
+}
+
+/// normalization of the intensities of each image before registration, in floating point, the
+/// transform found applies to the images without normalization
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Normalization {
+    #[default]
+    None = 0,
+    /// subtract the mean and divide by the standard deviation
+    ZScore = 1,
+    /// rescale the intensities to [0, 1]
+    MinMax = 2,
+}
+
+/// how the sitk registration method estimates the scales of the parameters for the optimizer,
+/// which matter because a change in a parameter of the matrix moves pixels much further than the
+/// same change in the translation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalesFrom {
+    /// from the jacobian of the transform
+    Jacobian = 1,
+    /// from the shift of voxels in index space
+    IndexShift = 2,
+    /// from the shift of voxels in physical space
+    PhysicalShift = 3,
+    /// no estimation, all scales are 1
+    None = 4,
+}
+
+/// options for the optimizer used in registration, None means the default of the registration
+/// method, so RegistrationOptions::default() reproduces the results without options
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegistrationOptions {
+    /// maximum number of iterations of the optimizer at each resolution
+    pub max_iterations: Option<u32>,
+    /// number of resolutions, each level halves the resolution of the one above
+    pub pyramid_levels: Option<u32>,
+    /// fraction of the pixels used to evaluate the metric in each iteration
+    pub sampling_fraction: Option<f64>,
+    /// number of histogram bins of the mutual information metric, only used when the metric is
+    /// mutual information, fewer bins suit images with a low dynamic range or few pixels, the
+    /// default is 32 for registration with elastix, and 50 otherwise
+    pub mi_bins: Option<u32>,
+    /// mutual information if None
+    pub metric: Option<Metric>,
+    /// setting any optimizer switches registration from elastix to the sitk registration method,
+    /// also for methods such as Transform::register_affine_with that otherwise use elastix, so
+    /// the result can differ from the one without an optimizer, the default of elastix if None
+    pub optimizer: Option<Optimizer>,
+    /// seed of the random number generator used to sample pixels, with a seed the same images
+    /// always give the same result, only the lower 32 bits are used
+    pub seed: Option<u64>,
+    /// the optimizer stops when the change of the metric, or for regular step gradient descent
+    /// the magnitude of the gradient, drops below this, only used by the sitk registration
+    /// method, the default is 1e-4 for regular step gradient descent and 1e-6 otherwise
+    pub convergence_tolerance: Option<f64>,
+    /// regular step gradient descent stops when the step becomes smaller than this, only used by
+    /// the sitk registration method, default 0.01
+    pub min_step_length: Option<f64>,
+    /// the largest step of the optimizer, which is the first step of regular step gradient
+    /// descent, default 4 for the sitk registration method, and the size of a pixel for elastix
+    pub max_step_length: Option<f64>,
+    /// standard deviation in pixels of a gaussian blur applied to both images before
+    /// registration, to suppress noise, the transform found applies to the images without blur
+    pub smoothing_sigma: Option<f64>,
+    /// map the intensities of the moving image onto the histogram of the fixed image before
+    /// registration, for example to compensate photobleaching, the transform found applies to
+    /// the images without matching
+    pub histogram_match: bool,
+    /// number of histogram bins used for histogram matching, default 256
+    pub histogram_levels: Option<u32>,
+    /// how the optimizer scales are estimated, only the sitk registration method has this, so
+    /// setting it switches registration from elastix to the sitk registration method, like
+    /// optimizer, physical shift if None
+    pub estimate_scales: Option<ScalesFrom>,
+    /// normalize the intensities of both images before registration, which fails for images with
+    /// constant intensity
+    pub normalization: Normalization,
+    /// stop the optimizer when registration takes longer than this, the transform found so far is
+    /// returned, setting a time limit means the sitk registration method is used instead of elastix,
+    /// the timeout of RegistrationConfig replaces this when both are set
+    pub time_limit: Option<Duration>,
+}
+
+impl RegistrationOptions {
+    pub fn builder() -> RegistrationOptionsBuilder {
+        RegistrationOptionsBuilder::default()
+    }
+}
+
+/// build RegistrationOptions:
+///
+/// RegistrationOptions::builder().max_iterations(500).sampling_fraction(0.5).build()
+#[derive(Clone, Debug, Default)]
+pub struct RegistrationOptionsBuilder {
+    options: RegistrationOptions,
+}
+
+impl RegistrationOptionsBuilder {
+    pub fn max_iterations(mut self, max_iterations: u32) -> Self {
+        self.options.max_iterations = Some(max_iterations);
+        self
+    }
+
+    pub fn pyramid_levels(mut self, pyramid_levels: u32) -> Self {
+        self.options.pyramid_levels = Some(pyramid_levels);
+        self
+    }
+
+    /// between 0 (exclusive) and 1 (inclusive)
+    pub fn sampling_fraction(mut self, sampling_fraction: f64) -> Self {
+        self.options.sampling_fraction = Some(sampling_fraction);
+        self
+    }
+
+    pub fn mi_bins(mut self, mi_bins: u32) -> Self {
+        self.options.mi_bins = Some(mi_bins);
+        self
+    }
+
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.options.metric = Some(metric);
+        self
+    }
+
+    pub fn optimizer(mut self, optimizer: Optimizer) -> Self {
+        self.options.optimizer = Some(optimizer);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.options.seed = Some(seed);
+        self
+    }
+
+    pub fn convergence_tolerance(mut self, convergence_tolerance: f64) -> Self {
+        self.options.convergence_tolerance = Some(convergence_tolerance);
+        self
+    }
+
+    pub fn min_step_length(mut self, min_step_length: f64) -> Self {
+        self.options.min_step_length = Some(min_step_length);
+        self
+    }
+
+    pub fn max_step_length(mut self, max_step_length: f64) -> Self {
+        self.options.max_step_length = Some(max_step_length);
+        self
+    }
+
+    pub fn smoothing_sigma(mut self, smoothing_sigma: f64) -> Self {
+        self.options.smoothing_sigma = Some(smoothing_sigma);
+        self
+    }
+
+    pub fn histogram_match(mut self, histogram_match: bool) -> Self {
+        self.options.histogram_match = histogram_match;
+        self
+    }
+
+    pub fn histogram_levels(mut self, histogram_levels: u32) -> Self {
+        self.options.histogram_levels = Some(histogram_levels);
+        self
+    }
+
+    pub fn estimate_scales(mut self, estimate_scales: ScalesFrom) -> Self {
+        self.options.estimate_scales = Some(estimate_scales);
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.options.normalization = normalization;
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> Self {
+        self.options.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn build(self) -> Result<RegistrationOptions> {
+        if self.options.max_iterations == Some(0) {
+            return Err(anyhow!("max_iterations cannot be zero"));
+        }
+        if self.options.pyramid_levels == Some(0) {
+            return Err(anyhow!("pyramid_levels cannot be zero"));
+        }
+        if self.options.mi_bins == Some(0) {
+            return Err(anyhow!("mi_bins cannot be zero"));
+        }
+        if self.options.histogram_levels == Some(0) {
+            return Err(anyhow!("histogram_levels cannot be zero"));
+        }
+        if self.options.time_limit == Some(Duration::ZERO) {
+            return Err(anyhow!("time_limit cannot be zero"));
+        }
+        if let Some(fraction) = self.options.sampling_fraction {
+            if !(fraction > 0f64 && fraction <= 1f64) {
+                return Err(anyhow!(
+                    "sampling_fraction must be in (0, 1], got {}",
+                    fraction
+                ));
+            }
+        }
+        for (name, value) in [
+            ("convergence_tolerance", self.options.convergence_tolerance),
+            ("min_step_length", self.options.min_step_length),
+            ("max_step_length", self.options.max_step_length),
+            ("smoothing_sigma", self.options.smoothing_sigma),
+        ] {
+            if let Some(value) = value {
+                if !(value > 0f64 && value.is_finite()) {
+                    return Err(anyhow!("{} must be positive, got {}", name, value));
+                }
+            }
+        }
+        if let (Some(min), Some(max)) = (self.options.min_step_length, self.options.max_step_length)
+        {
+            if min > max {
+                return Err(anyhow!(
+                    "min_step_length {} is larger than max_step_length {}",
+                    min,
+                    max
+                ));
+            }
+        }
+        Ok(self.options)
+    }
+}
+
+/// a callback for the progress of registration, called after each iteration of the optimizer with
+/// the iteration, counting from 0 over all resolutions, and the value of the metric, clones share
+/// the same callback
+#[derive(Clone)]
+pub struct Progress(Arc<Mutex<dyn FnMut(u32, f64) + Send>>);
+
+impl Progress {
+    pub fn new(callback: impl FnMut(u32, f64) + Send + 'static) -> Self {
+        Progress(Arc::new(Mutex::new(callback)))
+    }
+
+    pub(crate) fn call(&self, iteration: u32, metric: f64) {
+        // a panic in an earlier call poisons the mutex, but leaves the callback usable
+        let mut callback = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        callback(iteration, metric)
+    }
+}
+
+impl Debug for Progress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Progress")
+    }
+}
+
+/// equal if it is the same callback
+impl PartialEq for Progress {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// a flag to cancel registration from another thread, registration stops after the current
+/// iteration of the optimizer once the flag is true
+#[derive(Clone, Debug)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn new(flag: Arc<AtomicBool>) -> Self {
+        Cancel(flag)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// equal if it is the same flag
+impl PartialEq for Cancel {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// options for Transform::register
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegistrationConfig {
+    /// center of rotation and scaling (x, y), the geometric center of the fixed image if None,
+    /// the transform found has this center as origin
+    pub center: Option<[f64; 2]>,
+    /// options for the optimizer, the defaults of the registration method if not set
+    pub options: RegistrationOptions,
+    /// called after each iteration of the optimizer, setting this means the sitk registration
+    /// method is used instead of elastix
+    pub progress: Option<Progress>,
+    /// registration returns TransformError::Cancelled when this is set, setting this means the
+    /// sitk registration method is used instead of elastix
+    pub cancel: Option<Cancel>,
+    /// register using only the pixels in this rectangle: ([row, column], [height, width]), the
+    /// transform found still applies to the full images, the center of the roi is the default
+    /// center
+    pub roi: Option<([usize; 2], [usize; 2])>,
+    /// registration returns TransformError::TimedOut when it takes longer than this, with the
+    /// transform found so far, this is time_limit in the options returning an error instead of the
+    /// result, and replaces it when both are set, the sitk registration method is used instead of
+    /// elastix
+    pub timeout: Option<Duration>,
+}
+
+impl RegistrationConfig {
+    pub fn builder() -> RegistrationConfigBuilder {
+        RegistrationConfigBuilder::default()
+    }
+}
+
+/// build a RegistrationConfig, the options are validated like by RegistrationOptionsBuilder:
+///
+/// RegistrationConfig::builder().metric(Metric::MeanSquares).max_iterations(500).build()
+#[derive(Clone, Debug, Default)]
+pub struct RegistrationConfigBuilder {
+    center: Option<[f64; 2]>,
+    options: RegistrationOptionsBuilder,
+    progress: Option<Progress>,
+    cancel: Option<Cancel>,
+    roi: Option<([usize; 2], [usize; 2])>,
+    timeout: Option<Duration>,
+}
+
+impl RegistrationConfigBuilder {
+    pub fn center(mut self, center: [f64; 2]) -> Self {
+        self.center = Some(center);
+        self
+    }
+
+    /// origin: [row, column], size: [height, width]
+    pub fn roi(mut self, origin: [usize; 2], size: [usize; 2]) -> Self {
+        self.roi = Some((origin, size));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn progress(mut self, progress: impl FnMut(u32, f64) + Send + 'static) -> Self {
+        self.progress = Some(Progress::new(progress));
+        self
+    }
+
+    pub fn cancel(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(Cancel::new(flag));
+        self
+    }
+
+    /// replaces any options set before
+    pub fn options(mut self, options: RegistrationOptions) -> Self {
+        self.options = RegistrationOptionsBuilder { options };
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: u32) -> Self {
+        self.options = self.options.max_iterations(max_iterations);
+        self
+    }
+
+    pub fn pyramid_levels(mut self, pyramid_levels: u32) -> Self {
+        self.options = self.options.pyramid_levels(pyramid_levels);
+        self
+    }
+
+    pub fn sampling_fraction(mut self, sampling_fraction: f64) -> Self {
+        self.options = self.options.sampling_fraction(sampling_fraction);
+        self
+    }
+
+    pub fn mi_bins(mut self, mi_bins: u32) -> Self {
+        self.options = self.options.mi_bins(mi_bins);
+        self
+    }
+
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.options = self.options.metric(metric);
+        self
+    }
+
+    pub fn optimizer(mut self, optimizer: Optimizer) -> Self {
+        self.options = self.options.optimizer(optimizer);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.options = self.options.seed(seed);
+        self
+    }
+
+    pub fn convergence_tolerance(mut self, convergence_tolerance: f64) -> Self {
+        self.options = self.options.convergence_tolerance(convergence_tolerance);
+        self
+    }
+
+    pub fn min_step_length(mut self, min_step_length: f64) -> Self {
+        self.options = self.options.min_step_length(min_step_length);
+        self
+    }
+
+    pub fn max_step_length(mut self, max_step_length: f64) -> Self {
+        self.options = self.options.max_step_length(max_step_length);
+        self
+    }
+
+    pub fn smoothing_sigma(mut self, smoothing_sigma: f64) -> Self {
+        self.options = self.options.smoothing_sigma(smoothing_sigma);
+        self
+    }
+
+    pub fn histogram_match(mut self, histogram_match: bool) -> Self {
+        self.options = self.options.histogram_match(histogram_match);
+        self
+    }
+
+    pub fn histogram_levels(mut self, histogram_levels: u32) -> Self {
+        self.options = self.options.histogram_levels(histogram_levels);
+        self
+    }
+
+    pub fn estimate_scales(mut self, estimate_scales: ScalesFrom) -> Self {
+        self.options = self.options.estimate_scales(estimate_scales);
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.options = self.options.normalization(normalization);
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> Self {
+        self.options = self.options.time_limit(time_limit);
+        self
+    }
+
+    pub fn build(self) -> Result<RegistrationConfig> {
+        if let Some(center) = self.center {
+            if !center.iter().all(|c| c.is_finite()) {
+                return Err(anyhow!("center must be finite, got {:?}", center));
+            }
+        }
+        if let Some((_, size)) = self.roi {
+            if size.contains(&0) {
+                return Err(anyhow!("roi cannot be empty, got size {:?}", size));
+            }
+        }
+        if self.timeout == Some(Duration::ZERO) {
+            return Err(anyhow!("timeout cannot be zero"));
+        }
+        Ok(RegistrationConfig {
+            center: self.center,
+            options: self.options.build()?,
+            progress: self.progress,
+            cancel: self.cancel,
+            roi: self.roi,
+            timeout: self.timeout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_builder() -> Result<()> {
+        assert_eq!(
+            RegistrationOptions::builder().build()?,
+            RegistrationOptions::default()
+        );
+        let options = RegistrationOptions::builder()
+            .max_iterations(500)
+            .pyramid_levels(2)
+            .sampling_fraction(0.5)
+            .mi_bins(16)
+            .metric(Metric::NormalizedCorrelation)
+            .optimizer(Optimizer::ConjugateGradientLineSearch)
+            .seed(7)
+            .convergence_tolerance(1e-5)
+            .min_step_length(0.001)
+            .max_step_length(2.0)
+            .smoothing_sigma(1.5)
+            .histogram_match(true)
+            .histogram_levels(128)
+            .estimate_scales(ScalesFrom::Jacobian)
+            .normalization(Normalization::ZScore)
+            .time_limit(Duration::from_secs(5))
+            .build()?;
+        assert_eq!(options.max_iterations, Some(500));
+        assert_eq!(options.pyramid_levels, Some(2));
+        assert_eq!(options.sampling_fraction, Some(0.5));
+        assert_eq!(options.mi_bins, Some(16));
+        assert_eq!(options.metric, Some(Metric::NormalizedCorrelation));
+        assert_eq!(
+            options.optimizer,
+            Some(Optimizer::ConjugateGradientLineSearch)
+        );
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.convergence_tolerance, Some(1e-5));
+        assert_eq!(options.min_step_length, Some(0.001));
+        assert_eq!(options.max_step_length, Some(2.0));
+        assert_eq!(options.smoothing_sigma, Some(1.5));
+        assert!(options.histogram_match);
+        assert_eq!(options.histogram_levels, Some(128));
+        assert_eq!(options.estimate_scales, Some(ScalesFrom::Jacobian));
+        assert_eq!(options.normalization, Normalization::ZScore);
+        assert_eq!(options.time_limit, Some(Duration::from_secs(5)));
+        assert!(
+            RegistrationOptions::builder()
+                .max_iterations(0)
+                .build()
+                .is_err()
+        );
+        assert!(
+            RegistrationOptions::builder()
+                .pyramid_levels(0)
+                .build()
+                .is_err()
+        );
+        assert!(RegistrationOptions::builder().mi_bins(0).build().is_err());
+        assert!(
+            RegistrationOptions::builder()
+                .sampling_fraction(0.0)
+                .build()
+                .is_err()
+        );
+        assert!(
+            RegistrationOptions::builder()
+                .sampling_fraction(1.5)
+                .build()
+                .is_err()
+        );
+        assert!(
+            RegistrationOptions::builder()
+                .sampling_fraction(f64::NAN)
+                .build()
+                .is_err()
+        );
+        assert!(
+            RegistrationOptions::builder()
+                .convergence_tolerance(-1.0)
+                .build()
+                .is_err()
+        );
+        assert!(
+            RegistrationOptions::builder()
+                .min_step_length(2.0)
+                .max_step_length(1.0)
+                .build()
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn config_builder() -> Result<()> {
+        assert_eq!(
+            RegistrationConfig::builder().build()?,
+            RegistrationConfig::default()
+        );
+        let config = RegistrationConfig::builder()
+            .center([10.0, 20.0])
+            .metric(Metric::MeanSquares)
+            .optimizer(Optimizer::RegularStepGradientDescent)
+            .max_iterations(300)
+            .sampling_fraction(0.25)
+            .build()?;
+        assert_eq!(config.center, Some([10.0, 20.0]));
+        assert_eq!(config.roi, None);
+        assert_eq!(config.timeout, None);
+        let timeout = RegistrationConfig::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        assert_eq!(timeout.timeout, Some(Duration::from_secs(10)));
+        assert!(
+            RegistrationConfig::builder()
+                .timeout(Duration::ZERO)
+                .build()
+                .is_err()
+        );
+        assert_eq!(
+            config.options,
+            RegistrationOptions::builder()
+                .metric(Metric::MeanSquares)
+                .optimizer(Optimizer::RegularStepGradientDescent)
+                .max_iterations(300)
+                .sampling_fraction(0.25)
+                .build()?
+        );
+        let options = RegistrationOptions::builder().seed(3).build()?;
+        let config = RegistrationConfig::builder()
+            .max_iterations(300)
+            .options(options.clone())
+            .build()?;
+        assert_eq!(config.options, options);
+        assert!(
+            RegistrationConfig::builder()
+                .max_iterations(0)
+                .build()
+                .is_err()
+        );
+        assert!(
+            RegistrationConfig::builder()
+                .center([f64::NAN, 0.0])
+                .build()
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn roi() -> Result<()> {
+        let config = RegistrationConfig::builder()
+            .roi([100, 200], [300, 400])
+            .build()?;
+        assert_eq!(config.roi, Some(([100, 200], [300, 400])));
+        assert!(
+            RegistrationConfig::builder()
+                .roi([100, 200], [0, 400])
+                .build()
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn progress() -> Result<()> {
+        let count = Arc::new(Mutex::new(0));
+        let c = count.clone();
+        let config = RegistrationConfig::builder()
+            .progress(move |_, _| *c.lock().unwrap() += 1)
+            .build()?;
+        let progress = config.progress.clone().unwrap();
+        progress.call(0, 1.0);
+        progress.call(1, 0.5);
+        assert_eq!(*count.lock().unwrap(), 2);
+        assert_eq!(config.clone(), config);
+        assert_ne!(
+            config,
+            RegistrationConfig::builder().progress(|_, _| ()).build()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cancel() -> Result<()> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let config = RegistrationConfig::builder().cancel(flag.clone()).build()?;
+        let cancel = config.cancel.clone().unwrap();
+        assert!(!cancel.is_cancelled());
+        flag.store(true, Ordering::Relaxed);
+        assert!(cancel.is_cancelled());
+        assert_eq!(config.clone(), config);
+        assert_ne!(
+            config,
+            RegistrationConfig::builder()
+                .cancel(Arc::new(AtomicBool::new(true)))
+                .build()?
+        );
+        Ok(())
+    }
+}
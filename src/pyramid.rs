@@ -0,0 +1,111 @@
+use anyhow::{Result, anyhow};
+use ndarray::{Array2, AsArray, Ix2};
+use num::ToPrimitive;
+
+/// downsample an image by averaging blocks of factor x factor pixels, rows and columns at the end
+/// which do not fill a whole block are dropped, pixel (i, j) of the result is centered on pixel
+/// (factor * i + (factor - 1) / 2, factor * j + (factor - 1) / 2) of the image
+pub(crate) fn downsample<'a, A, T>(image: A, factor: usize) -> Result<Array2<f64>>
+where
+    T: 'a + ToPrimitive,
+    A: AsArray<'a, T, Ix2>,
+{
+    let image = image.into();
+    if factor == 0 {
+        return Err(anyhow!("downsampling factor cannot be zero"));
+    }
+    let (height, width) = image.dim();
+    let shape = (height / factor, width / factor);
+    if shape.0 == 0 || shape.1 == 0 {
+        return Err(anyhow!(
+            "image with shape {:?} is too small to downsample by {}",
+            image.shape(),
+            factor
+        ));
+    }
+    let mut out = Array2::<f64>::zeros(shape);
+    for ((i, j), p) in image.indexed_iter() {
+        if i < shape.0 * factor && j < shape.1 * factor {
+            out[[i / factor, j / factor]] += p.to_f64().unwrap_or(0f64);
+        }
+    }
+    out /= (factor * factor) as f64;
+    Ok(out)
+}
+
+/// downsample an image by blurring it with a gaussian with sigma factor / 2 to prevent aliasing,
+/// and taking every factor-th pixel, pixel (i, j) of the result is pixel (factor * i, factor * j)
+/// of the image
+pub(crate) fn gaussian_downsample<'a, A, T>(image: A, factor: usize) -> Result<Array2<f64>>
+where
+    T: 'a + ToPrimitive,
+    A: AsArray<'a, T, Ix2>,
+{
+    let image = image.into();
+    if factor == 0 {
+        return Err(anyhow!("downsampling factor cannot be zero"));
+    }
+    let image = image.map(|p| p.to_f64().unwrap_or(0f64));
+    if factor == 1 {
+        return Ok(image);
+    }
+    let sigma = factor as f64 / 2f64;
+    let radius = (3f64 * sigma).ceil() as isize;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|i| (-((i * i) as f64) / (2f64 * sigma * sigma)).exp())
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    let kernel: Vec<f64> = kernel.into_iter().map(|k| k / sum).collect();
+    // separable: blur along the columns at the sampled rows only, then along the rows at the
+    // sampled columns, the edges are extended
+    let (height, width) = image.dim();
+    let shape = (height.div_ceil(factor), width.div_ceil(factor));
+    let clamp = |i: isize, n: usize| i.clamp(0, n as isize - 1) as usize;
+    let rows = Array2::from_shape_fn((shape.0, width), |(i, j)| {
+        kernel
+            .iter()
+            .zip(-radius..=radius)
+            .map(|(k, d)| k * image[[clamp((factor * i) as isize + d, height), j]])
+            .sum::<f64>()
+    });
+    Ok(Array2::from_shape_fn(shape, |(i, j)| {
+        kernel
+            .iter()
+            .zip(-radius..=radius)
+            .map(|(k, d)| k * rows[[i, clamp((factor * j) as isize + d, width)]])
+            .sum::<f64>()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn downsample_blocks() -> Result<()> {
+        let a = array![[1u8, 3, 5, 7, 9], [1, 3, 5, 7, 9], [2, 2, 2, 2, 2]];
+        assert_eq!(downsample(&a, 2)?, array![[2.0, 6.0]]);
+        assert_eq!(downsample(&a, 1)?, a.mapv(|x| x as f64));
+        assert!(downsample(&a, 0).is_err());
+        assert!(downsample(&a, 4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn gaussian_downsample_constant() -> Result<()> {
+        let a = Array2::<u8>::from_elem((9, 10), 7);
+        let b = gaussian_downsample(&a, 4)?;
+        assert_eq!(b.dim(), (3, 3));
+        assert!(b.iter().all(|p| (p - 7.0).abs() < 1e-12));
+        assert_eq!(gaussian_downsample(&a, 1)?, a.mapv(|x| x as f64));
+        assert!(gaussian_downsample(&a, 0).is_err());
+        // a single bright pixel is spread out, but its total intensity is about preserved
+        let mut c = Array2::<f64>::zeros((32, 32));
+        c[[16, 16]] = 16.0;
+        let d = gaussian_downsample(&c, 2)?;
+        assert!(d[[8, 8]] < 16.0);
+        assert!((d.sum() * 4.0 - 16.0).abs() < 1.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use crate::sys::check_not_empty;
+use crate::{
+    Interpolator, PixelType, RegistrationConfig, RegistrationMode, Transform, TransformError,
+};
+use anyhow::{Result, anyhow};
+use ndarray::{Array2, ArrayView2};
+use num::ToPrimitive;
+
+/// register each frame against the first frame, returns one transform per frame, the identity for
+/// the first frame, each transform transforms its frame into the first frame
+///
+/// all frames are registered even if some fail, the failures are returned together as
+/// TransformError::Series, with the frame indices and the transforms that were found
+pub fn register_series<T: PixelType>(
+    frames: &[ArrayView2<T>],
+    mode: RegistrationMode,
+) -> Result<Vec<Transform>> {
+    let Some(first) = frames.first() else {
+        return Ok(Vec::new());
+    };
+    let (height, width) = first.dim();
+    check_not_empty(first.shape())?;
+    let identity = Transform::new(
+        [1f64, 0f64, 0f64, 1f64, 0f64, 0f64],
+        [((width - 1) as f64) / 2f64, ((height - 1) as f64) / 2f64],
+        [height, width],
+    );
+    let config = RegistrationConfig::default();
+    let mut transforms = vec![Some(identity)];
+    let mut errors = Vec::new();
+    for (i, frame) in frames.iter().enumerate().skip(1) {
+        match Transform::register(*first, *frame, mode, &config) {
+            Ok(transform) => transforms.push(Some(transform)),
+            Err(e) => {
+                transforms.push(None);
+                errors.push((i, e.to_string()));
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(transforms.into_iter().flatten().collect())
+    } else {
+        Err(TransformError::Series { transforms, errors }.into())
+    }
+}
+
+/// the result of register_to_mean_with_result
+#[derive(Clone, Debug)]
+pub struct MeanRegistration {
+    /// one transform per frame, their average is the identity
+    pub transforms: Vec<Transform>,
+    /// the largest change in any parameter in each iteration, to judge convergence
+    pub changes: Vec<f64>,
+}
+
+/// register each frame against the mean of all frames: starting from registration against the
+/// first frame, in each iteration all frames are transformed, averaged, and registered against
+/// that average again, the transforms are normalized such that their average is the identity,
+/// so that the reference does not drift
+pub fn register_to_mean<T: PixelType + ToPrimitive>(
+    frames: &[ArrayView2<T>],
+    n_iterations: usize,
+    mode: RegistrationMode,
+) -> Result<Vec<Transform>> {
+    Ok(register_to_mean_with_result(frames, n_iterations, mode)?.transforms)
+}
+
+/// like register_to_mean, but also returns the largest change in any parameter in each
+/// iteration
+pub fn register_to_mean_with_result<T: PixelType + ToPrimitive>(
+    frames: &[ArrayView2<T>],
+    n_iterations: usize,
+    mode: RegistrationMode,
+) -> Result<MeanRegistration> {
+    if frames.is_empty() {
+        return Ok(MeanRegistration {
+            transforms: Vec::new(),
+            changes: Vec::new(),
+        });
+    }
+    let frames: Vec<Array2<f64>> = frames
+        .iter()
+        .map(|frame| frame.mapv(|x| x.to_f64().unwrap_or(0f64)))
+        .collect();
+    let views: Vec<_> = frames.iter().map(|frame| frame.view()).collect();
+    let config = RegistrationConfig::default();
+    let mut transforms = normalize(register_series(&views, mode)?)?;
+    let mut changes = Vec::with_capacity(n_iterations);
+    for iteration in 0..n_iterations {
+        let warped = apply_series(&views, &transforms, Interpolator::BSpline { order: 3 })?;
+        let mut mean = Array2::<f64>::zeros(frames[0].dim());
+        for w in &warped {
+            mean += w;
+        }
+        mean /= warped.len() as f64;
+        let new = normalize(
+            views
+                .iter()
+                .map(|frame| Transform::register(mean.view(), frame.view(), mode, &config))
+                .collect::<Result<Vec<_>>>()?,
+        )?;
+        let change = transforms
+            .iter()
+            .zip(&new)
+            .flat_map(|(a, b)| {
+                a.parameters
+                    .iter()
+                    .zip(b.parameters)
+                    .map(|(p, q)| (p - q).abs())
+            })
+            .fold(0f64, f64::max);
+        log::info!(
+            "register_to_mean iteration {}: max parameter change {}",
+            iteration,
+            change
+        );
+        changes.push(change);
+        transforms = new;
+    }
+    Ok(MeanRegistration {
+        transforms,
+        changes,
+    })
+}
+
+/// change the reference such that the average of the transforms is the identity, all
+/// transforms must have the same origin
+fn normalize(transforms: Vec<Transform>) -> Result<Vec<Transform>> {
+    let n = transforms.len() as f64;
+    let mut parameters = [0f64; 6];
+    for transform in &transforms {
+        for (p, q) in parameters.iter_mut().zip(transform.parameters) {
+            *p += q / n;
+        }
+    }
+    let mean = Transform::new(parameters, transforms[0].origin, transforms[0].shape);
+    let inverse = mean.inverse()?.matrix();
+    Ok(transforms
+        .into_iter()
+        .map(|mut transform| {
+            let m = transform.matrix().dot(&inverse);
+            transform.parameters = [
+                m[[0, 0]],
+                m[[0, 1]],
+                m[[1, 0]],
+                m[[1, 1]],
+                m[[0, 2]],
+                m[[1, 2]],
+            ];
+            transform
+        })
+        .collect())
+}
+
+/// transform each frame with the corresponding transform
+pub fn apply_series<T: PixelType>(
+    frames: &[ArrayView2<T>],
+    transforms: &[Transform],
+    interpolator: Interpolator,
+) -> Result<Vec<Array2<T>>> {
+    if frames.len() != transforms.len() {
+        return Err(anyhow!(
+            "got {} frames but {} transforms",
+            frames.len(),
+            transforms.len()
+        ));
+    }
+    frames
+        .iter()
+        .zip(transforms)
+        .map(|(frame, transform)| transform.transform_image(*frame, interpolator))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rmse;
+    use crate::tests::{julia_image, noisy_image};
+    use ndarray::s;
+
+    #[test]
+    fn registration_series_empty() {
+        let e = Array2::<u8>::zeros((0, 10));
+        assert!(register_series(&[e.view()], RegistrationMode::Affine).is_err());
+    }
+
+    #[test]
+    fn registration_series() -> Result<()> {
+        let shifts = [[0f32, 0f32], [5f32, 3f32], [10f32, 6f32], [-4f32, 8f32]];
+        let frames = shifts
+            .iter()
+            .map(|[x, y]| julia_image(*x, *y))
+            .collect::<Result<Vec<_>>>()?;
+        let views: Vec<_> = frames.iter().map(|f| f.view()).collect();
+        let transforms = register_series(&views, RegistrationMode::Translation)?;
+        assert_eq!(transforms.len(), frames.len());
+        assert!(transforms[0].is_unity());
+        for (transform, [x, y]) in transforms.iter().zip(shifts) {
+            assert!((transform.parameters[4] + x as f64).abs() < 0.1);
+            assert!((transform.parameters[5] + y as f64).abs() < 0.1);
+        }
+
+        let warped = apply_series(&views, &transforms, Interpolator::BSpline { order: 3 })?;
+        let interior = s![50..550, 50..750];
+        for (frame, w) in frames.iter().zip(&warped).skip(1) {
+            assert!(
+                rmse(frames[0].slice(interior), w.slice(interior))?
+                    < 0.2 * rmse(frames[0].slice(interior), frame.slice(interior))?
+            );
+        }
+        assert!(
+            apply_series(&views, &transforms[1..], Interpolator::BSpline { order: 3 }).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registration_series_errors() -> Result<()> {
+        let j = julia_image(0f32, 0f32)?;
+        let c = Array2::<u8>::from_elem(j.dim(), 100);
+        let views = [j.view(), c.view(), j.view()];
+        let error = register_series(&views, RegistrationMode::Translation).unwrap_err();
+        match error.downcast_ref::<TransformError>() {
+            Some(TransformError::Series { transforms, errors }) => {
+                assert_eq!(transforms.len(), 3);
+                assert!(transforms[1].is_none());
+                assert!(transforms[2].is_some());
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].0, 1);
+            }
+            _ => panic!("expected TransformError::Series, got {:?}", error),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn registration_to_mean() -> Result<()> {
+        // frame 0 is much noisier than the others, which biases registration against it
+        let shifts = [
+            [0f32, 0f32],
+            [3f32, 2f32],
+            [-2f32, 4f32],
+            [5f32, -3f32],
+            [1f32, 1f32],
+        ];
+        let frames = shifts
+            .iter()
+            .enumerate()
+            .map(|(i, [x, y])| {
+                let amplitude = if i == 0 { 120f64 } else { 10f64 };
+                Ok(noisy_image(&julia_image(*x, *y)?, amplitude, i as u64))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let views: Vec<_> = frames.iter().map(|f| f.view()).collect();
+
+        // residual shifts after removing the common shift, which is arbitrary
+        let residual = |transforms: &[Transform]| {
+            let n = shifts.len() as f64;
+            let errors: Vec<[f64; 2]> = transforms
+                .iter()
+                .zip(shifts)
+                .map(|(t, [x, y])| [t.parameters[4] + x as f64, t.parameters[5] + y as f64])
+                .collect();
+            let mean = [0, 1].map(|k| errors.iter().map(|e| e[k]).sum::<f64>() / n);
+            (errors
+                .iter()
+                .map(|e| (e[0] - mean[0]).powi(2) + (e[1] - mean[1]).powi(2))
+                .sum::<f64>()
+                / n)
+                .sqrt()
+        };
+
+        let first = register_series(&views, RegistrationMode::Translation)?;
+        let result = register_to_mean_with_result(&views, 2, RegistrationMode::Translation)?;
+        assert_eq!(result.changes.len(), 2);
+        assert!(result.changes.iter().all(|c| c.is_finite()));
+        let mean = result.transforms;
+        assert_eq!(mean.len(), frames.len());
+        let average = [4, 5].map(|k| mean.iter().map(|t| t.parameters[k]).sum::<f64>());
+        assert!(average.iter().all(|a| a.abs() < 1e-9));
+        assert!(residual(&mean) < residual(&first));
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use crate::Transform;
+
+/// a history of transforms, for example found one after the other in interactive registration,
+/// with undo and redo, pushing a transform discards the transforms that were undone
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransformStack {
+    stack: Vec<Transform>,
+    current: usize,
+}
+
+impl TransformStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, t: Transform) {
+        self.stack.truncate(self.current);
+        self.stack.push(t);
+        self.current = self.stack.len();
+    }
+
+    /// remove the last transform from the combination, returns that transform, None if there is
+    /// nothing to undo
+    pub fn undo(&mut self) -> Option<&Transform> {
+        self.current = self.current.checked_sub(1)?;
+        self.stack.get(self.current)
+    }
+
+    /// add the last transform that was undone back to the combination, returns that transform,
+    /// None if there is nothing to redo
+    pub fn redo(&mut self) -> Option<&Transform> {
+        let t = self.stack.get(self.current)?;
+        self.current += 1;
+        Some(t)
+    }
+
+    /// the last transform in the combination
+    pub fn current(&self) -> Option<&Transform> {
+        self.current.checked_sub(1).map(|i| &self.stack[i])
+    }
+
+    /// the transform which applies all transforms in the combination in the order in which they
+    /// were pushed, None if it is empty
+    pub fn combined(&self) -> Option<Transform> {
+        Transform::compose_many(&self.stack[..self.current])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_redo() {
+        let a = Transform::from_translation([5.0, 3.0]);
+        let b = Transform::rotate_about(0.3, 10.0, 20.0);
+        let c = Transform::scale_about(1.1, 0.9, 10.0, 20.0);
+        let mut stack = TransformStack::new();
+        assert_eq!(stack.combined(), None);
+        assert_eq!(stack.undo(), None);
+        stack.push(a.clone());
+        stack.push(b.clone());
+        stack.push(c.clone());
+        assert_eq!(stack.current(), Some(&c));
+        assert_eq!(stack.combined(), Some(&c * &(&b * &a)));
+        assert_eq!(stack.redo(), None);
+
+        assert_eq!(stack.undo(), Some(&c));
+        assert_eq!(stack.undo(), Some(&b));
+        assert_eq!(stack.current(), Some(&a));
+        assert_eq!(stack.combined(), Some(a.clone()));
+        assert_eq!(stack.redo(), Some(&b));
+        assert_eq!(stack.combined(), Some(&b * &a));
+
+        // pushing discards c, which was undone
+        stack.push(c.clone());
+        assert_eq!(stack.redo(), None);
+        assert_eq!(stack.combined(), Some(&c * &(&b * &a)));
+        stack.undo();
+        stack.undo();
+        stack.undo();
+        assert_eq!(stack.current(), None);
+        assert_eq!(stack.undo(), None);
+        assert_eq!(stack.combined(), None);
+    }
+}
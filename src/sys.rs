@@ -1,9 +1,17 @@
-use crate::PixelType;
-use anyhow::Result;
-use libc::{c_double, c_uint};
-use ndarray::{Array2, AsArray, Ix2};
+use crate::{
+    Cancel, Interpolator, PixelType, Progress, RegistrationConfig, RegistrationMode,
+    RegistrationOptions, Transform, TransformError,
+};
+use anyhow::{Result, anyhow};
+use libc::{c_char, c_double, c_int, c_uint, c_void};
+use ndarray::{Array2, Array3, ArrayView2, ArrayView3, AsArray, Ix2, Ix3, s};
 use one_at_a_time_please::one_at_a_time;
+use std::any::Any;
+use std::ffi::CStr;
+use std::panic::{AssertUnwindSafe, catch_unwind};
 use std::ptr;
+use std::thread;
+use std::time::Instant;
 
 macro_rules! register_fn {
     ($($name:ident: $T:ty $(,)?)*) => {
@@ -11,10 +19,55 @@ macro_rules! register_fn {
             fn $name(
                 width: c_uint,
                 height: c_uint,
-                fixed_arr: *const $T,
-                moving_arr: *const $T,
-                translation_or_affine: bool,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                mode: c_uint,
+                edges: bool,
+                center: *const c_double,
+                spacing: *const c_double,
+                direction: *const c_double,
                 transform: &mut *mut c_double,
+                options: *const RegistrationParameters,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+#[cfg(feature = "elastix")]
+macro_rules! register_vector_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                channels: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                mode: c_uint,
+                transform: &mut *mut c_double,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! register_recording_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                mode: c_uint,
+                center: *const c_double,
+                initial: *const c_double,
+                transform: &mut *mut c_double,
+                callback: extern "C" fn(*mut c_void, c_double),
+                data: *mut c_void,
+                options: *const RegistrationParameters,
+                status: &mut c_int,
             );
         )*
     };
@@ -28,14 +81,259 @@ macro_rules! interp_fn {
                 height: c_uint,
                 transform: *const c_double,
                 origin: *const c_double,
+                spacing: *const c_double,
+                direction: *const c_double,
+                image: &mut *mut $T,
+                interpolator: c_uint,
+                roi: *const c_uint,
+                output: &mut *mut $T,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+#[cfg(feature = "elastix")]
+macro_rules! register_bspline_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                grid_spacing: *const c_uint,
+                field: &mut *mut c_double,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! register_demons_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                iterations: c_uint,
+                sigma: c_double,
+                field: &mut *mut c_double,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! warp_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
                 image: &mut *mut $T,
-                bspline_or_nn: bool,
+                field: *const c_double,
+                interpolator: c_uint,
+                output: &mut *mut $T,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! register_3d_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                depth: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                mode: c_uint,
+                transform: &mut *mut c_double,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! interp_3d_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                depth: c_uint,
+                transform: *const c_double,
+                origin: *const c_double,
+                image: &*const $T,
+                interpolator: c_uint,
+                output: &mut *mut $T,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! register_masked_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                fixed_mask: *const u8,
+                moving_mask: *const u8,
+                mode: c_uint,
+                transform: &mut *mut c_double,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! register_resample_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                mode: c_uint,
+                interpolator: c_uint,
+                transform: &mut *mut c_double,
+                output: &mut *mut $T,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! register_exhaustive_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                step: *const c_double,
+                range: *const c_double,
+                transform: &mut *mut c_double,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! register_3d_labels_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                depth: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                fixed_label_arr: &*const u8,
+                moving_label_arr: &*const u8,
+                transform: &mut *mut c_double,
+                status: &mut c_int,
+            );
+        )*
+    };
+}
+
+macro_rules! register_info_fn {
+    ($($name:ident: $T:ty $(,)?)*) => {
+        $(
+            fn $name(
+                width: c_uint,
+                height: c_uint,
+                fixed_arr: &*const $T,
+                moving_arr: &*const $T,
+                mode: c_uint,
+                center: *const c_double,
+                transform: &mut *mut c_double,
+                options: *const RegistrationParameters,
+                info: *mut RegistrationInfo,
+                callback: extern "C" fn(*mut c_void, c_double),
+                stop: Option<extern "C" fn(*mut c_void) -> bool>,
+                data: *mut c_void,
+                status: &mut c_int,
             );
         )*
     };
 }
 
+/// how the optimizer ended, filled in by the adapter
+#[repr(C)]
+struct RegistrationInfo {
+    final_metric: c_double,
+    iterations: c_uint,
+    stop_condition: [c_char; 256],
+    timed_out: bool,
+}
+
+/// RegistrationOptions as passed to the adapter, zero means the default, the seed is only used
+/// if fixed_seed is true
+#[repr(C)]
+struct RegistrationParameters {
+    max_iterations: c_uint,
+    pyramid_levels: c_uint,
+    sampling_fraction: c_double,
+    mi_bins: c_uint,
+    metric: c_uint,
+    optimizer: c_uint,
+    fixed_seed: bool,
+    seed: u64,
+    convergence_tolerance: c_double,
+    min_step_length: c_double,
+    max_step_length: c_double,
+    smoothing_sigma: c_double,
+    histogram_match: bool,
+    histogram_levels: c_uint,
+    estimate_scales: c_uint,
+    normalization: c_uint,
+    time_limit: c_double,
+}
+
+impl From<&RegistrationOptions> for RegistrationParameters {
+    fn from(options: &RegistrationOptions) -> Self {
+        RegistrationParameters {
+            max_iterations: options.max_iterations.unwrap_or(0) as c_uint,
+            pyramid_levels: options.pyramid_levels.unwrap_or(0) as c_uint,
+            sampling_fraction: options.sampling_fraction.unwrap_or(0f64),
+            mi_bins: options.mi_bins.unwrap_or(0) as c_uint,
+            metric: options.metric.map_or(0, |metric| metric as c_uint),
+            optimizer: options.optimizer.map_or(0, |optimizer| optimizer as c_uint),
+            fixed_seed: options.seed.is_some(),
+            seed: options.seed.unwrap_or(0),
+            convergence_tolerance: options.convergence_tolerance.unwrap_or(0f64),
+            min_step_length: options.min_step_length.unwrap_or(0f64),
+            max_step_length: options.max_step_length.unwrap_or(0f64),
+            smoothing_sigma: options.smoothing_sigma.unwrap_or(0f64),
+            histogram_match: options.histogram_match,
+            histogram_levels: options.histogram_levels.unwrap_or(0) as c_uint,
+            estimate_scales: options.estimate_scales.map_or(0, |scales| scales as c_uint),
+            normalization: options.normalization as c_uint,
+            time_limit: options
+                .time_limit
+                .map_or(0f64, |time_limit| time_limit.as_secs_f64()),
+        }
+    }
+}
+
 unsafe extern "C" {
+    fn sitk_adapter_last_error() -> *const c_char;
+    fn sitk_adapter_itk_version() -> *const c_char;
+    fn sitk_adapter_simpleitk_version() -> *const c_char;
+
     register_fn! {
         register_u8: u8,
         register_i8: i8,
@@ -49,6 +347,33 @@ unsafe extern "C" {
         register_f64: f64,
     }
 
+    #[cfg(feature = "elastix")]
+    register_vector_fn! {
+        register_vector_u8: u8,
+        register_vector_i8: i8,
+        register_vector_u16: u16,
+        register_vector_i16: i16,
+        register_vector_u32: u32,
+        register_vector_i32: i32,
+        register_vector_u64: u64,
+        register_vector_i64: i64,
+        register_vector_f32: f32,
+        register_vector_f64: f64,
+    }
+
+    register_recording_fn! {
+        register_recording_u8: u8,
+        register_recording_i8: i8,
+        register_recording_u16: u16,
+        register_recording_i16: i16,
+        register_recording_u32: u32,
+        register_recording_i32: i32,
+        register_recording_u64: u64,
+        register_recording_i64: i64,
+        register_recording_f32: f32,
+        register_recording_f64: f64,
+    }
+
     interp_fn! {
         interp_u8: u8,
         interp_i8: i8,
@@ -61,24 +386,355 @@ unsafe extern "C" {
         interp_f32: f32,
         interp_f64: f64,
     }
+
+    #[cfg(feature = "half")]
+    register_fn! {
+        register_f16: half::f16,
+    }
+
+    #[cfg(feature = "half")]
+    interp_fn! {
+        interp_f16: half::f16,
+    }
+
+    #[cfg(feature = "elastix")]
+    register_bspline_fn! {
+        register_bspline_u8: u8,
+        register_bspline_i8: i8,
+        register_bspline_u16: u16,
+        register_bspline_i16: i16,
+        register_bspline_u32: u32,
+        register_bspline_i32: i32,
+        register_bspline_u64: u64,
+        register_bspline_i64: i64,
+        register_bspline_f32: f32,
+        register_bspline_f64: f64,
+    }
+
+    register_demons_fn! {
+        register_demons_u8: u8,
+        register_demons_i8: i8,
+        register_demons_u16: u16,
+        register_demons_i16: i16,
+        register_demons_u32: u32,
+        register_demons_i32: i32,
+        register_demons_u64: u64,
+        register_demons_i64: i64,
+        register_demons_f32: f32,
+        register_demons_f64: f64,
+    }
+
+    warp_fn! {
+        warp_u8: u8,
+        warp_i8: i8,
+        warp_u16: u16,
+        warp_i16: i16,
+        warp_u32: u32,
+        warp_i32: i32,
+        warp_u64: u64,
+        warp_i64: i64,
+        warp_f32: f32,
+        warp_f64: f64,
+    }
+
+    register_3d_fn! {
+        register_3d_u8: u8,
+        register_3d_i8: i8,
+        register_3d_u16: u16,
+        register_3d_i16: i16,
+        register_3d_u32: u32,
+        register_3d_i32: i32,
+        register_3d_u64: u64,
+        register_3d_i64: i64,
+        register_3d_f32: f32,
+        register_3d_f64: f64,
+    }
+
+    interp_3d_fn! {
+        interp_3d_u8: u8,
+        interp_3d_i8: i8,
+        interp_3d_u16: u16,
+        interp_3d_i16: i16,
+        interp_3d_u32: u32,
+        interp_3d_i32: i32,
+        interp_3d_u64: u64,
+        interp_3d_i64: i64,
+        interp_3d_f32: f32,
+        interp_3d_f64: f64,
+    }
+
+    register_masked_fn! {
+        register_masked_u8: u8,
+        register_masked_i8: i8,
+        register_masked_u16: u16,
+        register_masked_i16: i16,
+        register_masked_u32: u32,
+        register_masked_i32: i32,
+        register_masked_u64: u64,
+        register_masked_i64: i64,
+        register_masked_f32: f32,
+        register_masked_f64: f64,
+    }
+
+    register_resample_fn! {
+        register_resample_u8: u8,
+        register_resample_i8: i8,
+        register_resample_u16: u16,
+        register_resample_i16: i16,
+        register_resample_u32: u32,
+        register_resample_i32: i32,
+        register_resample_u64: u64,
+        register_resample_i64: i64,
+        register_resample_f32: f32,
+        register_resample_f64: f64,
+    }
+
+    register_exhaustive_fn! {
+        register_exhaustive_u8: u8,
+        register_exhaustive_i8: i8,
+        register_exhaustive_u16: u16,
+        register_exhaustive_i16: i16,
+        register_exhaustive_u32: u32,
+        register_exhaustive_i32: i32,
+        register_exhaustive_u64: u64,
+        register_exhaustive_i64: i64,
+        register_exhaustive_f32: f32,
+        register_exhaustive_f64: f64,
+    }
+
+    register_3d_labels_fn! {
+        register_3d_labels_u8: u8,
+        register_3d_labels_i8: i8,
+        register_3d_labels_u16: u16,
+        register_3d_labels_i16: i16,
+        register_3d_labels_u32: u32,
+        register_3d_labels_i32: i32,
+        register_3d_labels_u64: u64,
+        register_3d_labels_i64: i64,
+        register_3d_labels_f32: f32,
+        register_3d_labels_f64: f64,
+    }
+
+    register_info_fn! {
+        register_info_u8: u8,
+        register_info_i8: i8,
+        register_info_u16: u16,
+        register_info_i16: i16,
+        register_info_u32: u32,
+        register_info_i32: i32,
+        register_info_u64: u64,
+        register_info_i64: i64,
+        register_info_f32: f32,
+        register_info_f64: f64,
+    }
+}
+
+/// turn a nonzero status returned by the adapter into an error with the message from ITK
+fn check_status(status: c_int) -> Result<()> {
+    if status == 0 {
+        Ok(())
+    } else {
+        let message = unsafe { CStr::from_ptr(sitk_adapter_last_error()) }.to_string_lossy();
+        Err(anyhow!("ITK error: {}", message.trim()))
+    }
+}
+
+/// the adapter uses unsigned int for the number of pixels
+fn check_size(shape: &[usize]) -> Result<()> {
+    match shape.iter().try_fold(1usize, |n, &s| n.checked_mul(s)) {
+        Some(n) if n <= c_uint::MAX as usize => Ok(()),
+        _ => Err(TransformError::ImageTooLarge {
+            shape: shape.to_vec(),
+        }
+        .into()),
+    }
+}
+
+/// the adapter needs at least one pixel, and so does the geometric center of an image
+pub(crate) fn check_not_empty(shape: &[usize]) -> Result<()> {
+    if shape.contains(&0) {
+        return Err(anyhow!("image with shape {:?} is empty", shape));
+    }
+    Ok(())
+}
+
+fn check_shapes(fixed: &[usize], moving: &[usize]) -> Result<()> {
+    if fixed != moving {
+        return Err(TransformError::ShapeMismatch {
+            fixed: fixed.to_vec(),
+            moving: moving.to_vec(),
+        }
+        .into());
+    }
+    check_size(fixed)
+}
+
+/// a diverging optimization can result in parameters which are nan or infinite
+pub(crate) fn check_finite(parameters: &[f64]) -> Result<()> {
+    if parameters.iter().all(|p| p.is_finite()) {
+        Ok(())
+    } else {
+        Err(TransformError::NonFinite {
+            parameters: parameters.to_vec(),
+        }
+        .into())
+    }
+}
+
+pub(crate) fn itk_version() -> String {
+    unsafe { CStr::from_ptr(sitk_adapter_itk_version()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub(crate) fn simpleitk_version() -> String {
+    unsafe { CStr::from_ptr(sitk_adapter_simpleitk_version()) }
+        .to_string_lossy()
+        .into_owned()
 }
 
+/// roi: row_min, col_min, row_max, col_max, the output is the transformed image in this region
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn interp<'a, A, T>(
     parameters: [f64; 6],
     origin: [f64; 2],
+    spacing: [f64; 2],
+    direction: [f64; 4],
     image: A,
-    bspline_or_nn: bool,
+    interpolator: Interpolator,
+    roi: [usize; 4],
 ) -> Result<Array2<T>>
 where
     T: 'a + PixelType,
     A: AsArray<'a, T, Ix2>,
 {
+    let image = image.into();
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!(
+        "interp",
+        shape = ?image.shape(),
+        pixel_type = T::PT,
+        interpolator = ?interpolator
+    )
+    .entered();
+    interpolator_code(interpolator)?;
+    // fast path: the identity (Transform::is_unity) maps every pixel onto itself, whatever the
+    // origin, spacing and direction, so the output is a copy of the region, which is much faster
+    // than the round trip through the adapter, bspline interpolation at the pixels themselves
+    // would only add rounding errors, see benches/transform_image.rs
+    if parameters == [1f64, 0f64, 0f64, 1f64, 0f64, 0f64]
+        && roi[2] <= image.nrows()
+        && roi[3] <= image.ncols()
+    {
+        return Ok(image
+            .slice(s![roi[0]..roi[2], roi[1]..roi[3]])
+            .as_standard_layout()
+            .into_owned());
+    }
+    let out_shape = [roi[2] - roi[0], roi[3] - roi[1]];
+    let n = out_shape[0] * out_shape[1];
+    let mut out: Vec<T> = Vec::with_capacity(n);
+    unsafe {
+        interp_to_ptr(
+            parameters,
+            origin,
+            spacing,
+            direction,
+            image,
+            interpolator,
+            roi,
+            out.as_mut_ptr(),
+        )?;
+        // the adapter has filled the whole output region
+        out.set_len(n);
+    }
+    Ok(Array2::from_shape_vec((out_shape[0], out_shape[1]), out)?)
+}
+
+/// like interp, but write the output into out, which must have the size of roi
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn interp_into<'a, A, T>(
+    parameters: [f64; 6],
+    origin: [f64; 2],
+    spacing: [f64; 2],
+    direction: [f64; 4],
+    image: A,
+    interpolator: Interpolator,
+    roi: [usize; 4],
+    out: &mut [T],
+) -> Result<()>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let n = (roi[2] - roi[0]) * (roi[3] - roi[1]);
+    if out.len() != n {
+        return Err(anyhow!(
+            "output has {} pixels, but the region has {}",
+            out.len(),
+            n
+        ));
+    }
+    unsafe {
+        interp_to_ptr(
+            parameters,
+            origin,
+            spacing,
+            direction,
+            image,
+            interpolator,
+            roi,
+            out.as_mut_ptr(),
+        )
+    }
+}
+
+/// the interpolator as passed to the adapter: 0 for nearest neighbor, n + 1 for a bspline of
+/// order n
+fn interpolator_code(interpolator: Interpolator) -> Result<c_uint> {
+    match interpolator {
+        Interpolator::NearestNeighbor => Ok(0),
+        Interpolator::BSpline { order } if order <= 5 => Ok(order + 1),
+        Interpolator::BSpline { order } => Err(anyhow!(
+            "the order of the bspline must be 0 to 5, got {}",
+            order
+        )),
+    }
+}
+
+/// out_ptr must be valid for writing the number of pixels in roi
+#[allow(clippy::too_many_arguments)]
+unsafe fn interp_to_ptr<'a, A, T>(
+    parameters: [f64; 6],
+    origin: [f64; 2],
+    spacing: [f64; 2],
+    direction: [f64; 4],
+    image: A,
+    interpolator: Interpolator,
+    roi: [usize; 4],
+    out_ptr: *mut T,
+) -> Result<()>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let interpolator = interpolator_code(interpolator)?;
     let image = image.into();
     let shape: Vec<usize> = image.shape().to_vec();
+    check_size(&shape)?;
     let width = shape[1] as c_uint;
     let height = shape[0] as c_uint;
-    let mut im: Vec<_> = image.into_iter().cloned().collect();
-    let im_ptr: *mut T = ptr::from_mut(unsafe { &mut *im.as_mut_ptr() });
+    // the adapter only reads the image, in C order, this copies only if the layout is different
+    let image = image.as_standard_layout();
+    let im_ptr: *mut T = image.as_ptr() as *mut T;
+    let out_shape = [roi[2] - roi[0], roi[3] - roi[1]];
+    let region = [
+        roi[1] as c_uint,
+        roi[0] as c_uint,
+        out_shape[1] as c_uint,
+        out_shape[0] as c_uint,
+    ];
+    let mut status: c_int = 0;
 
     match T::PT {
         1 => unsafe {
@@ -87,8 +743,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut u8),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut u8),
+                &mut status,
             );
         },
         2 => unsafe {
@@ -97,8 +758,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut i8),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut i8),
+                &mut status,
             );
         },
         3 => unsafe {
@@ -107,8 +773,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut u16),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut u16),
+                &mut status,
             );
         },
         4 => unsafe {
@@ -117,8 +788,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut i16),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut i16),
+                &mut status,
             );
         },
         5 => unsafe {
@@ -127,8 +803,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut u32),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut u32),
+                &mut status,
             );
         },
         6 => unsafe {
@@ -137,8 +818,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut i32),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut i32),
+                &mut status,
             );
         },
         7 => unsafe {
@@ -147,8 +833,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut u64),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut u64),
+                &mut status,
             );
         },
         8 => unsafe {
@@ -157,8 +848,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut i64),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut i64),
+                &mut status,
             );
         },
         9 => unsafe {
@@ -167,8 +863,13 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut f32),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut f32),
+                &mut status,
             );
         },
         10 => unsafe {
@@ -177,23 +878,47 @@ where
                 height,
                 parameters.as_ptr(),
                 origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
                 &mut (im_ptr as *mut f64),
-                bspline_or_nn,
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut f64),
+                &mut status,
+            );
+        },
+        #[cfg(feature = "half")]
+        11 => unsafe {
+            interp_f16(
+                width,
+                height,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                spacing.as_ptr(),
+                direction.as_ptr(),
+                &mut (im_ptr as *mut half::f16),
+                interpolator,
+                region.as_ptr(),
+                &mut (out_ptr as *mut half::f16),
+                &mut status,
             );
         },
-        _ => {}
+        _ => return Err(anyhow!("unsupported pixel type")),
     }
-    Ok(Array2::from_shape_vec(
-        (shape[0], shape[1]),
-        im.into_iter().collect(),
-    )?)
+    check_status(status)
 }
 
+/// center: center of rotation (x, y), which is returned as origin, the geometric center of the
+/// fixed image if None, options: the defaults of the adapter if None
 #[one_at_a_time]
 pub(crate) fn register<'a, A, T>(
     fixed: A,
     moving: A,
-    translation_or_affine: bool,
+    mode: RegistrationMode,
+    edges: bool,
+    center: Option<[f64; 2]>,
+    spacing_direction: Option<([f64; 2], [f64; 4])>,
+    options: Option<&RegistrationOptions>,
 ) -> Result<([f64; 6], [f64; 2], [usize; 2])>
 where
     T: 'a + PixelType,
@@ -201,18 +926,39 @@ where
 {
     let fixed = fixed.into();
     let moving = moving.into();
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!(
+        "register",
+        shape = ?fixed.shape(),
+        pixel_type = T::PT,
+        mode = ?mode
+    )
+    .entered();
+    check_shapes(fixed.shape(), moving.shape())?;
     let shape: Vec<usize> = fixed.shape().to_vec();
     let width = shape[1] as c_uint;
     let height = shape[0] as c_uint;
-    let fixed: Vec<_> = fixed.into_iter().collect();
-    let moving: Vec<_> = moving.into_iter().collect();
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
     let fixed_ptr = fixed.as_ptr();
     let moving_ptr = moving.as_ptr();
     let mut transform: Vec<c_double> = vec![0.0; 6];
     let mut transform_ptr: *mut c_double = ptr::from_mut(unsafe { &mut *transform.as_mut_ptr() });
+    let center_ptr = match &center {
+        Some(c) => c.as_ptr(),
+        None => ptr::null(),
+    };
+    let (spacing, direction) = spacing_direction.unwrap_or(([1.0, 1.0], [1.0, 0.0, 0.0, 1.0]));
+    let options = options.map(RegistrationParameters::from);
+    let options_ptr = match &options {
+        Some(o) => ptr::from_ref(o),
+        None => ptr::null(),
+    };
 
     // let ma0 = &mut moving as *mut Vec<T> as usize;
     // println!("ma0: {:#x}", ma0);
+    let mut status: c_int = 0;
 
     match T::PT {
         1 => {
@@ -220,10 +966,16 @@ where
                 register_u8(
                     width,
                     height,
-                    fixed_ptr as *const u8,
-                    moving_ptr as *const u8,
-                    translation_or_affine,
+                    &(fixed_ptr as *const u8),
+                    &(moving_ptr as *const u8),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -232,10 +984,16 @@ where
                 register_i8(
                     width,
                     height,
-                    fixed_ptr as *const i8,
-                    moving_ptr as *const i8,
-                    translation_or_affine,
+                    &(fixed_ptr as *const i8),
+                    &(moving_ptr as *const i8),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -244,10 +1002,16 @@ where
                 register_u16(
                     width,
                     height,
-                    fixed_ptr as *const u16,
-                    moving_ptr as *const u16,
-                    translation_or_affine,
+                    &(fixed_ptr as *const u16),
+                    &(moving_ptr as *const u16),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -256,10 +1020,16 @@ where
                 register_i16(
                     width,
                     height,
-                    fixed_ptr as *const i16,
-                    moving_ptr as *const i16,
-                    translation_or_affine,
+                    &(fixed_ptr as *const i16),
+                    &(moving_ptr as *const i16),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -268,10 +1038,16 @@ where
                 register_u32(
                     width,
                     height,
-                    fixed_ptr as *const u32,
-                    moving_ptr as *const u32,
-                    translation_or_affine,
+                    &(fixed_ptr as *const u32),
+                    &(moving_ptr as *const u32),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -280,10 +1056,16 @@ where
                 register_i32(
                     width,
                     height,
-                    fixed_ptr as *const i32,
-                    moving_ptr as *const i32,
-                    translation_or_affine,
+                    &(fixed_ptr as *const i32),
+                    &(moving_ptr as *const i32),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -292,10 +1074,16 @@ where
                 register_u64(
                     width,
                     height,
-                    fixed_ptr as *const u64,
-                    moving_ptr as *const u64,
-                    translation_or_affine,
+                    &(fixed_ptr as *const u64),
+                    &(moving_ptr as *const u64),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -304,10 +1092,16 @@ where
                 register_i64(
                     width,
                     height,
-                    fixed_ptr as *const i64,
-                    moving_ptr as *const i64,
-                    translation_or_affine,
+                    &(fixed_ptr as *const i64),
+                    &(moving_ptr as *const i64),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -316,10 +1110,16 @@ where
                 register_f32(
                     width,
                     height,
-                    fixed_ptr as *const f32,
-                    moving_ptr as *const f32,
-                    translation_or_affine,
+                    &(fixed_ptr as *const f32),
+                    &(moving_ptr as *const f32),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
@@ -328,15 +1128,42 @@ where
                 register_f64(
                     width,
                     height,
-                    fixed_ptr as *const f64,
-                    moving_ptr as *const f64,
-                    translation_or_affine,
+                    &(fixed_ptr as *const f64),
+                    &(moving_ptr as *const f64),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
+                    &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
+                )
+            };
+        }
+        #[cfg(feature = "half")]
+        11 => {
+            unsafe {
+                register_f16(
+                    width,
+                    height,
+                    &(fixed_ptr as *const half::f16),
+                    &(moving_ptr as *const half::f16),
+                    mode as c_uint,
+                    edges,
+                    center_ptr,
+                    spacing.as_ptr(),
+                    direction.as_ptr(),
                     &mut transform_ptr,
+                    options_ptr,
+                    &mut status,
                 )
             };
         }
-        _ => {}
+        _ => return Err(anyhow!("unsupported pixel type")),
     }
+    check_status(status)?;
+    check_finite(&transform)?;
 
     // let ma1 = &mut moving as *mut Vec<T> as usize;
     // println!("ma1: {:#x}", ma1);
@@ -353,10 +1180,2187 @@ where
             transform[4] as f64,
             transform[5] as f64,
         ],
-        [
-            ((shape[0] - 1) as f64) / 2f64,
+        center.unwrap_or([
             ((shape[1] - 1) as f64) / 2f64,
-        ],
+            ((shape[0] - 1) as f64) / 2f64,
+        ]),
         [shape[0], shape[1]],
     ))
 }
+
+extern "C" fn record_metric(data: *mut c_void, metric: c_double) {
+    let history = unsafe { &mut *(data as *mut Vec<f64>) };
+    history.push(metric);
+}
+
+/// register with the sitk registration method, returns the parameters of the transform about
+/// center, and the value of the metric after each iteration, initial: parameters of the affine
+/// transform about center to start the optimizer from, options: the defaults of the adapter if None
+#[one_at_a_time]
+pub(crate) fn register_recording<'a, A, T>(
+    fixed: A,
+    moving: A,
+    mode: RegistrationMode,
+    center: [f64; 2],
+    initial: Option<[f64; 6]>,
+    options: Option<&RegistrationOptions>,
+) -> Result<([f64; 6], Vec<f64>)>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    if let Some(initial) = &initial {
+        check_finite(initial)?;
+    }
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let mut transform = [0f64; 6];
+    let mut transform_ptr: *mut c_double = transform.as_mut_ptr();
+    let initial_ptr = match &initial {
+        Some(p) => p.as_ptr(),
+        None => ptr::null(),
+    };
+    let options = options.map(RegistrationParameters::from);
+    let options_ptr = match &options {
+        Some(o) => ptr::from_ref(o),
+        None => ptr::null(),
+    };
+    let mut history: Vec<f64> = Vec::new();
+    let data = &mut history as *mut Vec<f64> as *mut c_void;
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_recording_u8(
+                width,
+                height,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_recording_i8(
+                width,
+                height,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_recording_u16(
+                width,
+                height,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_recording_i16(
+                width,
+                height,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_recording_u32(
+                width,
+                height,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_recording_i32(
+                width,
+                height,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_recording_u64(
+                width,
+                height,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_recording_i64(
+                width,
+                height,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_recording_f32(
+                width,
+                height,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_recording_f64(
+                width,
+                height,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                mode as c_uint,
+                center.as_ptr(),
+                initial_ptr,
+                &mut transform_ptr,
+                record_metric,
+                data,
+                options_ptr,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    check_finite(&transform)?;
+    Ok((transform, history))
+}
+
+/// fixed and moving: [rows, columns, channels], all channels are registered jointly
+#[cfg(feature = "elastix")]
+#[one_at_a_time]
+pub(crate) fn register_vector<'a, A, T>(
+    fixed: A,
+    moving: A,
+    mode: RegistrationMode,
+) -> Result<([f64; 6], [f64; 2], [usize; 2])>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix3>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    let channels = shape[2] as c_uint;
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let mut transform: Vec<c_double> = vec![0.0; 6];
+    let mut transform_ptr: *mut c_double = transform.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_vector_u8(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_vector_i8(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_vector_u16(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_vector_i16(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_vector_u32(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_vector_i32(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_vector_u64(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_vector_i64(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_vector_f32(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_vector_f64(
+                width,
+                height,
+                channels,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    check_finite(&transform)?;
+
+    Ok((
+        [
+            transform[0],
+            transform[1],
+            transform[2],
+            transform[3],
+            transform[4],
+            transform[5],
+        ],
+        [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ],
+        [shape[0], shape[1]],
+    ))
+}
+
+/// grid_spacing: [rows, columns], returns the displacement (dx, dy) for each pixel, interleaved
+#[cfg(feature = "elastix")]
+#[one_at_a_time]
+pub(crate) fn register_bspline<'a, A, T>(
+    fixed: A,
+    moving: A,
+    grid_spacing: [usize; 2],
+) -> Result<Vec<f64>>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let spacing = [grid_spacing[1] as c_uint, grid_spacing[0] as c_uint];
+    let mut field: Vec<c_double> = vec![0.0; 2 * shape[0] * shape[1]];
+    let mut field_ptr: *mut c_double = field.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_bspline_u8(
+                width,
+                height,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_bspline_i8(
+                width,
+                height,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_bspline_u16(
+                width,
+                height,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_bspline_i16(
+                width,
+                height,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_bspline_u32(
+                width,
+                height,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_bspline_i32(
+                width,
+                height,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_bspline_u64(
+                width,
+                height,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_bspline_i64(
+                width,
+                height,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_bspline_f32(
+                width,
+                height,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_bspline_f64(
+                width,
+                height,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                spacing.as_ptr(),
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    Ok(field)
+}
+
+/// returns the displacement (dx, dy) for each pixel, interleaved
+#[one_at_a_time]
+pub(crate) fn register_demons<'a, A, T>(
+    fixed: A,
+    moving: A,
+    iterations: u32,
+    sigma: f64,
+) -> Result<Vec<f64>>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let mut field: Vec<c_double> = vec![0.0; 2 * shape[0] * shape[1]];
+    let mut field_ptr: *mut c_double = field.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_demons_u8(
+                width,
+                height,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_demons_i8(
+                width,
+                height,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_demons_u16(
+                width,
+                height,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_demons_i16(
+                width,
+                height,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_demons_u32(
+                width,
+                height,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_demons_i32(
+                width,
+                height,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_demons_u64(
+                width,
+                height,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_demons_i64(
+                width,
+                height,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_demons_f32(
+                width,
+                height,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_demons_f64(
+                width,
+                height,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                iterations as c_uint,
+                sigma,
+                &mut field_ptr,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    Ok(field)
+}
+
+/// field: the displacement (dx, dy) for each pixel, interleaved
+pub(crate) fn warp<'a, A, T>(
+    field: &[f64],
+    image: A,
+    interpolator: Interpolator,
+) -> Result<Array2<T>>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let interpolator = interpolator_code(interpolator)?;
+    let image = image.into();
+    let shape: Vec<usize> = image.shape().to_vec();
+    check_size(&shape)?;
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    let mut im: Vec<_> = image.into_iter().cloned().collect();
+    let im_ptr: *mut T = ptr::from_mut(unsafe { &mut *im.as_mut_ptr() });
+    let n = shape[0] * shape[1];
+    // the adapter reads a displacement in x and y for each pixel
+    if field.len() != 2 * n {
+        return Err(anyhow!(
+            "the field has {} values, but the image has {} pixels",
+            field.len(),
+            n
+        ));
+    }
+    let mut out: Vec<T> = Vec::with_capacity(n);
+    let out_ptr: *mut T = out.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            warp_u8(
+                width,
+                height,
+                &mut (im_ptr as *mut u8),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut u8),
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            warp_i8(
+                width,
+                height,
+                &mut (im_ptr as *mut i8),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut i8),
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            warp_u16(
+                width,
+                height,
+                &mut (im_ptr as *mut u16),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut u16),
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            warp_i16(
+                width,
+                height,
+                &mut (im_ptr as *mut i16),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut i16),
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            warp_u32(
+                width,
+                height,
+                &mut (im_ptr as *mut u32),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut u32),
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            warp_i32(
+                width,
+                height,
+                &mut (im_ptr as *mut i32),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut i32),
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            warp_u64(
+                width,
+                height,
+                &mut (im_ptr as *mut u64),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut u64),
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            warp_i64(
+                width,
+                height,
+                &mut (im_ptr as *mut i64),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut i64),
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            warp_f32(
+                width,
+                height,
+                &mut (im_ptr as *mut f32),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut f32),
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            warp_f64(
+                width,
+                height,
+                &mut (im_ptr as *mut f64),
+                field.as_ptr(),
+                interpolator,
+                &mut (out_ptr as *mut f64),
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    // the adapter has filled the whole output
+    unsafe { out.set_len(n) };
+    Ok(Array2::from_shape_vec((shape[0], shape[1]), out)?)
+}
+
+/// fixed and moving: [depth, rows, columns], returns the 3x3 matrix (row-major) followed by the
+/// translation (x, y, z), the center of rotation is the geometric center of the fixed image
+#[one_at_a_time]
+pub(crate) fn register_3d<'a, A, T>(
+    fixed: A,
+    moving: A,
+    mode: RegistrationMode,
+) -> Result<[f64; 12]>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix3>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    let width = shape[2] as c_uint;
+    let height = shape[1] as c_uint;
+    let depth = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let mut transform: Vec<c_double> = vec![0.0; 12];
+    let mut transform_ptr: *mut c_double = transform.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_3d_u8(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_3d_i8(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_3d_u16(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_3d_i16(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_3d_u32(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_3d_i32(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_3d_u64(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_3d_i64(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_3d_f32(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_3d_f64(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    check_finite(&transform)?;
+    let mut parameters = [0f64; 12];
+    parameters.copy_from_slice(&transform);
+    Ok(parameters)
+}
+
+/// image: [depth, rows, columns], the output has the same shape as the image
+pub(crate) fn interp_3d<'a, A, T>(
+    parameters: [f64; 12],
+    origin: [f64; 3],
+    image: A,
+    interpolator: Interpolator,
+) -> Result<Array3<T>>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix3>,
+{
+    let interpolator = interpolator_code(interpolator)?;
+    let image = image.into();
+    let shape: Vec<usize> = image.shape().to_vec();
+    check_size(&shape)?;
+    let width = shape[2] as c_uint;
+    let height = shape[1] as c_uint;
+    let depth = shape[0] as c_uint;
+    // the adapter only reads the image, in C order, this copies only if the layout is different
+    let image = image.as_standard_layout();
+    let im_ptr: *const T = image.as_ptr();
+    let n = shape[0] * shape[1] * shape[2];
+    let mut out: Vec<T> = Vec::with_capacity(n);
+    let out_ptr: *mut T = out.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            interp_3d_u8(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const u8),
+                interpolator,
+                &mut (out_ptr as *mut u8),
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            interp_3d_i8(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const i8),
+                interpolator,
+                &mut (out_ptr as *mut i8),
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            interp_3d_u16(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const u16),
+                interpolator,
+                &mut (out_ptr as *mut u16),
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            interp_3d_i16(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const i16),
+                interpolator,
+                &mut (out_ptr as *mut i16),
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            interp_3d_u32(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const u32),
+                interpolator,
+                &mut (out_ptr as *mut u32),
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            interp_3d_i32(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const i32),
+                interpolator,
+                &mut (out_ptr as *mut i32),
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            interp_3d_u64(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const u64),
+                interpolator,
+                &mut (out_ptr as *mut u64),
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            interp_3d_i64(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const i64),
+                interpolator,
+                &mut (out_ptr as *mut i64),
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            interp_3d_f32(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const f32),
+                interpolator,
+                &mut (out_ptr as *mut f32),
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            interp_3d_f64(
+                width,
+                height,
+                depth,
+                parameters.as_ptr(),
+                origin.as_ptr(),
+                &(im_ptr as *const f64),
+                interpolator,
+                &mut (out_ptr as *mut f64),
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    // the adapter has filled the whole output
+    unsafe { out.set_len(n) };
+    Ok(Array3::from_shape_vec((shape[0], shape[1], shape[2]), out)?)
+}
+
+/// masks: only pixels where the mask is nonzero are used, the masks must have the same shape as
+/// the images, returns the transform about the geometric center of the fixed image, which is
+/// returned as origin
+#[one_at_a_time]
+pub(crate) fn register_masked<'a, A, T>(
+    fixed: A,
+    moving: A,
+    fixed_mask: Option<ArrayView2<u8>>,
+    moving_mask: Option<ArrayView2<u8>>,
+    mode: RegistrationMode,
+) -> Result<([f64; 6], [f64; 2], [usize; 2])>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    for (name, mask_shape) in [
+        ("fixed_mask", fixed_mask.as_ref().map(|mask| mask.shape())),
+        ("moving_mask", moving_mask.as_ref().map(|mask| mask.shape())),
+    ] {
+        if let Some(mask_shape) = mask_shape {
+            if mask_shape != shape.as_slice() {
+                return Err(anyhow!(
+                    "{} must have the same shape as the images: {:?}, got {:?}",
+                    name,
+                    shape,
+                    mask_shape
+                ));
+            }
+        }
+    }
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let fixed_mask: Option<Vec<u8>> = fixed_mask.map(|mask| mask.iter().cloned().collect());
+    let moving_mask: Option<Vec<u8>> = moving_mask.map(|mask| mask.iter().cloned().collect());
+    let fixed_mask_ptr = fixed_mask
+        .as_ref()
+        .map_or(ptr::null(), |mask| mask.as_ptr());
+    let moving_mask_ptr = moving_mask
+        .as_ref()
+        .map_or(ptr::null(), |mask| mask.as_ptr());
+    let mut transform = [0f64; 6];
+    let mut transform_ptr: *mut c_double = transform.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_masked_u8(
+                width,
+                height,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_masked_i8(
+                width,
+                height,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_masked_u16(
+                width,
+                height,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_masked_i16(
+                width,
+                height,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_masked_u32(
+                width,
+                height,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_masked_i32(
+                width,
+                height,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_masked_u64(
+                width,
+                height,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_masked_i64(
+                width,
+                height,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_masked_f32(
+                width,
+                height,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_masked_f64(
+                width,
+                height,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                fixed_mask_ptr,
+                moving_mask_ptr,
+                mode as c_uint,
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    check_finite(&transform)?;
+    Ok((
+        transform,
+        [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ],
+        [shape[0], shape[1]],
+    ))
+}
+
+/// register and resample moving with the transform found in one call, returns the transform about
+/// the geometric center of the fixed image, which is returned as origin, and the resampled moving
+/// image
+#[allow(clippy::type_complexity)]
+#[one_at_a_time]
+pub(crate) fn register_resample<'a, A, T>(
+    fixed: A,
+    moving: A,
+    mode: RegistrationMode,
+    interpolator: Interpolator,
+) -> Result<([f64; 6], [f64; 2], [usize; 2], Array2<T>)>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let interpolator = interpolator_code(interpolator)?;
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    check_size(&shape)?;
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let mut transform = [0f64; 6];
+    let mut transform_ptr: *mut c_double = transform.as_mut_ptr();
+    let n = shape[0] * shape[1];
+    let mut out: Vec<T> = Vec::with_capacity(n);
+    let out_ptr: *mut T = out.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_resample_u8(
+                width,
+                height,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut u8),
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_resample_i8(
+                width,
+                height,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut i8),
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_resample_u16(
+                width,
+                height,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut u16),
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_resample_i16(
+                width,
+                height,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut i16),
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_resample_u32(
+                width,
+                height,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut u32),
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_resample_i32(
+                width,
+                height,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut i32),
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_resample_u64(
+                width,
+                height,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut u64),
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_resample_i64(
+                width,
+                height,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut i64),
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_resample_f32(
+                width,
+                height,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut f32),
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_resample_f64(
+                width,
+                height,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                mode as c_uint,
+                interpolator,
+                &mut transform_ptr,
+                &mut (out_ptr as *mut f64),
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    check_finite(&transform)?;
+    // the adapter has filled the whole output
+    unsafe { out.set_len(n) };
+    Ok((
+        transform,
+        [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ],
+        [shape[0], shape[1]],
+        Array2::from_shape_vec((shape[0], shape[1]), out)?,
+    ))
+}
+
+/// find the best translation on a grid: -range..=range in steps of step, for x and y, returns the
+/// translation about the geometric center of the fixed image, which is returned as origin
+#[one_at_a_time]
+pub(crate) fn register_exhaustive<'a, A, T>(
+    fixed: A,
+    moving: A,
+    step: [f64; 2],
+    range: [f64; 2],
+) -> Result<([f64; 6], [f64; 2], [usize; 2])>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    check_size(&shape)?;
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let mut transform = [0f64; 6];
+    let mut transform_ptr: *mut c_double = transform.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_exhaustive_u8(
+                width,
+                height,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_exhaustive_i8(
+                width,
+                height,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_exhaustive_u16(
+                width,
+                height,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_exhaustive_i16(
+                width,
+                height,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_exhaustive_u32(
+                width,
+                height,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_exhaustive_i32(
+                width,
+                height,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_exhaustive_u64(
+                width,
+                height,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_exhaustive_i64(
+                width,
+                height,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_exhaustive_f32(
+                width,
+                height,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_exhaustive_f64(
+                width,
+                height,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                step.as_ptr(),
+                range.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    check_finite(&transform)?;
+    Ok((
+        transform,
+        [
+            ((shape[1] - 1) as f64) / 2f64,
+            ((shape[0] - 1) as f64) / 2f64,
+        ],
+        [shape[0], shape[1]],
+    ))
+}
+
+/// affine registration of 3D images, guided by labels with the same shape as the images, which
+/// should overlap after registration, returns the matrix and the translation about the geometric
+/// center of fixed
+#[one_at_a_time]
+pub(crate) fn register_3d_labels<'a, A, T>(
+    fixed: A,
+    moving: A,
+    fixed_label: ArrayView3<u8>,
+    moving_label: ArrayView3<u8>,
+) -> Result<[f64; 12]>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix3>,
+{
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    check_shapes(fixed.shape(), fixed_label.shape())?;
+    check_shapes(fixed.shape(), moving_label.shape())?;
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    let width = shape[2] as c_uint;
+    let height = shape[1] as c_uint;
+    let depth = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_label: Vec<u8> = fixed_label.into_iter().cloned().collect();
+    let moving_label: Vec<u8> = moving_label.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let mut transform = [0f64; 12];
+    let mut transform_ptr: *mut c_double = transform.as_mut_ptr();
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_3d_labels_u8(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_3d_labels_i8(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_3d_labels_u16(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_3d_labels_i16(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_3d_labels_u32(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_3d_labels_i32(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_3d_labels_u64(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_3d_labels_i64(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_3d_labels_f32(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_3d_labels_f64(
+                width,
+                height,
+                depth,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                &fixed_label.as_ptr(),
+                &moving_label.as_ptr(),
+                &mut transform_ptr,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    check_status(status)?;
+    check_finite(&transform)?;
+    Ok(transform)
+}
+
+/// the state of register_info during registration: the value of the metric after each iteration,
+/// the progress callback, a panic of that callback, which must not unwind into the adapter, the
+/// cancel flag and whether it stopped the optimizer
+struct Observer<'a> {
+    history: Vec<f64>,
+    progress: Option<&'a Progress>,
+    panic: Option<Box<dyn Any + Send>>,
+    cancel: Option<&'a Cancel>,
+    cancelled: bool,
+}
+
+extern "C" fn observe(data: *mut c_void, metric: c_double) {
+    let observer = unsafe { &mut *(data as *mut Observer) };
+    let iteration = observer.history.len() as u32;
+    observer.history.push(metric);
+    // after a panic the callback is not called anymore, the panic is raised again when the adapter
+    // returns
+    if let (Some(progress), None) = (observer.progress, &observer.panic) {
+        if let Err(panic) = catch_unwind(AssertUnwindSafe(|| progress.call(iteration, metric))) {
+            observer.panic = Some(panic);
+        }
+    }
+}
+
+/// stop the optimizer when cancelled, or after a panic in the progress callback, whose result is
+/// thrown away anyway
+extern "C" fn stop(data: *mut c_void) -> bool {
+    let observer = unsafe { &mut *(data as *mut Observer) };
+    if observer.cancel.is_some_and(Cancel::is_cancelled) {
+        observer.cancelled = true;
+    }
+    observer.cancelled || observer.panic.is_some()
+}
+
+/// register like register, returns the parameters of the transform about center, the final
+/// value of the metric, the number of iterations of the optimizer over all resolutions, why the
+/// optimizer stopped and the value of the metric after each iteration, with elastix there are no
+/// iterations, config: the center in config is not used, progress, cancel and timeout need the
+/// sitk registration method, they are checked after each iteration, returns
+/// TransformError::Cancelled when cancelled, and TransformError::TimedOut with the transform so
+/// far, about center and with the shape of fixed, after the timeout, a panic in the progress
+/// callback is returned as the inner error, to be resumed by the caller once the lock is released
+#[allow(clippy::type_complexity)]
+#[one_at_a_time]
+pub(crate) fn register_info<'a, A, T>(
+    fixed: A,
+    moving: A,
+    mode: RegistrationMode,
+    center: [f64; 2],
+    config: &RegistrationConfig,
+) -> Result<thread::Result<([f64; 6], f64, u32, String, Vec<f64>)>>
+where
+    T: 'a + PixelType,
+    A: AsArray<'a, T, Ix2>,
+{
+    let start = Instant::now();
+    let fixed = fixed.into();
+    let moving = moving.into();
+    check_shapes(fixed.shape(), moving.shape())?;
+    let cancel = config.cancel.as_ref();
+    if cancel.is_some_and(Cancel::is_cancelled) {
+        return Err(TransformError::Cancelled.into());
+    }
+    let shape: Vec<usize> = fixed.shape().to_vec();
+    let width = shape[1] as c_uint;
+    let height = shape[0] as c_uint;
+    // copy into contiguous buffers, the views may have any memory layout
+    let fixed: Vec<T> = fixed.into_iter().cloned().collect();
+    let moving: Vec<T> = moving.into_iter().cloned().collect();
+    let fixed_ptr = fixed.as_ptr();
+    let moving_ptr = moving.as_ptr();
+    let mut transform = [0f64; 6];
+    let mut transform_ptr: *mut c_double = transform.as_mut_ptr();
+    // the timeout is the time limit of the adapter, so it replaces the time limit in the options
+    let mut options = RegistrationParameters::from(&config.options);
+    if let Some(timeout) = config.timeout {
+        options.time_limit = timeout.as_secs_f64();
+    }
+    let options_ptr = ptr::from_ref(&options);
+    let mut info = RegistrationInfo {
+        final_metric: 0f64,
+        iterations: 0,
+        stop_condition: [0; 256],
+        timed_out: false,
+    };
+    let mut observer = Observer {
+        history: Vec::new(),
+        progress: config.progress.as_ref(),
+        panic: None,
+        cancel,
+        cancelled: false,
+    };
+    let data = &mut observer as *mut Observer as *mut c_void;
+    // without stop the adapter may use elastix, which cannot be observed
+    let stop_callback = (config.progress.is_some() || config.cancel.is_some())
+        .then_some(stop as extern "C" fn(*mut c_void) -> bool);
+    let mut status: c_int = 0;
+
+    match T::PT {
+        1 => unsafe {
+            register_info_u8(
+                width,
+                height,
+                &(fixed_ptr as *const u8),
+                &(moving_ptr as *const u8),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        2 => unsafe {
+            register_info_i8(
+                width,
+                height,
+                &(fixed_ptr as *const i8),
+                &(moving_ptr as *const i8),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        3 => unsafe {
+            register_info_u16(
+                width,
+                height,
+                &(fixed_ptr as *const u16),
+                &(moving_ptr as *const u16),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        4 => unsafe {
+            register_info_i16(
+                width,
+                height,
+                &(fixed_ptr as *const i16),
+                &(moving_ptr as *const i16),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        5 => unsafe {
+            register_info_u32(
+                width,
+                height,
+                &(fixed_ptr as *const u32),
+                &(moving_ptr as *const u32),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        6 => unsafe {
+            register_info_i32(
+                width,
+                height,
+                &(fixed_ptr as *const i32),
+                &(moving_ptr as *const i32),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        7 => unsafe {
+            register_info_u64(
+                width,
+                height,
+                &(fixed_ptr as *const u64),
+                &(moving_ptr as *const u64),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        8 => unsafe {
+            register_info_i64(
+                width,
+                height,
+                &(fixed_ptr as *const i64),
+                &(moving_ptr as *const i64),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        9 => unsafe {
+            register_info_f32(
+                width,
+                height,
+                &(fixed_ptr as *const f32),
+                &(moving_ptr as *const f32),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        10 => unsafe {
+            register_info_f64(
+                width,
+                height,
+                &(fixed_ptr as *const f64),
+                &(moving_ptr as *const f64),
+                mode as c_uint,
+                center.as_ptr(),
+                &mut transform_ptr,
+                options_ptr,
+                &mut info,
+                observe,
+                stop_callback,
+                data,
+                &mut status,
+            );
+        },
+        _ => return Err(anyhow!("unsupported pixel type")),
+    }
+    if let Some(panic) = observer.panic {
+        return Ok(Err(panic));
+    }
+    check_status(status)?;
+    if observer.cancelled {
+        return Err(TransformError::Cancelled.into());
+    }
+    if config.timeout.is_some() && info.timed_out {
+        return Err(TransformError::TimedOut {
+            elapsed: start.elapsed(),
+            transform: check_finite(&transform)
+                .is_ok()
+                .then(|| Transform::new(transform, center, [shape[0], shape[1]])),
+        }
+        .into());
+    }
+    check_finite(&transform)?;
+    let stop_condition = unsafe { CStr::from_ptr(info.stop_condition.as_ptr()) }
+        .to_string_lossy()
+        .into_owned();
+    Ok(Ok((
+        transform,
+        info.final_metric,
+        info.iterations,
+        stop_condition,
+        observer.history,
+    )))
+}
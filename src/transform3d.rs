@@ -0,0 +1,729 @@
+use crate::sys::{check_not_empty, interp_3d, register_3d_labels};
+use crate::{Interpolator, PixelType, Transform};
+use anyhow::{Result, anyhow};
+use ndarray::{Array2, Array3, ArrayView3, AsArray, Ix2, Ix3, array, s};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{from_reader, to_writer};
+use std::fs::File;
+use std::ops::Mul;
+use std::path::PathBuf;
+
+/// a 3D affine transform: the 3x3 matrix (row-major) followed by the translation (x, y, z),
+/// about origin (x, y, z), shape is the shape of the image: [depth, rows, columns]
+///
+/// the yaml has the same fields as that of Transform, but with different lengths, so that a 2D
+/// transform cannot be read as a 3D transform, or the other way around
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transform3D {
+    pub parameters: [f64; 12],
+    pub origin: [f64; 3],
+    pub shape: [usize; 3],
+}
+
+impl Transform3D {
+    /// parameters: m00, m01, m02, m10, m11, m12, m20, m21, m22, tx, ty, tz
+    pub fn new(parameters: [f64; 12], origin: [f64; 3], shape: [usize; 3]) -> Self {
+        Transform3D {
+            parameters,
+            origin,
+            shape,
+        }
+    }
+
+    /// a rigid transform: rotation by rx, ry and rz radians about the x, y and z axes through
+    /// origin, followed by translation (tx, ty, tz)
+    ///
+    /// the rotation is R = Rz(rz) * Ry(ry) * Rx(rx) (ZYX convention): first about x, then y, then
+    /// z, all about the fixed axes, like decompose, this is ITK's Euler3DTransform with
+    /// SetComputeZYX(true), by default Euler3DTransform uses Rz * Rx * Ry
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_euler_translation(
+        rx: f64,
+        ry: f64,
+        rz: f64,
+        tx: f64,
+        ty: f64,
+        tz: f64,
+        origin: [f64; 3],
+        shape: [usize; 3],
+    ) -> Self {
+        let (sx, cx) = rx.sin_cos();
+        let (sy, cy) = ry.sin_cos();
+        let (sz, cz) = rz.sin_cos();
+        Transform3D::new(
+            [
+                cz * cy,
+                cz * sy * sx - sz * cx,
+                cz * sy * cx + sz * sx,
+                sz * cy,
+                sz * sy * sx + cz * cx,
+                sz * sy * cx - cz * sx,
+                -sy,
+                cy * sx,
+                cy * cx,
+                tx,
+                ty,
+                tz,
+            ],
+            origin,
+            shape,
+        )
+    }
+
+    /// read a transform from a yaml file
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let file = File::open(path)?;
+        Ok(from_reader(file)?)
+    }
+
+    /// write a transform to a yaml file
+    pub fn to_file(&self, path: PathBuf) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        to_writer(&mut file, self)?;
+        Ok(())
+    }
+
+    /// read a transform from a yaml string
+    pub fn from_yaml_str(yaml: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    /// write a transform to a yaml string
+    pub fn to_yaml_string(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// true if transform does nothing
+    pub fn is_unity(&self) -> bool {
+        self.parameters
+            == [
+                1f64, 0f64, 0f64, 0f64, 1f64, 0f64, 0f64, 0f64, 1f64, 0f64, 0f64, 0f64,
+            ]
+    }
+
+    /// get the 4x4 homogeneous matrix defining the transform
+    pub fn matrix(&self) -> Array2<f64> {
+        let p = &self.parameters;
+        Array2::from_shape_vec(
+            (4, 4),
+            vec![
+                p[0], p[1], p[2], p[9], p[3], p[4], p[5], p[10], p[6], p[7], p[8], p[11], 0f64,
+                0f64, 0f64, 1f64,
+            ],
+        )
+        .unwrap()
+    }
+
+    /// the transform from a 4x4 homogeneous matrix
+    fn from_matrix(m: &Array2<f64>, origin: [f64; 3], shape: [usize; 3]) -> Self {
+        Transform3D::new(
+            [
+                m[[0, 0]],
+                m[[0, 1]],
+                m[[0, 2]],
+                m[[1, 0]],
+                m[[1, 1]],
+                m[[1, 2]],
+                m[[2, 0]],
+                m[[2, 1]],
+                m[[2, 2]],
+                m[[0, 3]],
+                m[[1, 3]],
+                m[[2, 3]],
+            ],
+            origin,
+            shape,
+        )
+    }
+
+    /// get the inverse transform, the 3x3 matrix is inverted with Cramer's rule
+    pub fn inverse(&self) -> Result<Transform3D> {
+        let p = &self.parameters;
+        let m = |i: usize, j: usize| p[3 * i + j];
+        // cofactor of element (i, j)
+        let c = |i: usize, j: usize| {
+            let (i0, i1) = ((i + 1) % 3, (i + 2) % 3);
+            let (j0, j1) = ((j + 1) % 3, (j + 2) % 3);
+            m(i0, j0) * m(i1, j1) - m(i0, j1) * m(i1, j0)
+        };
+        let d = m(0, 0) * c(0, 0) + m(0, 1) * c(0, 1) + m(0, 2) * c(0, 2);
+        if d == 0f64 {
+            return Err(anyhow!("transform matrix is not invertible"));
+        }
+        let mut parameters = [0f64; 12];
+        for i in 0..3 {
+            for j in 0..3 {
+                parameters[3 * i + j] = c(j, i) / d;
+            }
+        }
+        for i in 0..3 {
+            parameters[9 + i] = -(0..3)
+                .map(|j| parameters[3 * i + j] * p[9 + j])
+                .sum::<f64>();
+        }
+        Ok(Transform3D::new(parameters, self.origin, self.shape))
+    }
+
+    /// decompose the 3x3 matrix with a QR decomposition into M = R * S * K, with R a rotation,
+    /// S = diag(scale) and K a shear: a unit upper triangular matrix, returns the euler angles
+    /// (x, y, z), scale (x, y, z) and shear (xy, xz, yz)
+    ///
+    /// the rotation is R = Rz(z) * Ry(y) * Rx(x): extrinsic rotations about x, then y, then z,
+    /// angles in radians, when y = ±pi / 2 (gimbal lock) z is chosen 0, a reflection results in a
+    /// negative z scale, the decomposition is not meaningful for a singular matrix
+    pub fn decompose(&self) -> ([f64; 3], [f64; 3], [f64; 3]) {
+        let p = &self.parameters;
+        let column = |j: usize| [p[j], p[3 + j], p[6 + j]];
+        let dot = |a: &[f64; 3], b: &[f64; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+        let norm = |a: &[f64; 3]| dot(a, a).sqrt();
+
+        // gram-schmidt on the columns
+        let a = [column(0), column(1), column(2)];
+        let r00 = norm(&a[0]);
+        let q0 = a[0].map(|x| x / r00);
+        let r01 = dot(&q0, &a[1]);
+        let u1 = [0, 1, 2].map(|i| a[1][i] - r01 * q0[i]);
+        let r11 = norm(&u1);
+        let q1 = u1.map(|x| x / r11);
+        let r02 = dot(&q0, &a[2]);
+        let r12 = dot(&q1, &a[2]);
+        let u2 = [0, 1, 2].map(|i| a[2][i] - r02 * q0[i] - r12 * q1[i]);
+        let mut r22 = norm(&u2);
+        let mut q2 = u2.map(|x| x / r22);
+        // q0 x q1 = ±q2, keep the rotation proper
+        let cross = [
+            q0[1] * q1[2] - q0[2] * q1[1],
+            q0[2] * q1[0] - q0[0] * q1[2],
+            q0[0] * q1[1] - q0[1] * q1[0],
+        ];
+        if dot(&cross, &q2) < 0f64 {
+            q2 = q2.map(|x| -x);
+            r22 = -r22;
+        }
+
+        // q is the rotation matrix with columns q0, q1, q2
+        let q = |i: usize, j: usize| [q0, q1, q2][j][i];
+        let y = (-q(2, 0)).clamp(-1f64, 1f64).asin();
+        let (x, z) = if q(2, 0).abs() < 1f64 - 1e-12 {
+            (q(2, 1).atan2(q(2, 2)), q(1, 0).atan2(q(0, 0)))
+        } else {
+            ((-q(1, 2)).atan2(q(1, 1)), 0f64)
+        };
+        (
+            [x, y, z],
+            [r00, r11, r22],
+            [r01 / r00, r02 / r00, r12 / r11],
+        )
+    }
+
+    /// the 2D transform of the slices perpendicular to axis (0: depth, 1: rows, 2: columns), for
+    /// images sliced like image.index_axis(Axis(axis), i), the out-of-plane translation is
+    /// discarded, returns an error if the transform mixes the in-plane and out-of-plane
+    /// coordinates
+    pub fn to_2d_slice(&self, axis: usize) -> Result<Transform> {
+        if axis > 2 {
+            return Err(anyhow!("axis must be 0, 1 or 2, got {}", axis));
+        }
+        // coordinates are (x, y, z), axes are [z, y, x]
+        let c = 2 - axis;
+        let [u, v] = match c {
+            0 => [1, 2],
+            1 => [0, 2],
+            _ => [0, 1],
+        };
+        let p = &self.parameters;
+        let m = |i: usize, j: usize| p[3 * i + j];
+        if [m(u, c), m(v, c), m(c, u), m(c, v)]
+            .iter()
+            .any(|x| x.abs() > 1e-12)
+        {
+            return Err(anyhow!(
+                "the transform mixes the coordinates in the plane perpendicular to axis {} with \
+                 the coordinate along it",
+                axis
+            ));
+        }
+        let shape = match axis {
+            0 => [self.shape[1], self.shape[2]],
+            1 => [self.shape[0], self.shape[2]],
+            _ => [self.shape[0], self.shape[1]],
+        };
+        Ok(Transform::new(
+            [m(u, u), m(u, v), m(v, u), m(v, v), p[9 + u], p[9 + v]],
+            [self.origin[u], self.origin[v]],
+            shape,
+        ))
+    }
+
+    /// adapt the transform to a new origin (x, y, z) and shape [depth, rows, columns]: the origin
+    /// is shifted by half the difference in size along each axis, x with the columns, y with the
+    /// rows and z with the depth, unlike Transform::adapt, which pairs origin and shape in order
+    pub fn adapt(&mut self, origin: [f64; 3], shape: [usize; 3]) {
+        let d = |axis: usize| (self.shape[axis] as f64 - shape[axis] as f64) / 2f64;
+        self.origin = [origin[0] + d(2), origin[1] + d(1), origin[2] + d(0)];
+        self.shape = shape;
+    }
+
+    /// get coordinates resulting from transforming input coordinates, with columns x, y and z,
+    /// with the homogeneous matrix
+    pub fn transform_coordinates<'a, A, T>(&self, coordinates: A) -> Result<Array2<f64>>
+    where
+        T: 'a + Clone + Into<f64>,
+        A: AsArray<'a, T, Ix2>,
+    {
+        let coordinates = coordinates.into();
+        if coordinates.ncols() != 3 {
+            return Err(anyhow!("coordinates must have three columns"));
+        }
+        let m = self.matrix();
+        let mut res = Array2::zeros(coordinates.raw_dim());
+        for (mut r, c) in res.rows_mut().into_iter().zip(coordinates.rows()) {
+            let a = array![
+                c[0].clone().into(),
+                c[1].clone().into(),
+                c[2].clone().into(),
+                1f64
+            ];
+            r.assign(&m.dot(&a).slice(s![..3]));
+        }
+        Ok(res)
+    }
+
+    /// transform the vertices (x, y, z) of a triangle mesh like transform_coordinates, the faces
+    /// index into the vertices and are returned unchanged
+    pub fn apply_to_surface_mesh(
+        &self,
+        vertices: &[[f64; 3]],
+        faces: &[[usize; 3]],
+    ) -> (Vec<[f64; 3]>, Vec<[usize; 3]>) {
+        let p = &self.parameters;
+        let vertices = vertices
+            .iter()
+            .map(|v| {
+                [0, 1, 2]
+                    .map(|i| p[3 * i] * v[0] + p[3 * i + 1] * v[1] + p[3 * i + 2] * v[2] + p[9 + i])
+            })
+            .collect();
+        (vertices, faces.to_vec())
+    }
+
+    /// find the affine transform which transforms moving into fixed, both images have shape
+    /// [depth, rows, columns], the labels have the same shape and mark structures which should
+    /// overlap after registration: besides the mutual information between the images, the
+    /// fraction of the voxels where the nonzero parts of the labels do not overlap is minimized,
+    /// the origin is the geometric center of fixed
+    pub fn register_affine_3d_segmentation_guided<'a, A, T>(
+        fixed_image: A,
+        moving_image: A,
+        fixed_label: ArrayView3<u8>,
+        moving_label: ArrayView3<u8>,
+    ) -> Result<Transform3D>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix3>,
+    {
+        let fixed_image = fixed_image.into();
+        let (depth, height, width) = fixed_image.dim();
+        check_not_empty(fixed_image.shape())?;
+        let parameters =
+            register_3d_labels(fixed_image, moving_image.into(), fixed_label, moving_label)?;
+        Ok(Transform3D::new(
+            parameters,
+            [
+                ((width - 1) as f64) / 2f64,
+                ((height - 1) as f64) / 2f64,
+                ((depth - 1) as f64) / 2f64,
+            ],
+            [depth, height, width],
+        ))
+    }
+
+    /// transform an image with shape [depth, rows, columns]
+    pub fn transform_image<'a, A, T>(
+        &self,
+        image: A,
+        interpolator: Interpolator,
+    ) -> Result<Array3<T>>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix3>,
+    {
+        interp_3d(self.parameters, self.origin, image, interpolator)
+    }
+
+    /// transform an image using cubic bspline interpolation
+    pub fn transform_image_bspline<'a, A, T>(&self, image: A) -> Result<Array3<T>>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix3>,
+    {
+        self.transform_image(image, Interpolator::BSpline { order: 3 })
+    }
+
+    /// transform an image using nearest neighbor interpolation
+    pub fn transform_image_nearest_neighbor<'a, A, T>(&self, image: A) -> Result<Array3<T>>
+    where
+        T: 'a + PixelType,
+        A: AsArray<'a, T, Ix3>,
+    {
+        self.transform_image(image, Interpolator::NearestNeighbor)
+    }
+}
+
+/// the transform applying other first and then self, origin and shape are taken from self
+impl Mul for Transform3D {
+    type Output = Transform3D;
+
+    fn mul(self, other: Transform3D) -> Transform3D {
+        Transform3D::from_matrix(&self.matrix().dot(&other.matrix()), self.origin, self.shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::blobs_image;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn adapt_3d() {
+        let mut t = Transform3D::new(
+            [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 2.0, 3.0],
+            [0.0; 3],
+            [20, 40, 60],
+        );
+        t.adapt([10.0, 20.0, 30.0], [10, 30, 70]);
+        assert_eq!(t.origin, [5.0, 25.0, 35.0]);
+        assert_eq!(t.shape, [10, 30, 70]);
+        assert_eq!(t.parameters[9..], [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn transform_coordinates_3d() -> Result<()> {
+        // rotation by 90 degrees about z, then translation
+        let t = Transform3D::new(
+            [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 2.0, 1.0, 2.0, 3.0],
+            [0.0, 0.0, 0.0],
+            [10, 10, 10],
+        );
+        let points = array![[1.0, 0.0, 0.0], [0.0, 1.0, 1.0], [2.0, 3.0, -4.0]];
+        let c = t.transform_coordinates(&points)?;
+        assert_eq!(
+            c,
+            array![[1.0, 3.0, 3.0], [0.0, 2.0, 5.0], [-2.0, 4.0, -5.0]]
+        );
+        assert!(t.transform_coordinates(&array![[1.0, 2.0]]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn surface_mesh_3d() -> Result<()> {
+        let t =
+            Transform3D::from_euler_translation(0.3, -0.2, 0.5, 1.0, -2.0, 3.0, [5.0; 3], [10; 3]);
+        // a tetrahedron
+        let vertices = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let faces = [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]];
+        let (v, f) = t.apply_to_surface_mesh(&vertices, &faces);
+        assert_eq!(f, faces);
+        let c = t.transform_coordinates(&Array2::from(vertices.to_vec()))?;
+        assert_eq!(v.len(), vertices.len());
+        for (a, b) in v.iter().zip(c.rows()) {
+            assert!(a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-12));
+        }
+        // a rigid transform preserves the lengths of the edges
+        let length = |a: [f64; 3], b: [f64; 3]| {
+            ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+        };
+        for [i, j, k] in faces {
+            for (a, b) in [(i, j), (j, k), (k, i)] {
+                assert!((length(v[a], v[b]) - length(vertices[a], vertices[b])).abs() < 1e-12);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn inverse_3d() -> Result<()> {
+        let t = Transform3D::new(
+            [
+                1.2, 0.1, -0.3, 0.2, 0.9, 0.1, -0.1, 0.3, 1.1, 4.0, -2.0, 1.5,
+            ],
+            [10.0, 20.0, 30.0],
+            [20, 40, 60],
+        );
+        let u = t.inverse()?;
+        assert_eq!(u.origin, t.origin);
+        assert_eq!(u.shape, t.shape);
+        for v in [t.clone() * u.clone(), u * t.clone()] {
+            assert!(
+                (v.matrix() - Array2::<f64>::eye(4))
+                    .iter()
+                    .all(|x| x.abs() < 1e-12)
+            );
+        }
+        let singular = Transform3D::new(
+            [1.0, 2.0, 3.0, 2.0, 4.0, 6.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+            [0.0; 3],
+            [10, 10, 10],
+        );
+        assert!(singular.inverse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decompose_3d() {
+        let rotation = |[x, y, z]: [f64; 3]| {
+            let (sx, cx) = x.sin_cos();
+            let (sy, cy) = y.sin_cos();
+            let (sz, cz) = z.sin_cos();
+            let rx = array![[1.0, 0.0, 0.0], [0.0, cx, -sx], [0.0, sx, cx]];
+            let ry = array![[cy, 0.0, sy], [0.0, 1.0, 0.0], [-sy, 0.0, cy]];
+            let rz = array![[cz, -sz, 0.0], [sz, cz, 0.0], [0.0, 0.0, 1.0]];
+            rz.dot(&ry).dot(&rx)
+        };
+        let angles = [0.3, -0.5, 1.2];
+        let scale = [1.5, 0.8, 1.1];
+        let shear = [0.2, -0.1, 0.3];
+        let k = array![
+            [1.0, shear[0], shear[1]],
+            [0.0, 1.0, shear[2]],
+            [0.0, 0.0, 1.0]
+        ];
+        let m =
+            rotation(angles).dot(&Array2::from_diag(&array![scale[0], scale[1], scale[2]]).dot(&k));
+        let mut parameters = [0.0; 12];
+        for (p, x) in parameters.iter_mut().zip(m.iter()) {
+            *p = *x;
+        }
+        let t = Transform3D::new(parameters, [0.0; 3], [10, 10, 10]);
+        let (a, b, c) = t.decompose();
+        for (u, v) in [(a, angles), (b, scale), (c, shear)] {
+            assert!(u.iter().zip(v).all(|(u, v)| (u - v).abs() < 1e-12));
+        }
+
+        // gimbal lock
+        let m = rotation([0.4, std::f64::consts::FRAC_PI_2, 0.0]);
+        for (p, x) in parameters.iter_mut().zip(m.iter()) {
+            *p = *x;
+        }
+        let (a, b, c) = Transform3D::new(parameters, [0.0; 3], [10, 10, 10]).decompose();
+        assert!((rotation(a) - m).iter().all(|x| x.abs() < 1e-12));
+        assert!(b.iter().all(|x| (x - 1.0).abs() < 1e-12));
+        assert!(c.iter().all(|x| x.abs() < 1e-12));
+    }
+
+    #[test]
+    fn from_euler_translation() -> Result<()> {
+        let t = Transform3D::from_euler_translation(
+            0.0,
+            0.0,
+            std::f64::consts::FRAC_PI_2,
+            1.0,
+            2.0,
+            3.0,
+            [0.0; 3],
+            [10, 10, 10],
+        );
+        let c = t.transform_coordinates(&array![[1.0, 0.0, 0.0]])?;
+        assert!(
+            (c - array![[1.0, 3.0, 3.0]])
+                .iter()
+                .all(|x| x.abs() < 1e-12)
+        );
+
+        let t = Transform3D::from_euler_translation(
+            0.3,
+            -0.5,
+            1.2,
+            1.0,
+            2.0,
+            3.0,
+            [1.0, 2.0, 3.0],
+            [10, 20, 30],
+        );
+        assert_eq!(t.origin, [1.0, 2.0, 3.0]);
+        assert_eq!(t.shape, [10, 20, 30]);
+        assert_eq!(t.parameters[9..], [1.0, 2.0, 3.0]);
+        let (angles, scale, shear) = t.decompose();
+        for (a, b) in angles.iter().zip([0.3, -0.5, 1.2]) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        assert!(scale.iter().all(|s| (s - 1.0).abs() < 1e-12));
+        assert!(shear.iter().all(|s| s.abs() < 1e-12));
+        Ok(())
+    }
+
+    #[test]
+    fn serialization_3d() -> Result<()> {
+        let t = Transform3D::new(
+            [
+                1.2, 0.1, -0.3, 0.2, 0.9, 0.1, -0.1, 0.3, 1.1, 4.0, -2.0, 1.5,
+            ],
+            [10.0, 20.0, 30.0],
+            [20, 40, 60],
+        );
+        let file = NamedTempFile::new()?;
+        t.to_file(file.path().to_path_buf())?;
+        assert_eq!(Transform3D::from_file(file.path().to_path_buf())?, t);
+        assert_eq!(Transform3D::from_yaml_str(&t.to_yaml_string()?)?, t);
+
+        // 2D and 3D transforms cannot be mixed up
+        let u = Transform::new([1.2, 0.3, -0.4, 0.9, 10.2, -9.5], [59.5, 49.5], [120, 100]);
+        u.to_file(file.path().to_path_buf())?;
+        assert!(Transform3D::from_file(file.path().to_path_buf()).is_err());
+        t.to_file(file.path().to_path_buf())?;
+        assert!(Transform::from_file(file.path().to_path_buf()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn to_2d_slice() -> Result<()> {
+        // rotation about z, and a scale of z
+        let (sin, cos) = 0.3f64.sin_cos();
+        let t = Transform3D::new(
+            [cos, -sin, 0.0, sin, cos, 0.0, 0.0, 0.0, 2.0, 1.0, 2.0, 3.0],
+            [10.0, 20.0, 30.0],
+            [20, 40, 60],
+        );
+        let u = t.to_2d_slice(0)?;
+        assert_eq!(
+            u,
+            Transform::new([cos, -sin, sin, cos, 1.0, 2.0], [10.0, 20.0], [40, 60])
+        );
+        // the other slices mix x and y
+        assert!(t.to_2d_slice(1).is_err());
+        assert!(t.to_2d_slice(2).is_err());
+        assert!(t.to_2d_slice(3).is_err());
+
+        let t = Transform3D::new(
+            [1.0, 0.0, 0.0, 0.0, 1.5, 0.2, 0.0, 0.1, 2.0, 1.0, 2.0, 3.0],
+            [10.0, 20.0, 30.0],
+            [20, 40, 60],
+        );
+        assert_eq!(
+            t.to_2d_slice(2)?,
+            Transform::new([1.5, 0.2, 0.1, 2.0, 2.0, 3.0], [20.0, 30.0], [20, 40])
+        );
+        assert!(t.to_2d_slice(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn mul_3d() -> Result<()> {
+        let t = Transform3D::new(
+            [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 2.0, 1.0, 2.0, 3.0],
+            [0.0; 3],
+            [10, 10, 10],
+        );
+        let u = Transform3D::new(
+            [1.0, 0.5, 0.0, 0.0, 1.0, 0.0, 0.2, 0.0, 1.0, -1.0, 0.0, 4.0],
+            [0.0; 3],
+            [10, 10, 10],
+        );
+        let points = array![[1.0, 0.0, 0.0], [0.0, 1.0, 1.0], [2.0, 3.0, -4.0]];
+        let a = (t.clone() * u.clone()).transform_coordinates(&points)?;
+        let b = t.transform_coordinates(&u.transform_coordinates(&points)?)?;
+        assert!((a - b).iter().all(|x| x.abs() < 1e-12));
+        Ok(())
+    }
+
+    #[test]
+    fn transform_image_3d() -> Result<()> {
+        let shift = [3.0, -2.0, 1.0];
+        let j = blobs_image([0.0; 3]);
+        let k = blobs_image(shift);
+        let t = Transform3D::new(
+            [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 3.0, -2.0, 1.0],
+            [23.5, 23.5, 23.5],
+            [48, 48, 48],
+        );
+        let interior = s![4..44, 4..44, 4..44];
+        let n = t.transform_image_nearest_neighbor(k.view())?;
+        assert_eq!(n.shape(), j.shape());
+        assert!(
+            n.slice(interior)
+                .iter()
+                .zip(j.slice(interior))
+                .all(|(a, b)| (a - b).abs() < 1e-9)
+        );
+        let b = t.transform_image_bspline(k.view())?;
+        assert!(
+            b.slice(interior)
+                .iter()
+                .zip(j.slice(interior))
+                .all(|(a, b)| (a - b).abs() < 1e-4)
+        );
+        // a non-contiguous view gives the same result
+        let kt = k.t().as_standard_layout().to_owned();
+        assert_eq!(t.transform_image_nearest_neighbor(kt.t())?, n);
+        Ok(())
+    }
+
+    #[test]
+    fn registration_3d_segmentation_guided() -> Result<()> {
+        let shift = [3.0, -2.0, 1.5];
+        let j = blobs_image([0.0; 3]);
+        let k = blobs_image(shift);
+        let label = |image: &Array3<f64>| image.mapv(|v| (v > 50.0) as u8);
+        let (jl, kl) = (label(&j), label(&k));
+        let t = Transform3D::register_affine_3d_segmentation_guided(
+            j.view(),
+            k.view(),
+            jl.view(),
+            kl.view(),
+        )?;
+        assert_eq!(t.shape, [48, 48, 48]);
+        assert_eq!(t.origin, [23.5, 23.5, 23.5]);
+        // the squared deviation of the matrix from the identity, and the largest deviation of the
+        // translation from shift
+        let error = |t: &Transform3D| {
+            let m = t.matrix();
+            let d = (&m.slice(s![..3, ..3]) - &Array2::<f64>::eye(3))
+                .powi(2)
+                .sum();
+            let e = (0..3)
+                .map(|i| (m[[i, 3]] - shift[i]).abs())
+                .fold(0f64, f64::max);
+            (d, e)
+        };
+        let (d, e) = error(&t);
+        assert!(d < 1e-3);
+        assert!(e < 0.2);
+        // the labels agree with the images, so they should not make the result worse than
+        // registration of the images alone
+        let (d_plain, e_plain) = error(&Transform::register_affine_3d(j.view(), k.view())?);
+        assert!(d <= d_plain + 1e-4);
+        assert!(e <= e_plain + 0.05);
+
+        let empty = Array3::<u8>::zeros((48, 48, 48));
+        assert!(
+            Transform3D::register_affine_3d_segmentation_guided(
+                j.view(),
+                k.view(),
+                jl.view(),
+                empty.view()
+            )
+            .is_err()
+        );
+        assert!(
+            Transform3D::register_affine_3d_segmentation_guided(
+                j.view(),
+                k.view(),
+                jl.view(),
+                kl.slice(s![1.., .., ..])
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+}